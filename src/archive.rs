@@ -0,0 +1,202 @@
+use crate::metadata::CoreMetadata;
+use flate2::read::GzDecoder;
+use std::error::Error;
+use std::io::{Cursor, Read};
+
+#[derive(Debug)]
+enum ArchiveError {
+    UnsupportedFormat(String),
+    MetadataNotFound(String),
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedFormat(name) => write!(f, "unsupported archive format: {}", name),
+            Self::MetadataNotFound(name) => write!(f, "no core metadata found in {}", name),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ArchiveFile {
+    pub(crate) path: String,
+    pub(crate) content: Vec<u8>,
+}
+
+impl ArchiveFile {
+    pub(crate) fn size(&self) -> u64 {
+        self.content.len() as u64
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Archive {
+    pub(crate) filename: String,
+    pub(crate) files: Vec<ArchiveFile>,
+}
+
+impl Archive {
+    pub(crate) fn read(filename: &str, data: &[u8]) -> Result<Archive, Box<dyn Error>> {
+        let files = if filename.ends_with(".tar.gz") {
+            read_tar_gz(data)?
+        } else if filename.ends_with(".whl") {
+            read_zip(data)?
+        } else {
+            return Result::Err(ArchiveError::UnsupportedFormat(filename.to_string()).into());
+        };
+
+        Result::Ok(Archive {
+            filename: filename.to_string(),
+            files,
+        })
+    }
+
+    pub(crate) fn core_metadata(&self) -> Result<CoreMetadata, Box<dyn Error>> {
+        // An sdist carries its metadata in PKG-INFO at the root of the top-level directory, a
+        // wheel in METADATA inside its .dist-info directory.
+        let file = self
+            .files
+            .iter()
+            .find(|x| {
+                let parts: Vec<&str> = x.path.trim_start_matches("./").split('/').collect();
+                parts.len() == 2
+                    && (parts[1] == "PKG-INFO"
+                        || (parts[0].ends_with(".dist-info") && parts[1] == "METADATA"))
+            })
+            .ok_or_else(|| ArchiveError::MetadataNotFound(self.filename.clone()))?;
+
+        Result::Ok(CoreMetadata::parse(
+            String::from_utf8_lossy(&file.content).as_ref(),
+        ))
+    }
+}
+
+fn read_tar_gz(data: &[u8]) -> Result<Vec<ArchiveFile>, Box<dyn Error>> {
+    let mut archive = tar::Archive::new(GzDecoder::new(data));
+    let mut files: Vec<ArchiveFile> = Vec::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path()?.to_string_lossy().to_string();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        files.push(ArchiveFile { path, content });
+    }
+
+    Result::Ok(files)
+}
+
+fn read_zip(data: &[u8]) -> Result<Vec<ArchiveFile>, Box<dyn Error>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+    let mut files: Vec<ArchiveFile> = Vec::new();
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+
+        if !entry.is_file() {
+            continue;
+        }
+
+        let path = entry.name().to_string();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        files.push(ArchiveFile { path, content });
+    }
+
+    Result::Ok(files)
+}
+
+/// Writes an sdist named `filename` that holds nothing but the PKG-INFO of `name` `version`.
+#[cfg(test)]
+pub(crate) fn write_sdist(
+    dir: &std::path::Path,
+    filename: &str,
+    name: &str,
+    version: &str,
+) -> std::path::PathBuf {
+    write_sdist_requiring(dir, filename, name, version, &[])
+}
+
+/// Like `write_sdist`, with a `Requires-Dist` header in the PKG-INFO for each of `requires_dist`.
+#[cfg(test)]
+pub(crate) fn write_sdist_requiring(
+    dir: &std::path::Path,
+    filename: &str,
+    name: &str,
+    version: &str,
+    requires_dist: &[&str],
+) -> std::path::PathBuf {
+    use flate2::write::GzEncoder;
+
+    let path = dir.join(filename);
+    let mut pkg_info = format!(
+        "Metadata-Version: 2.1\nName: {}\nVersion: {}\n",
+        name, version
+    );
+
+    for requirement in requires_dist {
+        pkg_info.push_str(&format!("Requires-Dist: {}\n", requirement));
+    }
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(pkg_info.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+
+    let mut builder = tar::Builder::new(GzEncoder::new(
+        std::fs::File::create(&path).unwrap(),
+        flate2::Compression::default(),
+    ));
+    builder
+        .append_data(
+            &mut header,
+            format!("{}-{}/PKG-INFO", name, version),
+            pkg_info.as_bytes(),
+        )
+        .unwrap();
+    builder.into_inner().unwrap().finish().unwrap();
+
+    path
+}
+
+/// Writes a pure python wheel that holds nothing but the METADATA and RECORD of `name` `version`.
+#[cfg(test)]
+pub(crate) fn write_wheel(dir: &std::path::Path, name: &str, version: &str) -> std::path::PathBuf {
+    use std::io::Write;
+
+    let path = dir.join(format!("{}-{}-py3-none-any.whl", name, version));
+    let dist_info = format!("{}-{}.dist-info", name, version);
+    let metadata = format!(
+        "Metadata-Version: 2.1\nName: {}\nVersion: {}\n",
+        name, version
+    );
+    let record = format!(
+        "{}/METADATA,sha256={},{}\n{}/RECORD,,\n",
+        dist_info,
+        crate::digest::sha256_record(metadata.as_bytes()),
+        metadata.len(),
+        dist_info
+    );
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+
+    for (file, content) in [("METADATA", metadata), ("RECORD", record)] {
+        zip.start_file(
+            format!("{}/{}", dist_info, file),
+            zip::write::FileOptions::default(),
+        )
+        .unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+
+    zip.finish().unwrap();
+
+    path
+}
@@ -1,18 +1,94 @@
+use crate::config::AwsSettings;
+use aws_config::meta::credentials::LazyCachingCredentialsProvider;
 use aws_config::meta::region::RegionProviderChain;
+use aws_config::profile::{ProfileFileCredentialsProvider, ProfileFileRegionProvider};
+use aws_config::sso::SsoCredentialsProvider;
+use aws_config::sts::AssumeRoleProvider;
 use aws_sdk_s3::{Client, Region};
+use aws_types::credentials::SharedCredentialsProvider;
 use simple_error::bail;
 use std::error::Error;
 
-pub(crate) async fn get_s3_client() -> Result<Client, Box<dyn Error>> {
-    let region_name = "us-west-2";
-    let region_provider = RegionProviderChain::first_try(Region::new(region_name));
-    match region_provider.region().await {
-        None => bail!(format!(
-            "the AWS region provided is invalid: {}",
-            region_name
+const DEFAULT_REGION: &str = "us-west-2";
+const DEFAULT_SESSION_NAME: &str = "sling";
+
+/// Builds an S3 client for a profile. Credentials come from the default provider chain unless
+/// the profile names an AWS profile or an SSO account, and are then used to assume `role_arn`
+/// when one is given.
+pub(crate) async fn get_s3_client(settings: &AwsSettings) -> Result<Client, Box<dyn Error>> {
+    let region_provider = match (&settings.region, &settings.profile) {
+        (Some(region), _) => RegionProviderChain::first_try(Region::new(region.clone())),
+        (None, Some(profile)) => RegionProviderChain::first_try(
+            ProfileFileRegionProvider::builder()
+                .profile_name(profile)
+                .build(),
+        )
+        .or_else(Region::new(DEFAULT_REGION)),
+        (None, None) => RegionProviderChain::first_try(Region::new(DEFAULT_REGION)),
+    };
+
+    let region = match region_provider.region().await {
+        None => bail!("no AWS region could be resolved"),
+        Some(region) => region,
+    };
+
+    let credentials = match (&settings.sso, &settings.profile) {
+        (Some(sso), _) => Some(SharedCredentialsProvider::new(
+            SsoCredentialsProvider::builder()
+                .start_url(&sso.start_url)
+                .account_id(&sso.account_id)
+                .role_name(&sso.role_name)
+                .region(
+                    sso.region
+                        .clone()
+                        .map(Region::new)
+                        .unwrap_or_else(|| region.clone()),
+                )
+                .build(),
         )),
-        Some(_) => Ok(Client::new(
-            &aws_config::from_env().region(region_provider).load().await,
+        (None, Some(profile)) => Some(SharedCredentialsProvider::new(
+            ProfileFileCredentialsProvider::builder()
+                .profile_name(profile)
+                .build(),
         )),
+        (None, None) => None,
+    };
+
+    let credentials = match &settings.role_arn {
+        Some(role_arn) => {
+            let source = match credentials {
+                Some(credentials) => credentials,
+                None => SharedCredentialsProvider::new(
+                    aws_config::default_provider::credentials::default_provider().await,
+                ),
+            };
+            let mut builder = AssumeRoleProvider::builder(role_arn)
+                .region(region.clone())
+                .session_name(
+                    settings
+                        .role_session_name
+                        .as_deref()
+                        .unwrap_or(DEFAULT_SESSION_NAME),
+                );
+
+            if let Some(external_id) = &settings.external_id {
+                builder = builder.external_id(external_id);
+            }
+
+            Some(SharedCredentialsProvider::new(builder.build(source)))
+        }
+        None => credentials,
+    };
+
+    let mut loader = aws_config::from_env().region(region);
+
+    if let Some(credentials) = credentials {
+        loader = loader.credentials_provider(
+            LazyCachingCredentialsProvider::builder()
+                .load(credentials)
+                .build(),
+        );
     }
+
+    Ok(Client::new(&loader.load().await))
 }
@@ -0,0 +1,239 @@
+use crate::storage::driver::StorageDriver;
+use bytes::Bytes;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+/// Location of the metadata document sling maintains alongside the packages in a bucket.
+pub(crate) const METADATA_KEY: &str = ".sling/metadata.json";
+
+pub(crate) const LATEST_TAG: &str = "latest";
+
+const MAX_UPDATE_ATTEMPTS: usize = 10;
+
+#[derive(Debug)]
+enum BucketMetadataError {
+    Conflict(String),
+}
+
+impl std::fmt::Display for BucketMetadataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Conflict(bucket) => write!(
+                f,
+                "metadata of {} kept changing while it was being updated",
+                bucket
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BucketMetadataError {}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub(crate) struct BucketMetadata {
+    #[serde(default)]
+    pub(crate) packages: BTreeMap<String, PackageMetadata>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub(crate) struct PackageMetadata {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) yanked: BTreeMap<String, Yank>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) tags: BTreeMap<String, String>,
+    /// The index the project was cached from by `serve --upstream`. Projects without it were
+    /// published to the bucket directly and are never merged with upstream files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) upstream: Option<String>,
+}
+
+impl PackageMetadata {
+    fn is_empty(&self) -> bool {
+        self.yanked.is_empty() && self.tags.is_empty() && self.upstream.is_none()
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub(crate) struct Yank {
+    pub(crate) reason: Option<String>,
+}
+
+impl BucketMetadata {
+    pub(crate) async fn load(
+        driver: &'_ dyn StorageDriver,
+        bucket: &str,
+    ) -> Result<BucketMetadata, Box<dyn Error>> {
+        let data = driver.get(bucket, METADATA_KEY).await?;
+        serde_json::from_slice(&data).map_err(|e| e.into())
+    }
+
+    /// Applies a change to the current metadata of a bucket and writes it back unless another
+    /// writer updated it in the meantime, in which case the change is applied again to what they
+    /// wrote. Nothing is written when the change leaves the metadata as it was.
+    pub(crate) async fn update<F>(
+        driver: &'_ dyn StorageDriver,
+        bucket: &str,
+        mut change: F,
+    ) -> Result<BucketMetadata, Box<dyn Error>>
+    where
+        F: FnMut(&mut BucketMetadata) + Send,
+    {
+        for _ in 0..MAX_UPDATE_ATTEMPTS {
+            let (original, revision) = match driver.get_revision(bucket, METADATA_KEY).await? {
+                Some((data, revision)) => (serde_json::from_slice(&data)?, Some(revision)),
+                None => (BucketMetadata::default(), None),
+            };
+            let mut metadata = original.clone();
+
+            change(&mut metadata);
+
+            if metadata == original {
+                return Result::Ok(metadata);
+            }
+
+            let data = Bytes::from(serde_json::to_vec_pretty(&metadata)?);
+
+            if driver
+                .put_if_unchanged(bucket, METADATA_KEY, data, revision.as_deref())
+                .await?
+            {
+                return Result::Ok(metadata);
+            }
+        }
+
+        Result::Err(BucketMetadataError::Conflict(bucket.to_string()).into())
+    }
+
+    pub(crate) fn get_yank(&self, name: &str, version: &str) -> Option<&Yank> {
+        self.packages.get(name).and_then(|x| x.yanked.get(version))
+    }
+
+    pub(crate) fn yank(&mut self, name: &str, version: &str, reason: Option<String>) {
+        self.packages
+            .entry(name.to_string())
+            .or_default()
+            .yanked
+            .insert(version.to_string(), Yank { reason });
+    }
+
+    pub(crate) fn unyank(&mut self, name: &str, version: &str) -> bool {
+        let removed = match self.packages.get_mut(name) {
+            Some(package) => package.yanked.remove(version).is_some(),
+            None => false,
+        };
+
+        self.packages.retain(|_, x| !x.is_empty());
+        removed
+    }
+
+    pub(crate) fn get_tag(&self, name: &str, tag: &str) -> Option<&String> {
+        self.packages.get(name).and_then(|x| x.tags.get(tag))
+    }
+
+    pub(crate) fn get_tags(&self, name: &str, version: &str) -> Vec<String> {
+        match self.packages.get(name) {
+            Some(package) => package
+                .tags
+                .iter()
+                .filter(|x| x.1 == version)
+                .map(|x| x.0.clone())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub(crate) fn tag(&mut self, name: &str, version: &str, tag: &str) {
+        self.packages
+            .entry(name.to_string())
+            .or_default()
+            .tags
+            .insert(tag.to_string(), version.to_string());
+    }
+
+    pub(crate) fn untag(&mut self, name: &str, tag: &str) -> bool {
+        let removed = match self.packages.get_mut(name) {
+            Some(package) => package.tags.remove(tag).is_some(),
+            None => false,
+        };
+
+        self.packages.retain(|_, x| !x.is_empty());
+        removed
+    }
+
+    pub(crate) fn get_upstream(&self, name: &str) -> Option<&String> {
+        self.packages.get(name).and_then(|x| x.upstream.as_ref())
+    }
+
+    pub(crate) fn set_upstream(&mut self, name: &str, url: &str) {
+        self.packages.entry(name.to_string()).or_default().upstream = Some(url.to_string());
+    }
+
+    /// Drops every record that refers to a version which no longer exists.
+    pub(crate) fn remove_version(&mut self, name: &str, version: &str) -> bool {
+        let mut removed = self.unyank(name, version);
+
+        for tag in self.get_tags(name, version) {
+            removed = self.untag(name, &tag) || removed;
+        }
+
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryStorageDriver;
+
+    #[tokio::test]
+    async fn test_update_retries_after_concurrent_write() {
+        let driver = MemoryStorageDriver::default();
+        let mut attempts = 0;
+
+        BucketMetadata::update(&driver, "bucket", |x| x.tag("pkg", "1.0.0", LATEST_TAG))
+            .await
+            .unwrap();
+
+        let metadata = BucketMetadata::update(&driver, "bucket", |x| {
+            attempts += 1;
+
+            // Another writer sneaks in between the read and the write of the first attempt
+            if attempts == 1 {
+                let mut other = BucketMetadata::default();
+                other.tag("pkg", "1.0.0", LATEST_TAG);
+                other.set_upstream("other", "https://pypi.org/simple");
+                driver.insert(
+                    "bucket",
+                    METADATA_KEY,
+                    &serde_json::to_string(&other).unwrap(),
+                );
+            }
+
+            x.yank("pkg", "1.0.0", None);
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(attempts, 2);
+        assert_eq!(
+            metadata,
+            BucketMetadata::load(&driver, "bucket").await.unwrap()
+        );
+        assert!(metadata.get_yank("pkg", "1.0.0").is_some());
+        assert!(metadata.get_upstream("other").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_update_skips_unchanged() {
+        let driver = MemoryStorageDriver::default();
+
+        BucketMetadata::update(&driver, "bucket", |x| {
+            x.untag("pkg", LATEST_TAG);
+        })
+        .await
+        .unwrap();
+
+        assert!(driver.keys("bucket").is_empty());
+    }
+}
@@ -0,0 +1,437 @@
+use crate::digest;
+use crate::index::Index;
+use crate::install::download_package;
+use crate::package::Package;
+use crate::package_version::PackageVersion;
+use crate::{pip, publish, yank, RuntimeConfig, StorageDriver};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const MANIFEST_PATH: &str = "manifest.json";
+const PACKAGES_DIR: &str = "packages";
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+enum BundleError {
+    NoBucketDefined,
+    PackageNotFound(String),
+    ManifestMissing,
+    UnsupportedFormat(u32),
+    FileMissing(String),
+    UnexpectedFile(String),
+    DuplicateFile(String),
+    InvalidPath(String),
+    HashMismatch(String, String, String),
+}
+
+impl std::fmt::Display for BundleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoBucketDefined => write!(f, "no bucket was provided"),
+            Self::PackageNotFound(pkg) => write!(f, "package not found: {}", pkg),
+            Self::ManifestMissing => write!(f, "bundle does not contain {}", MANIFEST_PATH),
+            Self::UnsupportedFormat(version) => {
+                write!(f, "unsupported bundle format version: {}", version)
+            }
+            Self::FileMissing(filename) => write!(f, "bundle is missing {}", filename),
+            Self::UnexpectedFile(path) => write!(f, "bundle contains unlisted file {}", path),
+            Self::DuplicateFile(filename) => {
+                write!(f, "bundle manifest lists {} more than once", filename)
+            }
+            Self::InvalidPath(path) => {
+                write!(f, "bundle manifest contains an invalid path: {}", path)
+            }
+            Self::HashMismatch(filename, expected, actual) => write!(
+                f,
+                "sha256 mismatch for {}: expected {}, got {}",
+                filename, expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Manifest {
+    format_version: u32,
+    created: String,
+    source: String,
+    packages: Vec<ManifestEntry>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ManifestEntry {
+    name: String,
+    version: String,
+    filename: String,
+    sha256: String,
+    size: u64,
+}
+
+struct Bundle {
+    manifest: Manifest,
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+/// Resolves the packages against the bucket and writes them, along with a manifest of their
+/// digests, to a single tar file that can be carried to a machine without network access.
+pub(crate) async fn export(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    packages: Vec<Package>,
+    output: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let bucket = if config.bucket.is_none() {
+        return Result::Err(BundleError::NoBucketDefined.into());
+    } else {
+        config.bucket.clone().unwrap()
+    };
+
+    let index = Index::from_storage_bucket(driver, bucket.as_str()).await?;
+    let dir = tempdir::TempDir::new("sling-")?;
+    let mut entries: Vec<ManifestEntry> = Vec::new();
+
+    for package in packages {
+        let entry = index
+            .resolve(&package)
+            .ok_or_else(|| BundleError::PackageNotFound(package.to_string()))?;
+
+        if entries
+            .iter()
+            .any(|x| x.name == entry.name && x.version == entry.version)
+        {
+            continue;
+        }
+
+        if !matches!(package.version, PackageVersion::Literal(_)) {
+            println!(
+                "Resolved package version: {} -> {}@{}",
+                package, entry.name, entry.version
+            );
+        }
+
+        yank::warn_if_yanked(&index, &entry);
+
+        let filename = entry.object.filename().to_string();
+        let target = dir.path().join(&filename);
+        download_package(driver, entry.clone(), &target).await?;
+
+        let data = std::fs::read(&target)?;
+        entries.push(ManifestEntry {
+            name: entry.name.clone(),
+            version: entry.version.clone(),
+            filename,
+            sha256: digest::sha256(&data),
+            size: data.len() as u64,
+        });
+    }
+
+    let manifest = Manifest {
+        format_version: FORMAT_VERSION,
+        created: chrono::Utc::now().to_rfc3339(),
+        source: format!("{}://{}", driver.get_protocol(), bucket),
+        packages: entries,
+    };
+
+    let mut builder = tar::Builder::new(File::create(output)?);
+    append_file(
+        &mut builder,
+        MANIFEST_PATH,
+        &serde_json::to_vec_pretty(&manifest)?,
+    )?;
+
+    for entry in manifest.packages.iter() {
+        builder.append_path_with_name(
+            dir.path().join(&entry.filename),
+            format!("{}/{}", PACKAGES_DIR, entry.filename),
+        )?;
+    }
+
+    builder.into_inner()?;
+
+    println!(
+        "Exported {} package(s) to {}",
+        manifest.packages.len(),
+        output.display()
+    );
+
+    Result::Ok(())
+}
+
+/// Verifies every file in a bundle against its manifest, then either publishes the packages to
+/// the bucket or installs them with pip in the order they were exported.
+pub(crate) async fn import(
+    config: &RuntimeConfig,
+    driver: Option<&'_ dyn StorageDriver>,
+    path: &Path,
+    overwrite: bool,
+    update_latest: bool,
+) -> Result<(), Box<dyn Error>> {
+    let Bundle {
+        manifest,
+        mut files,
+    } = read_bundle(path)?;
+
+    println!(
+        "Verified {} package(s) from {} (Created={})",
+        manifest.packages.len(),
+        manifest.source,
+        manifest.created
+    );
+
+    let dir = tempdir::TempDir::new("sling-")?;
+
+    let driver = match driver {
+        Some(driver) => driver,
+        None => {
+            let python = config
+                .python
+                .clone()
+                .unwrap_or_else(|| String::from("python"));
+            let pip_args = config.pip_args.clone().unwrap_or_else(|| String::from(""));
+
+            for entry in manifest.packages.iter() {
+                let target = dir.path().join(&entry.filename);
+                std::fs::write(&target, take_file(&mut files, &entry.filename)?)?;
+                pip::install_package(&python, &pip_args, &target, &[])?;
+            }
+
+            return Result::Ok(());
+        }
+    };
+
+    let bucket = if config.bucket.is_none() {
+        return Result::Err(BundleError::NoBucketDefined.into());
+    } else {
+        config.bucket.clone().unwrap()
+    };
+
+    let index = Index::from_storage_bucket(driver, bucket.as_str()).await?;
+    let mut paths: Vec<PathBuf> = Vec::new();
+
+    for entry in manifest.packages.iter() {
+        let key = format!("{}/{}", entry.name, entry.filename);
+
+        if !overwrite && index.find_file(&key).is_some() {
+            println!(
+                "Skipping {}@{}: already published",
+                entry.name, entry.version
+            );
+            continue;
+        }
+
+        let target = dir.path().join(&entry.filename);
+        std::fs::write(&target, take_file(&mut files, &entry.filename)?)?;
+        paths.push(target);
+    }
+
+    if paths.is_empty() {
+        return Result::Ok(());
+    }
+
+    let options = publish::PublishOptions {
+        overwrite,
+        update_latest,
+        jobs: 4,
+    };
+
+    publish::publish(config, driver, &paths, options).await
+}
+
+fn take_file(
+    files: &mut BTreeMap<String, Vec<u8>>,
+    filename: &str,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    files
+        .remove(filename)
+        .ok_or_else(|| BundleError::FileMissing(filename.to_string()).into())
+}
+
+fn read_bundle(path: &Path) -> Result<Bundle, Box<dyn Error>> {
+    let mut archive = tar::Archive::new(File::open(path)?);
+    let mut manifest: Option<Manifest> = None;
+    let mut files: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let name = entry.path()?.to_string_lossy().to_string();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+
+        if name == MANIFEST_PATH {
+            manifest = Some(serde_json::from_slice(&content)?);
+        } else {
+            match name.strip_prefix(&format!("{}/", PACKAGES_DIR)) {
+                Some(filename) => files.insert(filename.to_string(), content),
+                None => return Result::Err(BundleError::UnexpectedFile(name).into()),
+            };
+        }
+    }
+
+    let manifest = manifest.ok_or(BundleError::ManifestMissing)?;
+
+    if manifest.format_version != FORMAT_VERSION {
+        return Result::Err(BundleError::UnsupportedFormat(manifest.format_version).into());
+    }
+
+    let mut listed: HashSet<&str> = HashSet::new();
+
+    for entry in manifest.packages.iter() {
+        // Both end up in paths on disk and object keys, so neither may leave its directory
+        for component in [&entry.name, &entry.filename] {
+            if !is_plain_name(component) {
+                return Result::Err(BundleError::InvalidPath(component.clone()).into());
+            }
+        }
+
+        if !listed.insert(entry.filename.as_str()) {
+            return Result::Err(BundleError::DuplicateFile(entry.filename.clone()).into());
+        }
+
+        let data = files
+            .get(&entry.filename)
+            .ok_or_else(|| BundleError::FileMissing(entry.filename.clone()))?;
+        let actual = digest::sha256(data);
+
+        if actual != entry.sha256 {
+            return Result::Err(
+                BundleError::HashMismatch(entry.filename.clone(), entry.sha256.clone(), actual)
+                    .into(),
+            );
+        }
+    }
+
+    if let Some(filename) = files
+        .keys()
+        .find(|x| !manifest.packages.iter().any(|y| &y.filename == *x))
+    {
+        return Result::Err(
+            BundleError::UnexpectedFile(format!("{}/{}", PACKAGES_DIR, filename)).into(),
+        );
+    }
+
+    Result::Ok(Bundle { manifest, files })
+}
+
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '\0'])
+}
+
+fn append_file<W: std::io::Write>(
+    builder: &mut tar::Builder<W>,
+    path: &str,
+    data: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(chrono::Utc::now().timestamp() as u64);
+    header.set_cksum();
+    builder.append_data(&mut header, path, data)?;
+
+    Result::Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &[u8] = b"pkg";
+
+    fn manifest_entry(name: &str, filename: &str) -> ManifestEntry {
+        ManifestEntry {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            filename: filename.to_string(),
+            sha256: digest::sha256(DATA),
+            size: DATA.len() as u64,
+        }
+    }
+
+    /// Writes a bundle whose manifest lists `packages` and which contains `files`, bypassing
+    /// the checks `export` would apply.
+    fn write_bundle(path: &Path, packages: Vec<ManifestEntry>, files: &[&str]) {
+        let manifest = Manifest {
+            format_version: FORMAT_VERSION,
+            created: "2024-01-01T00:00:00Z".to_string(),
+            source: "s3://bucket".to_string(),
+            packages,
+        };
+        let mut builder = tar::Builder::new(File::create(path).unwrap());
+        append_file(
+            &mut builder,
+            MANIFEST_PATH,
+            &serde_json::to_vec(&manifest).unwrap(),
+        )
+        .unwrap();
+
+        for file in files {
+            // append_file refuses paths with "..", so write the name into the header directly
+            let mut header = tar::Header::new_gnu();
+            header.as_gnu_mut().unwrap().name[..file.len()].copy_from_slice(file.as_bytes());
+            header.set_size(DATA.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, DATA).unwrap();
+        }
+
+        builder.finish().unwrap();
+    }
+
+    #[test]
+    fn test_read_bundle_duplicate() {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let path = dir.path().join("bundle.tar");
+        let entry = manifest_entry("pkg", "pkg-1.0.0.tar.gz");
+
+        write_bundle(&path, vec![entry.clone()], &["packages/pkg-1.0.0.tar.gz"]);
+        assert!(read_bundle(&path).is_ok());
+
+        write_bundle(
+            &path,
+            vec![entry.clone(), entry],
+            &["packages/pkg-1.0.0.tar.gz"],
+        );
+        assert_eq!(
+            read_bundle(&path).err().unwrap().to_string(),
+            "bundle manifest lists pkg-1.0.0.tar.gz more than once"
+        );
+    }
+
+    #[test]
+    fn test_read_bundle_path_traversal() {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let path = dir.path().join("bundle.tar");
+
+        for (name, filename) in [
+            ("pkg", "../pkg-1.0.0.tar.gz"),
+            ("pkg", "nested/pkg-1.0.0.tar.gz"),
+            ("pkg", ".."),
+            ("../other", "pkg-1.0.0.tar.gz"),
+        ] {
+            write_bundle(
+                &path,
+                vec![manifest_entry(name, filename)],
+                &[&format!("packages/{}", filename)],
+            );
+
+            let error = read_bundle(&path).err().unwrap().to_string();
+            assert!(
+                error.starts_with("bundle manifest contains an invalid path"),
+                "{}: {}",
+                filename,
+                error
+            );
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use crate::archive::Archive;
+use crate::digest;
+use crate::distribution::{Distribution, DistributionKind};
+use crate::semantic_version::SemanticVersion;
+use crate::simple::normalize_name;
+use std::str::FromStr;
+
+const METADATA_VERSIONS: [&str; 8] = ["1.0", "1.1", "1.2", "2.0", "2.1", "2.2", "2.3", "2.4"];
+
+/// Checks a distribution the way `twine check` would before it is uploaded, returning every
+/// problem found. An empty result means the file is fine to publish.
+pub(crate) fn check(distribution: &Distribution, archive: &Archive) -> Vec<String> {
+    let mut problems: Vec<String> = Vec::new();
+
+    match archive.core_metadata() {
+        Result::Ok(metadata) => {
+            match metadata.metadata_version() {
+                Some(version) if METADATA_VERSIONS.contains(&version) => (),
+                Some(version) => problems.push(format!("invalid Metadata-Version {}", version)),
+                None => problems.push("missing Metadata-Version".to_string()),
+            }
+
+            match metadata.name() {
+                Some(name) if normalize_name(name) == normalize_name(&distribution.name) => (),
+                Some(name) => problems.push(format!(
+                    "metadata name {} does not match filename {}",
+                    name, distribution.filename
+                )),
+                None => problems.push("missing Name".to_string()),
+            }
+
+            match metadata.version() {
+                Some(version) if same_version(version, &distribution.version) => (),
+                Some(version) => problems.push(format!(
+                    "metadata version {} does not match filename {}",
+                    version, distribution.filename
+                )),
+                None => problems.push("missing Version".to_string()),
+            }
+        }
+        Result::Err(e) => problems.push(e.to_string()),
+    }
+
+    if distribution.kind == DistributionKind::Wheel {
+        problems.append(&mut check_record(archive));
+    }
+
+    problems
+}
+
+fn same_version(a: &str, b: &str) -> bool {
+    match (SemanticVersion::from_str(a), SemanticVersion::from_str(b)) {
+        (Result::Ok(a), Result::Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Verifies every file of a wheel against the digests and sizes listed in its RECORD.
+fn check_record(archive: &Archive) -> Vec<String> {
+    let record = match archive.files.iter().find(|x| {
+        let parts: Vec<&str> = x.path.split('/').collect();
+        parts.len() == 2 && parts[0].ends_with(".dist-info") && parts[1] == "RECORD"
+    }) {
+        Some(record) => record,
+        None => return vec!["missing RECORD".to_string()],
+    };
+
+    let mut problems: Vec<String> = Vec::new();
+    let mut listed: Vec<String> = Vec::new();
+
+    for line in String::from_utf8_lossy(&record.content).lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields = split_csv_line(line);
+        let (path, hash, size) = match fields.as_slice() {
+            [path, hash, size] => (path, hash, size),
+            _ => {
+                problems.push(format!("malformed RECORD line: {}", line));
+                continue;
+            }
+        };
+
+        listed.push(path.clone());
+
+        if *path == record.path {
+            continue;
+        }
+
+        let file = match archive.files.iter().find(|x| x.path == *path) {
+            Some(file) => file,
+            None => {
+                problems.push(format!("{} is listed in RECORD but missing", path));
+                continue;
+            }
+        };
+
+        match hash.split_once('=') {
+            Some(("sha256", expected)) => {
+                if digest::sha256_record(&file.content) != expected {
+                    problems.push(format!("{} does not match its RECORD hash", path));
+                }
+            }
+            Some((algorithm, _)) => {
+                problems.push(format!("{} uses unsupported hash {}", path, algorithm))
+            }
+            None => problems.push(format!("{} has no hash in RECORD", path)),
+        }
+
+        if !size.is_empty() && size.parse::<u64>().ok() != Some(file.size()) {
+            problems.push(format!("{} does not match its RECORD size", path));
+        }
+    }
+
+    for file in archive.files.iter() {
+        // Signatures of RECORD cannot be listed in the file they sign
+        let signature = file.path.ends_with("/RECORD.jws") || file.path.ends_with("/RECORD.p7s");
+
+        if !signature && !listed.contains(&file.path) {
+            problems.push(format!("{} is not listed in RECORD", file.path));
+        }
+    }
+
+    problems
+}
+
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::ArchiveFile;
+
+    fn wheel(files: Vec<(&str, &str)>) -> Archive {
+        Archive {
+            filename: "my_pkg-1.0.0-py3-none-any.whl".to_string(),
+            files: files
+                .into_iter()
+                .map(|(path, content)| ArchiveFile {
+                    path: path.to_string(),
+                    content: content.as_bytes().to_vec(),
+                })
+                .collect(),
+        }
+    }
+
+    fn record_line(path: &str, content: &str) -> String {
+        format!(
+            "{},sha256={},{}",
+            path,
+            digest::sha256_record(content.as_bytes()),
+            content.len()
+        )
+    }
+
+    #[test]
+    fn test_check_wheel() {
+        let distribution = Distribution::from_filename("my_pkg-1.0.0-py3-none-any.whl").unwrap();
+        let metadata = "Metadata-Version: 2.1\nName: My.Pkg\nVersion: 1.0.0\n";
+        let module = "print('hello')\n";
+        let record = format!(
+            "{}\n{}\nmy_pkg-1.0.0.dist-info/RECORD,,\n",
+            record_line("my_pkg-1.0.0.dist-info/METADATA", metadata),
+            record_line("my_pkg/__init__.py", module)
+        );
+
+        let valid = wheel(vec![
+            ("my_pkg-1.0.0.dist-info/METADATA", metadata),
+            ("my_pkg/__init__.py", module),
+            ("my_pkg-1.0.0.dist-info/RECORD", &record),
+        ]);
+        assert!(check(&distribution, &valid).is_empty());
+
+        let tampered = wheel(vec![
+            ("my_pkg-1.0.0.dist-info/METADATA", metadata),
+            ("my_pkg/__init__.py", "print('HELLO')\n"),
+            ("my_pkg/extra.py", ""),
+            ("my_pkg-1.0.0.dist-info/RECORD", &record),
+        ]);
+        assert_eq!(
+            check(&distribution, &tampered),
+            vec![
+                "my_pkg/__init__.py does not match its RECORD hash",
+                "my_pkg/extra.py is not listed in RECORD",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_metadata() {
+        let distribution = Distribution::from_filename("my_pkg-1.0.0.tar.gz").unwrap();
+        let archive = Archive {
+            filename: distribution.filename.clone(),
+            files: vec![ArchiveFile {
+                path: "my_pkg-1.0.0/PKG-INFO".to_string(),
+                content: b"Metadata-Version: 3.0\nName: other\nVersion: 1.0.1\n".to_vec(),
+            }],
+        };
+
+        assert_eq!(
+            check(&distribution, &archive),
+            vec![
+                "invalid Metadata-Version 3.0",
+                "metadata name other does not match filename my_pkg-1.0.0.tar.gz",
+                "metadata version 1.0.1 does not match filename my_pkg-1.0.0.tar.gz",
+            ]
+        );
+
+        // Written by older releases of bdist_wheel
+        let archive = Archive {
+            filename: distribution.filename.clone(),
+            files: vec![ArchiveFile {
+                path: "my_pkg-1.0.0/PKG-INFO".to_string(),
+                content: b"Metadata-Version: 2.0\nName: my-pkg\nVersion: 1.0.0\n".to_vec(),
+            }],
+        };
+
+        assert!(check(&distribution, &archive).is_empty());
+    }
+
+    #[test]
+    fn test_split_csv_line() {
+        assert_eq!(
+            split_csv_line("\"a,\"\"b\"\".py\",sha256=x,1"),
+            vec!["a,\"b\".py", "sha256=x", "1"]
+        );
+    }
+}
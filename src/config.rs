@@ -1,11 +1,78 @@
 use crate::{yaml, RuntimeConfig};
 use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub(crate) struct Config {
     pub(crate) default_bucket_name: Option<String>,
     pub(crate) default_pip_args: Option<String>,
     pub(crate) default_python_interpreter: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) profiles: BTreeMap<String, Profile>,
+    /// Tokens accepted by the upload endpoint of `sling serve`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) upload_tokens: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub(crate) struct Profile {
+    pub(crate) driver: Option<String>,
+    pub(crate) bucket: Option<String>,
+    pub(crate) pip_args: Option<String>,
+    pub(crate) python: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) gcp: Option<GcpSettings>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) aws: Option<AwsSettings>,
+}
+
+/// How the gs driver authenticates.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub(crate) struct GcpSettings {
+    /// `adc` (the default) to use Application Default Credentials, `metadata` to only use the
+    /// metadata server, `anonymous` to send no credentials, or the path to a credentials JSON file.
+    pub(crate) credentials: Option<String>,
+    /// Storage API endpoint, i.e. an emulator such as fake-gcs-server. `STORAGE_EMULATOR_HOST`
+    /// takes precedence when set.
+    pub(crate) endpoint: Option<String>,
+    /// Service account to impersonate with the resolved credentials.
+    pub(crate) impersonate_service_account: Option<String>,
+    /// Delegation chain used when impersonating.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) delegates: Vec<String>,
+}
+
+/// How the s3 driver authenticates.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub(crate) struct AwsSettings {
+    /// Named profile from `~/.aws/config` and `~/.aws/credentials`.
+    pub(crate) profile: Option<String>,
+    pub(crate) region: Option<String>,
+    /// Role to assume with the resolved credentials.
+    pub(crate) role_arn: Option<String>,
+    pub(crate) role_session_name: Option<String>,
+    pub(crate) external_id: Option<String>,
+    /// Sign in through AWS SSO instead. Requires a cached token from `aws sso login`.
+    pub(crate) sso: Option<AwsSsoSettings>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub(crate) struct AwsSsoSettings {
+    pub(crate) start_url: String,
+    pub(crate) account_id: String,
+    pub(crate) role_name: String,
+    /// Region of the SSO portal, when it differs from the bucket's.
+    pub(crate) region: Option<String>,
+}
+
+impl Profile {
+    pub fn as_runtime_config(&self) -> RuntimeConfig {
+        RuntimeConfig {
+            bucket: self.bucket.clone(),
+            pip_args: self.pip_args.clone(),
+            python: self.python.clone(),
+        }
+    }
 }
 
 impl Config {
@@ -17,6 +84,8 @@ impl Config {
                 default_bucket_name: None,
                 default_pip_args: None,
                 default_python_interpreter: None,
+                profiles: BTreeMap::new(),
+                upload_tokens: Vec::new(),
             })
         }
     }
@@ -44,6 +113,8 @@ mod tests {
             default_bucket_name: Some("abc".to_string()),
             default_pip_args: None,
             default_python_interpreter: None,
+            profiles: BTreeMap::new(),
+            upload_tokens: Vec::new(),
         };
         assert_eq!(
             config.as_runtime_config(),
@@ -0,0 +1,298 @@
+use crate::bucket_metadata::BucketMetadata;
+use crate::index::{Entry, Index};
+use crate::package::Package;
+use crate::package_version::PackageVersion;
+use crate::{RuntimeConfig, StorageDriver};
+use std::error::Error;
+use std::io::Write;
+
+#[derive(Debug)]
+enum DeleteError {
+    NoBucketDefined,
+    VersionRequired(String),
+    PackageNotFound(String),
+    OnlyVersion(String),
+    LatestVersion(String),
+    AllVersions(String),
+}
+
+impl std::fmt::Display for DeleteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoBucketDefined => write!(f, "no bucket was provided"),
+            Self::VersionRequired(pkg) => write!(
+                f,
+                "a version or requirement is required (use --all to delete every version): {}",
+                pkg
+            ),
+            Self::PackageNotFound(pkg) => write!(f, "package not found: {}", pkg),
+            Self::OnlyVersion(pkg) => write!(
+                f,
+                "refusing to delete the only version of a package without --force: {}",
+                pkg
+            ),
+            Self::LatestVersion(pkg) => write!(
+                f,
+                "refusing to delete the latest version of a package without --force: {}",
+                pkg
+            ),
+            Self::AllVersions(pkg) => write!(
+                f,
+                "refusing to delete every version of a package without --force: {}",
+                pkg
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DeleteError {}
+
+pub(crate) struct DeleteOptions {
+    pub(crate) all: bool,
+    pub(crate) dry_run: bool,
+    pub(crate) force: bool,
+    pub(crate) yes: bool,
+}
+
+pub(crate) async fn delete(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    package: &Package,
+    options: DeleteOptions,
+) -> Result<(), Box<dyn Error>> {
+    let bucket = if config.bucket.is_none() {
+        return Result::Err(DeleteError::NoBucketDefined.into());
+    } else {
+        config.bucket.clone().unwrap()
+    };
+
+    let index = Index::from_storage_bucket(driver, bucket.as_str()).await?;
+    let available = index.list_versions(&package.name);
+    let targets: Vec<Entry> = match &package.version {
+        _ if options.all => available.clone(),
+        PackageVersion::Literal(version) => available
+            .iter()
+            .filter(|x| x.version == *version)
+            .cloned()
+            .collect(),
+        PackageVersion::Requirement(requirement) => available
+            .iter()
+            .filter(|x| {
+                x.semantic_version()
+                    .is_some_and(|v| requirement.matches(&v))
+            })
+            .cloned()
+            .collect(),
+        PackageVersion::Tag(_) => index.resolve(package).into_iter().collect(),
+        PackageVersion::Latest => {
+            return Result::Err(DeleteError::VersionRequired(package.name.clone()).into())
+        }
+    };
+
+    if targets.is_empty() {
+        return Result::Err(DeleteError::PackageNotFound(package.to_string()).into());
+    }
+
+    if !options.force {
+        if options.all {
+            return Result::Err(DeleteError::AllVersions(package.name.clone()).into());
+        }
+
+        if targets.len() == available.len() {
+            return Result::Err(DeleteError::OnlyVersion(package.name.clone()).into());
+        }
+
+        if let Some(latest) = index.find_latest(&package.name) {
+            if targets.iter().any(|x| x.version == latest.version) {
+                return Result::Err(
+                    DeleteError::LatestVersion(format!("{}@{}", latest.name, latest.version))
+                        .into(),
+                );
+            }
+        }
+    }
+
+    for entry in targets.iter() {
+        for file in entry.files.iter() {
+            println!(
+                "{}Delete {}@{} ({})",
+                if options.dry_run { "[dry-run] " } else { "" },
+                entry.name,
+                entry.version,
+                file.get_object_url()
+            );
+        }
+    }
+
+    if options.dry_run {
+        return Result::Ok(());
+    }
+
+    if !options.yes
+        && !confirm(&format!(
+            "Delete {} package file(s)?",
+            targets.iter().map(|x| x.files.len()).sum::<usize>()
+        ))?
+    {
+        println!("Aborted");
+        return Result::Ok(());
+    }
+
+    delete_entries(driver, bucket.as_str(), &targets).await
+}
+
+pub(crate) async fn delete_entries(
+    driver: &'_ dyn StorageDriver,
+    bucket: &str,
+    entries: &[Entry],
+) -> Result<(), Box<dyn Error>> {
+    for entry in entries {
+        for file in entry.files.iter() {
+            println!("Deleting {}", file.get_object_url());
+            driver.delete(bucket, file.key.as_str()).await?;
+        }
+    }
+
+    BucketMetadata::update(driver, bucket, |metadata| {
+        for entry in entries {
+            metadata.remove_version(&entry.name, &entry.version);
+        }
+    })
+    .await?;
+
+    Result::Ok(())
+}
+
+pub(crate) fn confirm(prompt: &str) -> Result<bool, Box<dyn Error>> {
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    Result::Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bucket_metadata::{BucketMetadata, LATEST_TAG};
+    use crate::storage::memory::MemoryStorageDriver;
+    use std::str::FromStr;
+
+    async fn setup() -> (RuntimeConfig, MemoryStorageDriver) {
+        let driver = MemoryStorageDriver::default();
+
+        for version in ["1.0.0", "1.1.0", "2.0.0"] {
+            driver.insert("bucket", &format!("pkg/pkg-{}.tar.gz", version), "content");
+        }
+
+        BucketMetadata::update(&driver, "bucket", |x| x.tag("pkg", "1.1.0", LATEST_TAG))
+            .await
+            .unwrap();
+
+        let config = RuntimeConfig {
+            bucket: Some("bucket".to_string()),
+            ..RuntimeConfig::default()
+        };
+
+        (config, driver)
+    }
+
+    fn options(all: bool, force: bool) -> DeleteOptions {
+        DeleteOptions {
+            all,
+            dry_run: false,
+            force,
+            yes: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_latest() {
+        let (config, driver) = setup().await;
+        let package = Package::from_str("pkg@1.1.0").unwrap();
+
+        let error = delete(&config, &driver, &package, options(false, false))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "refusing to delete the latest version of a package without --force: pkg@1.1.0"
+        );
+        assert!(driver
+            .keys("bucket")
+            .contains(&"pkg/pkg-1.1.0.tar.gz".to_string()));
+
+        delete(&config, &driver, &package, options(false, true))
+            .await
+            .unwrap();
+        assert!(!driver
+            .keys("bucket")
+            .contains(&"pkg/pkg-1.1.0.tar.gz".to_string()));
+
+        let metadata = BucketMetadata::load(&driver, "bucket").await.unwrap();
+        assert_eq!(metadata.get_tag("pkg", LATEST_TAG), None);
+    }
+
+    #[tokio::test]
+    async fn test_delete_version() {
+        let (config, driver) = setup().await;
+
+        delete(
+            &config,
+            &driver,
+            &Package::from_str("pkg@2.0.0").unwrap(),
+            options(false, false),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            driver.keys("bucket"),
+            vec![
+                ".sling/metadata.json",
+                "pkg/pkg-1.0.0.tar.gz",
+                "pkg/pkg-1.1.0.tar.gz"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_all() {
+        let (config, driver) = setup().await;
+        let package = Package::from_str("pkg").unwrap();
+
+        let error = delete(&config, &driver, &package, options(false, false))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("a version or requirement"));
+
+        let error = delete(&config, &driver, &package, options(true, false))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "refusing to delete every version of a package without --force: pkg"
+        );
+
+        let error = delete(
+            &config,
+            &driver,
+            &Package::from_str("pkg@>=1.0.0").unwrap(),
+            options(false, false),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "refusing to delete the only version of a package without --force: pkg"
+        );
+        assert_eq!(driver.keys("bucket").len(), 4);
+
+        delete(&config, &driver, &package, options(true, true))
+            .await
+            .unwrap();
+        assert_eq!(driver.keys("bucket"), vec![".sling/metadata.json"]);
+    }
+}
@@ -0,0 +1,14 @@
+use sha2::{Digest, Sha256};
+
+pub(crate) fn md5(data: &[u8]) -> String {
+    format!("{:x}", md5::compute(data))
+}
+
+pub(crate) fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Formats a sha256 digest the way wheel RECORD files do: urlsafe base64 without padding.
+pub(crate) fn sha256_record(data: &[u8]) -> String {
+    base64::encode_config(Sha256::digest(data), base64::URL_SAFE_NO_PAD)
+}
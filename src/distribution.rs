@@ -0,0 +1,98 @@
+use crate::package::Package;
+use crate::package_version::PackageVersion;
+use crate::semantic_version::VERSION_PATTERN;
+use regex::Regex;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum DistributionKind {
+    Sdist,
+    Wheel,
+}
+
+/// A single distribution file of a package release, either a source distribution or a wheel.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Distribution {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    pub(crate) filename: String,
+    pub(crate) kind: DistributionKind,
+}
+
+impl Distribution {
+    /// Parses `name-1.0.0.tar.gz` and `name-1.0.0[-build]-python-abi-platform.whl` filenames.
+    /// Dots in a wheel's project name are folded to underscores, matching the bucket layout.
+    pub(crate) fn from_filename(filename: &str) -> Option<Distribution> {
+        let sdist = Regex::new(&format!(
+            r"^([a-zA-Z0-9_]+)-({})\.tar\.gz$",
+            VERSION_PATTERN
+        ))
+        .unwrap();
+        let wheel = Regex::new(&format!(
+            r"^([a-zA-Z0-9_.]+)-({})(?:-\d[a-zA-Z0-9_.]*)?-[a-zA-Z0-9_.]+-[a-zA-Z0-9_.]+-[a-zA-Z0-9_.]+\.whl$",
+            VERSION_PATTERN
+        ))
+        .unwrap();
+
+        let (captures, kind) = if let Some(captures) = sdist.captures(filename) {
+            (captures, DistributionKind::Sdist)
+        } else {
+            (wheel.captures(filename)?, DistributionKind::Wheel)
+        };
+
+        Some(Distribution {
+            name: captures[1].replace('.', "_"),
+            version: captures[2].to_string(),
+            filename: filename.to_string(),
+            kind,
+        })
+    }
+
+    pub(crate) fn as_package(&self) -> Package {
+        Package {
+            name: self.name.clone(),
+            version: PackageVersion::Literal(self.version.clone()),
+            extras: Vec::new(),
+        }
+    }
+
+    pub(crate) fn object_key(&self) -> String {
+        format!("{}/{}", self.name, self.filename)
+    }
+
+    /// Orders the files of a release by how suitable they are as the one sling installs when
+    /// asked for that release: source distributions, then pure Python wheels, then the rest.
+    pub(crate) fn install_rank(&self) -> usize {
+        match self.kind {
+            DistributionKind::Sdist => 0,
+            DistributionKind::Wheel if self.filename.ends_with("-none-any.whl") => 1,
+            DistributionKind::Wheel => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_filename() {
+        let sdist = Distribution::from_filename("my_pkg-1.2.0.tar.gz").unwrap();
+        assert_eq!(sdist.name, "my_pkg");
+        assert_eq!(sdist.version, "1.2.0");
+        assert_eq!(sdist.kind, DistributionKind::Sdist);
+        assert_eq!(sdist.object_key(), "my_pkg/my_pkg-1.2.0.tar.gz");
+
+        let wheel = Distribution::from_filename(
+            "zope.interface-5.4.0rc1-1-cp39-cp39-manylinux1_x86_64.whl",
+        )
+        .unwrap();
+        assert_eq!(wheel.name, "zope_interface");
+        assert_eq!(wheel.version, "5.4.0rc1");
+        assert_eq!(wheel.kind, DistributionKind::Wheel);
+        assert_eq!(wheel.install_rank(), 2);
+
+        assert!(Distribution::from_filename("my-pkg-1.2.0.tar.gz").is_none());
+        assert!(Distribution::from_filename("my_pkg-1.2.0.zip").is_none());
+        assert!(Distribution::from_filename("my_pkg-1.2.0-py3-none.whl").is_none());
+    }
+}
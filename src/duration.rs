@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+#[derive(Debug)]
+pub(crate) enum ParseDurationError {
+    InvalidFormat(String),
+}
+
+impl std::fmt::Display for ParseDurationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::InvalidFormat(s) => write!(
+                f,
+                "invalid duration (expected a number followed by s, m, h, d or w): {}",
+                s
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseDurationError {}
+
+pub(crate) fn parse_duration(s: &str) -> Result<Duration, ParseDurationError> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| ParseDurationError::InvalidFormat(s.to_string()))?;
+    let (amount, unit) = s.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| ParseDurationError::InvalidFormat(s.to_string()))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Result::Err(ParseDurationError::InvalidFormat(s.to_string())),
+    };
+
+    Result::Ok(Duration::from_secs(amount * seconds))
+}
+
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    for (unit, size) in [
+        ("w", 7 * 24 * 60 * 60),
+        ("d", 24 * 60 * 60),
+        ("h", 60 * 60),
+        ("m", 60),
+    ] {
+        if seconds >= size && seconds.is_multiple_of(size) {
+            return format!("{}{}", seconds / size, unit);
+        }
+    }
+
+    format!("{}s", seconds)
+}
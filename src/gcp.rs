@@ -1,16 +1,728 @@
+use crate::config::GcpSettings;
+use crate::http;
+use chrono::{DateTime, Duration, Utc};
+use hyper::{header, Body, Method, Request};
+use serde_derive::Deserialize;
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::path::{Path, PathBuf};
 
-pub(crate) async fn get_gs_client() -> Result<google_storage1::Storage, Box<dyn Error>> {
-    let var = std::env::var("GOOGLE_APPLICATION_CREDENTIALS").map_err(|e| {
-        simple_error::SimpleError::new(format!(
-            "failed to read GOOGLE_APPLICATION_CREDENTIALS: {}",
-            e
-        ))
-    })?;
-    let key = yup_oauth2::read_service_account_key(std::path::Path::new(var.as_str())).await?;
-    let builder = yup_oauth2::ServiceAccountAuthenticator::builder(key);
-    Ok(google_storage1::Storage::new(
-        hyper::Client::builder().build(hyper_rustls::HttpsConnector::with_native_roots()),
-        builder.build().await?,
-    ))
+const SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const IAM_CREDENTIALS_URL: &str = "https://iamcredentials.googleapis.com/v1";
+const DEFAULT_METADATA_HOST: &str = "169.254.169.254";
+
+#[derive(Debug)]
+enum GcpError {
+    CredentialsNotFound,
+    InvalidCredentialsFile(String, String),
+    UnsupportedCredentialSource(String),
+    SubjectTokenNotFound(String),
+    TokenRequestFailed(String, String),
+    SigningUnsupported,
+    InvalidPrivateKey(String),
+}
+
+impl std::fmt::Display for GcpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::CredentialsNotFound => write!(
+                f,
+                "no Google Cloud credentials found (set GOOGLE_APPLICATION_CREDENTIALS, run `gcloud auth application-default login` or run on Google Cloud)"
+            ),
+            Self::InvalidCredentialsFile(path, msg) => {
+                write!(f, "invalid credentials file {}: {}", path, msg)
+            }
+            Self::UnsupportedCredentialSource(source) => {
+                write!(f, "unsupported external account credential source: {}", source)
+            }
+            Self::SubjectTokenNotFound(source) => {
+                write!(f, "no subject token found in {}", source)
+            }
+            Self::TokenRequestFailed(url, msg) => {
+                write!(f, "token request to {} failed: {}", url, msg)
+            }
+            Self::SigningUnsupported => write!(
+                f,
+                "signing requires service account credentials (a key file, the metadata server or impersonation)"
+            ),
+            Self::InvalidPrivateKey(msg) => write!(f, "invalid service account key: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for GcpError {}
+
+struct Token {
+    value: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[async_trait::async_trait]
+trait TokenSource: Send + Sync {
+    async fn fetch(&self) -> Result<Token, Box<dyn Error>>;
+
+    /// The email of the service account that signs on behalf of these credentials.
+    async fn service_account(&self) -> Result<String, Box<dyn Error>> {
+        Result::Err(GcpError::SigningUnsupported.into())
+    }
+
+    /// Signs `data` with RSA-SHA256 as the service account.
+    async fn sign(&self, _data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        Result::Err(GcpError::SigningUnsupported.into())
+    }
+}
+
+/// Access tokens for Google Cloud APIs, refreshed shortly before they expire.
+pub(crate) struct Credentials {
+    source: Box<dyn TokenSource>,
+    cached: tokio::sync::Mutex<Option<Token>>,
+}
+
+impl Credentials {
+    fn new(source: Box<dyn TokenSource>) -> Credentials {
+        Credentials {
+            source,
+            cached: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    pub(crate) async fn token(&self) -> Result<String, Box<dyn Error>> {
+        let mut cached = self.cached.lock().await;
+
+        if let Some(token) = cached.as_ref() {
+            if token.expires_at - Duration::minutes(1) > Utc::now() {
+                return Result::Ok(token.value.clone());
+            }
+        }
+
+        let token = self.source.fetch().await?;
+        let value = token.value.clone();
+        *cached = Some(token);
+
+        Result::Ok(value)
+    }
+
+    pub(crate) async fn service_account(&self) -> Result<String, Box<dyn Error>> {
+        self.source.service_account().await
+    }
+
+    pub(crate) async fn sign(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.source.sign(data).await
+    }
+}
+
+/// Resolves the credentials selected by a profile, or `None` for anonymous access. Without
+/// settings this follows the Application Default Credentials chain: `GOOGLE_APPLICATION_CREDENTIALS`,
+/// the file written by `gcloud auth application-default login`, then the metadata server. Emulators
+/// set through `STORAGE_EMULATOR_HOST` are accessed anonymously unless credentials are chosen.
+pub(crate) async fn get_credentials(
+    settings: &GcpSettings,
+) -> Result<Option<Credentials>, Box<dyn Error>> {
+    let emulated = std::env::var("STORAGE_EMULATOR_HOST")
+        .map(|x| !x.is_empty())
+        .unwrap_or(false);
+    let source = match settings.credentials.as_deref() {
+        Some("anonymous") => return Result::Ok(None),
+        None if emulated => return Result::Ok(None),
+        None | Some("adc") => application_default().await?,
+        Some("metadata") => Box::new(MetadataServer::new()),
+        Some(path) => from_file(Path::new(path))?,
+    };
+
+    let source = match &settings.impersonate_service_account {
+        Some(account) => Box::new(Impersonated {
+            source,
+            url: impersonation_url(account),
+            delegates: settings.delegates.clone(),
+        }),
+        None => source,
+    };
+
+    Result::Ok(Some(Credentials::new(source)))
+}
+
+async fn application_default() -> Result<Box<dyn TokenSource>, Box<dyn Error>> {
+    if let Some(path) = std::env::var_os("GOOGLE_APPLICATION_CREDENTIALS") {
+        return from_file(Path::new(&path));
+    }
+
+    if let Some(path) = well_known_file().filter(|x| x.exists()) {
+        return from_file(&path);
+    }
+
+    let metadata = MetadataServer::new();
+
+    if metadata.is_available().await {
+        return Result::Ok(Box::new(metadata));
+    }
+
+    Result::Err(GcpError::CredentialsNotFound.into())
+}
+
+/// The user credentials file written by `gcloud auth application-default login`.
+fn well_known_file() -> Option<PathBuf> {
+    let dir = match std::env::var_os("CLOUDSDK_CONFIG") {
+        Some(dir) => PathBuf::from(dir),
+        None if cfg!(windows) => dirs::config_dir()?.join("gcloud"),
+        None => dirs::home_dir()?.join(".config").join("gcloud"),
+    };
+
+    Some(dir.join("application_default_credentials.json"))
+}
+
+fn from_file(path: &Path) -> Result<Box<dyn TokenSource>, Box<dyn Error>> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        GcpError::InvalidCredentialsFile(path.display().to_string(), e.to_string())
+    };
+    let content = std::fs::read(path).map_err(|e| invalid(&e))?;
+    let file: CredentialsFile = serde_json::from_slice(&content).map_err(|e| invalid(&e))?;
+
+    Result::Ok(file.into_source())
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum CredentialsFile {
+    ServiceAccount(yup_oauth2::ServiceAccountKey),
+    AuthorizedUser(AuthorizedUser),
+    ExternalAccount(ExternalAccount),
+    ImpersonatedServiceAccount(ImpersonatedServiceAccount),
+}
+
+impl CredentialsFile {
+    fn into_source(self) -> Box<dyn TokenSource> {
+        match self {
+            Self::ServiceAccount(key) => Box::new(ServiceAccount { key }),
+            Self::AuthorizedUser(user) => Box::new(user),
+            Self::ExternalAccount(account) => Box::new(account),
+            Self::ImpersonatedServiceAccount(account) => Box::new(Impersonated {
+                source: account.source_credentials.into_source(),
+                url: account.service_account_impersonation_url,
+                delegates: account.delegates,
+            }),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ImpersonatedServiceAccount {
+    service_account_impersonation_url: String,
+    #[serde(default)]
+    delegates: Vec<String>,
+    source_credentials: Box<CredentialsFile>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<i64>,
+}
+
+async fn request_token(request: Request<Body>) -> Result<Token, Box<dyn Error>> {
+    let url = request.uri().to_string();
+    let (status, body) = http::send(request).await?;
+
+    if !status.is_success() {
+        return Result::Err(
+            GcpError::TokenRequestFailed(
+                url,
+                format!("{} {}", status, String::from_utf8_lossy(&body).trim()),
+            )
+            .into(),
+        );
+    }
+
+    let response: TokenResponse = serde_json::from_slice(&body)?;
+
+    Result::Ok(Token {
+        value: response.access_token,
+        expires_at: Utc::now() + Duration::seconds(response.expires_in.unwrap_or(3600)),
+    })
+}
+
+fn form_request(url: &str, fields: &[(&str, &str)]) -> Result<Request<Body>, hyper::http::Error> {
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(fields)
+        .finish();
+
+    Request::post(url)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(body))
+}
+
+struct ServiceAccount {
+    key: yup_oauth2::ServiceAccountKey,
+}
+
+#[async_trait::async_trait]
+impl TokenSource for ServiceAccount {
+    async fn fetch(&self) -> Result<Token, Box<dyn Error>> {
+        let authenticator = yup_oauth2::ServiceAccountAuthenticator::builder(self.key.clone())
+            .build()
+            .await?;
+        let token = authenticator.token(&[SCOPE]).await?;
+
+        Result::Ok(Token {
+            value: token.as_str().to_string(),
+            expires_at: token
+                .expiration_time()
+                .unwrap_or_else(|| Utc::now() + Duration::hours(1)),
+        })
+    }
+
+    async fn service_account(&self) -> Result<String, Box<dyn Error>> {
+        Result::Ok(self.key.client_email.clone())
+    }
+
+    async fn sign(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let der = base64::decode(
+            self.key
+                .private_key
+                .lines()
+                .filter(|x| !x.starts_with("-----"))
+                .collect::<String>(),
+        )?;
+        let key = ring::signature::RsaKeyPair::from_pkcs8(&der)
+            .map_err(|e| GcpError::InvalidPrivateKey(e.to_string()))?;
+        let mut signature = vec![0; key.public_modulus_len()];
+
+        key.sign(
+            &ring::signature::RSA_PKCS1_SHA256,
+            &ring::rand::SystemRandom::new(),
+            data,
+            &mut signature,
+        )
+        .map_err(|e| GcpError::InvalidPrivateKey(e.to_string()))?;
+
+        Result::Ok(signature)
+    }
+}
+
+/// User credentials from `gcloud auth application-default login`.
+#[derive(Deserialize)]
+struct AuthorizedUser {
+    client_id: String,
+    client_secret: String,
+    refresh_token: String,
+}
+
+#[async_trait::async_trait]
+impl TokenSource for AuthorizedUser {
+    async fn fetch(&self) -> Result<Token, Box<dyn Error>> {
+        request_token(form_request(
+            TOKEN_URL,
+            &[
+                ("grant_type", "refresh_token"),
+                ("client_id", &self.client_id),
+                ("client_secret", &self.client_secret),
+                ("refresh_token", &self.refresh_token),
+            ],
+        )?)
+        .await
+    }
+}
+
+/// The metadata server available on GCE, GKE (including workload identity) and Cloud Run.
+struct MetadataServer {
+    host: String,
+}
+
+impl MetadataServer {
+    fn new() -> MetadataServer {
+        MetadataServer {
+            host: std::env::var("GCE_METADATA_HOST")
+                .unwrap_or_else(|_| DEFAULT_METADATA_HOST.to_string()),
+        }
+    }
+
+    fn request(&self, path: &str) -> Result<Request<Body>, hyper::http::Error> {
+        Request::get(format!("http://{}/computeMetadata/v1/{}", self.host, path))
+            .header("Metadata-Flavor", "Google")
+            .body(Body::empty())
+    }
+
+    async fn is_available(&self) -> bool {
+        let request = match self.request("") {
+            Result::Ok(request) => request,
+            Result::Err(_) => return false,
+        };
+        let response = tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            http::client().request(request),
+        )
+        .await;
+
+        matches!(response, Result::Ok(Result::Ok(response))
+            if response.headers().get("Metadata-Flavor").map(|x| x == "Google").unwrap_or(false))
+    }
+}
+
+#[async_trait::async_trait]
+impl TokenSource for MetadataServer {
+    async fn fetch(&self) -> Result<Token, Box<dyn Error>> {
+        request_token(self.request(&format!(
+            "instance/service-accounts/default/token?scopes={}",
+            urlencoding::encode(SCOPE)
+        ))?)
+        .await
+    }
+
+    async fn service_account(&self) -> Result<String, Box<dyn Error>> {
+        let request = self.request("instance/service-accounts/default/email")?;
+        let url = request.uri().to_string();
+        let (status, body) = http::send(request).await?;
+
+        if !status.is_success() {
+            return Result::Err(GcpError::TokenRequestFailed(url, status.to_string()).into());
+        }
+
+        Result::Ok(String::from_utf8_lossy(&body).trim().to_string())
+    }
+
+    async fn sign(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let account = self.service_account().await?;
+        let token = self.fetch().await?;
+
+        sign_blob(&account, &token.value, &[], data).await
+    }
+}
+
+/// Workload identity federation: a token from another identity provider is exchanged for a
+/// Google access token through the Security Token Service.
+#[derive(Deserialize)]
+struct ExternalAccount {
+    audience: String,
+    subject_token_type: String,
+    token_url: String,
+    service_account_impersonation_url: Option<String>,
+    workforce_pool_user_project: Option<String>,
+    credential_source: CredentialSource,
+}
+
+#[derive(Deserialize)]
+struct CredentialSource {
+    file: Option<String>,
+    url: Option<String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    environment_id: Option<String>,
+    format: Option<CredentialSourceFormat>,
+}
+
+#[derive(Deserialize)]
+struct CredentialSourceFormat {
+    #[serde(rename = "type")]
+    kind: String,
+    subject_token_field_name: Option<String>,
+}
+
+impl ExternalAccount {
+    async fn subject_token(&self) -> Result<String, Box<dyn Error>> {
+        let source = &self.credential_source;
+        let (location, content) = match (&source.file, &source.url) {
+            (Some(file), _) => (file.clone(), std::fs::read_to_string(file)?),
+            (None, Some(url)) => {
+                let mut request = Request::get(url.as_str());
+
+                for (name, value) in source.headers.iter() {
+                    request = request.header(name.as_str(), value.as_str());
+                }
+
+                let (status, body) = http::send(request.body(Body::empty())?).await?;
+
+                if !status.is_success() {
+                    return Result::Err(
+                        GcpError::TokenRequestFailed(url.clone(), status.to_string()).into(),
+                    );
+                }
+
+                (url.clone(), String::from_utf8_lossy(&body).into_owned())
+            }
+            (None, None) => {
+                return Result::Err(
+                    GcpError::UnsupportedCredentialSource(
+                        source
+                            .environment_id
+                            .clone()
+                            .unwrap_or_else(|| "executable".to_string()),
+                    )
+                    .into(),
+                )
+            }
+        };
+
+        match &source.format {
+            Some(format) if format.kind == "json" => {
+                let field = format
+                    .subject_token_field_name
+                    .as_deref()
+                    .unwrap_or("access_token");
+                let value: serde_json::Value = serde_json::from_str(&content)?;
+
+                value[field]
+                    .as_str()
+                    .map(|x| x.to_string())
+                    .ok_or_else(|| GcpError::SubjectTokenNotFound(location).into())
+            }
+            _ => Result::Ok(content.trim().to_string()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TokenSource for ExternalAccount {
+    async fn fetch(&self) -> Result<Token, Box<dyn Error>> {
+        let token = self.exchange().await?;
+
+        match &self.service_account_impersonation_url {
+            Some(url) => impersonate(url, &token.value, &[]).await,
+            None => Result::Ok(token),
+        }
+    }
+
+    async fn service_account(&self) -> Result<String, Box<dyn Error>> {
+        self.service_account_impersonation_url
+            .as_deref()
+            .and_then(impersonated_account)
+            .ok_or_else(|| GcpError::SigningUnsupported.into())
+    }
+
+    async fn sign(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let account = self.service_account().await?;
+        let token = self.exchange().await?;
+
+        sign_blob(&account, &token.value, &[], data).await
+    }
+}
+
+impl ExternalAccount {
+    async fn exchange(&self) -> Result<Token, Box<dyn Error>> {
+        let subject_token = self.subject_token().await?;
+        let options = self
+            .workforce_pool_user_project
+            .as_ref()
+            .map(|project| serde_json::json!({ "userProject": project }).to_string());
+        let mut fields = vec![
+            (
+                "grant_type",
+                "urn:ietf:params:oauth:grant-type:token-exchange",
+            ),
+            ("audience", self.audience.as_str()),
+            ("scope", SCOPE),
+            (
+                "requested_token_type",
+                "urn:ietf:params:oauth:token-type:access_token",
+            ),
+            ("subject_token", subject_token.as_str()),
+            ("subject_token_type", self.subject_token_type.as_str()),
+        ];
+
+        if let Some(options) = options.as_deref() {
+            fields.push(("options", options));
+        }
+
+        request_token(form_request(&self.token_url, &fields)?).await
+    }
+}
+
+/// Short-lived credentials for a service account, minted with another identity's token.
+struct Impersonated {
+    source: Box<dyn TokenSource>,
+    url: String,
+    delegates: Vec<String>,
+}
+
+#[async_trait::async_trait]
+impl TokenSource for Impersonated {
+    async fn fetch(&self) -> Result<Token, Box<dyn Error>> {
+        let token = self.source.fetch().await?;
+
+        impersonate(&self.url, &token.value, &self.delegates).await
+    }
+
+    async fn service_account(&self) -> Result<String, Box<dyn Error>> {
+        impersonated_account(&self.url).ok_or_else(|| GcpError::SigningUnsupported.into())
+    }
+
+    async fn sign(&self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        let account = self.service_account().await?;
+        let token = self.source.fetch().await?;
+
+        sign_blob(&account, &token.value, &self.delegates, data).await
+    }
+}
+
+fn impersonation_url(account: &str) -> String {
+    format!(
+        "{}/{}:generateAccessToken",
+        IAM_CREDENTIALS_URL,
+        service_account_resource(account)
+    )
+}
+
+/// Extracts the service account email from a `generateAccessToken` url.
+fn impersonated_account(url: &str) -> Option<String> {
+    let (_, account) = url.rsplit_once("/serviceAccounts/")?;
+    let (account, _) = account.split_once(':')?;
+
+    Some(urlencoding::decode(account).ok()?.into_owned())
+}
+
+fn service_account_resource(account: &str) -> String {
+    if account.contains('/') {
+        account.to_string()
+    } else {
+        format!("projects/-/serviceAccounts/{}", account)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateAccessTokenResponse {
+    access_token: String,
+    expire_time: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignBlobResponse {
+    signed_blob: String,
+}
+
+async fn impersonate(
+    url: &str,
+    token: &str,
+    delegates: &[String],
+) -> Result<Token, Box<dyn Error>> {
+    let body = serde_json::json!({
+        "scope": [SCOPE],
+        "delegates": delegates
+            .iter()
+            .map(|x| service_account_resource(x))
+            .collect::<Vec<String>>(),
+    });
+    let response: GenerateAccessTokenResponse = iam_request(url, token, body).await?;
+
+    Result::Ok(Token {
+        value: response.access_token,
+        expires_at: DateTime::parse_from_rfc3339(&response.expire_time)?.with_timezone(&Utc),
+    })
+}
+
+async fn sign_blob(
+    account: &str,
+    token: &str,
+    delegates: &[String],
+    data: &[u8],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let url = format!(
+        "{}/{}:signBlob",
+        IAM_CREDENTIALS_URL,
+        service_account_resource(account)
+    );
+    let body = serde_json::json!({
+        "payload": base64::encode(data),
+        "delegates": delegates
+            .iter()
+            .map(|x| service_account_resource(x))
+            .collect::<Vec<String>>(),
+    });
+    let response: SignBlobResponse = iam_request(&url, token, body).await?;
+
+    Result::Ok(base64::decode(response.signed_blob)?)
+}
+
+async fn iam_request<T: serde::de::DeserializeOwned>(
+    url: &str,
+    token: &str,
+    body: serde_json::Value,
+) -> Result<T, Box<dyn Error>> {
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))?;
+    let (status, body) = http::send(request).await?;
+
+    if !status.is_success() {
+        return Result::Err(
+            GcpError::TokenRequestFailed(
+                url.to_string(),
+                format!("{} {}", status, String::from_utf8_lossy(&body).trim()),
+            )
+            .into(),
+        );
+    }
+
+    Result::Ok(serde_json::from_slice(&body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_credentials_file() {
+        let file: CredentialsFile = serde_json::from_str(
+            r#"{
+                "type": "impersonated_service_account",
+                "service_account_impersonation_url": "https://example.com/sa:generateAccessToken",
+                "source_credentials": {
+                    "type": "authorized_user",
+                    "client_id": "id",
+                    "client_secret": "secret",
+                    "refresh_token": "refresh"
+                }
+            }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            file,
+            CredentialsFile::ImpersonatedServiceAccount(ImpersonatedServiceAccount {
+                ref source_credentials,
+                ..
+            }) if matches!(**source_credentials, CredentialsFile::AuthorizedUser(_))
+        ));
+
+        let file: CredentialsFile = serde_json::from_str(
+            r#"{
+                "type": "external_account",
+                "audience": "//iam.googleapis.com/projects/1/locations/global/workloadIdentityPools/ci/providers/github",
+                "subject_token_type": "urn:ietf:params:oauth:token-type:jwt",
+                "token_url": "https://sts.googleapis.com/v1/token",
+                "credential_source": {
+                    "url": "https://example.com/token",
+                    "headers": {"Authorization": "bearer abc"},
+                    "format": {"type": "json", "subject_token_field_name": "value"}
+                }
+            }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            file,
+            CredentialsFile::ExternalAccount(ExternalAccount {
+                service_account_impersonation_url: None,
+                ..
+            })
+        ));
+
+        assert!(serde_json::from_str::<CredentialsFile>(r#"{"type": "unknown"}"#).is_err());
+    }
+
+    #[test]
+    fn test_impersonation_url() {
+        assert_eq!(
+            impersonated_account(&impersonation_url(
+                "publisher@project.iam.gserviceaccount.com"
+            ))
+            .as_deref(),
+            Some("publisher@project.iam.gserviceaccount.com")
+        );
+        assert_eq!(
+            impersonation_url("publisher@project.iam.gserviceaccount.com"),
+            "https://iamcredentials.googleapis.com/v1/projects/-/serviceAccounts/publisher@project.iam.gserviceaccount.com:generateAccessToken"
+        );
+    }
 }
@@ -0,0 +1,83 @@
+use bytes::Bytes;
+use hyper::client::HttpConnector;
+use hyper::{header, Body, Client, Request, StatusCode};
+use hyper_rustls::HttpsConnector;
+use std::error::Error;
+use url::Url;
+
+const MAX_REDIRECTS: usize = 5;
+
+pub(crate) const USER_AGENT: &str = concat!("sling/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug)]
+enum HttpError {
+    UnexpectedStatus(String, StatusCode),
+    TooManyRedirects(String),
+}
+
+impl std::fmt::Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::UnexpectedStatus(url, status) => {
+                write!(f, "request to {} failed with status {}", url, status)
+            }
+            Self::TooManyRedirects(url) => write!(f, "too many redirects fetching {}", url),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+pub(crate) fn client() -> Client<HttpsConnector<HttpConnector>, Body> {
+    Client::builder().build(HttpsConnector::with_native_roots())
+}
+
+/// Sends a request without following redirects and returns the status along with the body.
+pub(crate) async fn send(
+    mut request: Request<Body>,
+) -> Result<(StatusCode, Bytes), Box<dyn Error>> {
+    request.headers_mut().insert(
+        header::USER_AGENT,
+        header::HeaderValue::from_static(USER_AGENT),
+    );
+
+    let response = client().request(request).await?;
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+
+    Result::Ok((status, body))
+}
+
+/// Fetches a URL over http or https, following redirects. Returns the final URL along with the
+/// body so relative links in the response can be resolved against it.
+pub(crate) async fn get(url: &Url) -> Result<(Url, Bytes), Box<dyn Error>> {
+    let client = client();
+    let mut url = url.clone();
+
+    for _ in 0..=MAX_REDIRECTS {
+        let request = Request::get(url.as_str())
+            .header(header::USER_AGENT, USER_AGENT)
+            .body(Body::empty())?;
+        let response = client.request(request).await?;
+        let status = response.status();
+
+        if status.is_redirection() {
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|x| x.to_str().ok())
+                .ok_or_else(|| HttpError::UnexpectedStatus(url.to_string(), status))?;
+            url = url.join(location)?;
+            continue;
+        }
+
+        if !status.is_success() {
+            return Result::Err(HttpError::UnexpectedStatus(url.to_string(), status).into());
+        }
+
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        return Result::Ok((url, body));
+    }
+
+    Result::Err(HttpError::TooManyRedirects(url.to_string()).into())
+}
@@ -0,0 +1,466 @@
+use crate::digest;
+use crate::distribution::Distribution;
+use crate::http;
+use crate::package::Package;
+use crate::package_version::PackageVersion;
+use crate::publish;
+use crate::semantic_version::SemanticVersion;
+use crate::simple::{normalize_name, parse_project_page, FileLink};
+use crate::{RuntimeConfig, StorageDriver};
+use std::error::Error;
+use std::str::FromStr;
+use url::Url;
+
+/// PEP 440 versions with any number of release segments, as upstream indexes name their files.
+/// Only those `SemanticVersion` can represent are imported.
+const RELEASE_PATTERN: &str = r"\d+(?:\.\d+)*(?:(?:a|b|rc)\d+|\.dev\d+)?";
+
+#[derive(Debug)]
+enum ImportError {
+    NoMatchingVersion(String),
+    TagsUnsupported(String),
+    HashMissing(String),
+    HashMismatch(String, String, String),
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoMatchingVersion(package) => {
+                write!(f, "no distribution found for {}", package)
+            }
+            Self::TagsUnsupported(package) => {
+                write!(
+                    f,
+                    "tags cannot be resolved against a remote index: {}",
+                    package
+                )
+            }
+            Self::HashMissing(filename) => {
+                write!(f, "index did not provide a sha256 digest for {}", filename)
+            }
+            Self::HashMismatch(filename, expected, actual) => write!(
+                f,
+                "sha256 mismatch for {}: expected {}, got {}",
+                filename, expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+struct Candidate {
+    version: SemanticVersion,
+    distribution: Distribution,
+    link: FileLink,
+}
+
+/// Downloads the source distribution and wheels of a release from a PEP 503 simple index and
+/// publishes them to the bucket. The index must advertise a sha256 digest for every file, which
+/// is verified before upload.
+pub(crate) async fn import(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    package: &Package,
+    index_url: &Url,
+    overwrite: bool,
+    update_latest: bool,
+) -> Result<(), Box<dyn Error>> {
+    let page_url = project_url(index_url, &package.name)?;
+
+    println!("Fetching {}", page_url);
+
+    let (page_url, page) = http::get(&page_url).await?;
+    let links = parse_project_page(String::from_utf8_lossy(&page).as_ref(), &page_url);
+    let candidates = select(&package.name, &package.version, links)?;
+
+    if candidates.is_empty() {
+        return Result::Err(ImportError::NoMatchingVersion(package.to_string()).into());
+    }
+
+    if let Some(candidate) = candidates.iter().find(|x| x.link.sha256.is_none()) {
+        return Result::Err(ImportError::HashMissing(candidate.link.filename.clone()).into());
+    }
+
+    let dir = tempdir::TempDir::new("sling-")?;
+    let mut paths = Vec::new();
+
+    for candidate in candidates {
+        if let Some(reason) = &candidate.link.yanked {
+            println!(
+                "Warning: {} is yanked upstream{}",
+                candidate.link.filename,
+                if reason.is_empty() {
+                    String::new()
+                } else {
+                    format!(" (Reason={})", reason)
+                }
+            );
+        }
+
+        let expected = candidate.link.sha256.clone().unwrap_or_default();
+
+        println!("Downloading {}", candidate.link.url);
+
+        let (_, data) = http::get(&Url::parse(&candidate.link.url)?).await?;
+        let actual = digest::sha256(&data);
+
+        if actual != expected {
+            return Result::Err(
+                ImportError::HashMismatch(candidate.link.filename.clone(), expected, actual).into(),
+            );
+        }
+
+        println!("Verified {} (sha256={})", candidate.link.filename, actual);
+
+        // Write the file under the name sling indexes it by, whatever the upstream spelling
+        let path = dir.path().join(&candidate.distribution.filename);
+        std::fs::write(&path, &data)?;
+        paths.push(path);
+    }
+
+    let options = publish::PublishOptions {
+        overwrite,
+        update_latest,
+        jobs: 1,
+    };
+
+    publish::publish(config, driver, &paths, options).await
+}
+
+pub(crate) fn project_url(index_url: &Url, name: &str) -> Result<Url, url::ParseError> {
+    let mut base = index_url.clone();
+
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{}/", base.path()));
+    }
+
+    base.join(&format!("{}/", normalize_name(name)))
+}
+
+/// Picks the files of the release to import. Yanked files are only considered for exact pins,
+/// as described by PEP 592.
+fn select(
+    name: &str,
+    version: &PackageVersion,
+    links: Vec<FileLink>,
+) -> Result<Vec<Candidate>, ImportError> {
+    let mut candidates: Vec<Candidate> = Vec::new();
+
+    for link in links {
+        let distribution = match parse_upstream_filename(&link.filename) {
+            Some(x) if normalize_name(&x.name) == normalize_name(name) => x,
+            _ => continue,
+        };
+        let version = match SemanticVersion::from_str(&distribution.version) {
+            Result::Ok(x) => x,
+            Result::Err(_) => continue,
+        };
+
+        // Upstream may list the same file under several spellings of the project name
+        if !candidates
+            .iter()
+            .any(|x| x.distribution.filename == distribution.filename)
+        {
+            candidates.push(Candidate {
+                version,
+                distribution,
+                link,
+            });
+        }
+    }
+
+    let predicate: Box<dyn Fn(&Candidate) -> bool> = match version {
+        PackageVersion::Literal(literal) => {
+            let literal = SemanticVersion::from_str(literal).ok();
+            return Result::Ok(
+                candidates
+                    .into_iter()
+                    .filter(|x| Some(&x.version) == literal.as_ref())
+                    .collect(),
+            );
+        }
+        PackageVersion::Latest => Box::new(|_| true),
+        PackageVersion::Requirement(requirement) => {
+            let requirement = requirement.clone();
+            Box::new(move |x| requirement.matches(&x.version))
+        }
+        PackageVersion::Tag(_) => {
+            return Result::Err(ImportError::TagsUnsupported(format!(
+                "{}@{}",
+                name, version
+            )));
+        }
+    };
+
+    let matching: Vec<Candidate> = candidates
+        .into_iter()
+        .filter(|x| x.link.yanked.is_none() && predicate(x))
+        .collect();
+    let prefer_releases = matching.iter().any(|x| !x.version.is_prerelease());
+    let highest = matching
+        .iter()
+        .map(|x| &x.version)
+        .filter(|x| !prefer_releases || !x.is_prerelease())
+        .fold(None, |a: Option<&SemanticVersion>, b| match a {
+            Some(a) if a > b => Some(a),
+            _ => Some(b),
+        })
+        .cloned();
+
+    Result::Ok(
+        matching
+            .into_iter()
+            .filter(|x| Some(&x.version) == highest.as_ref())
+            .collect(),
+    )
+}
+
+/// Maps an upstream sdist or wheel filename onto the distribution sling stores it as. The project
+/// name is normalized and spelled with underscores and the version padded to three release
+/// segments, so every spelling upstream lands on the same bucket key.
+pub(crate) fn parse_upstream_filename(filename: &str) -> Option<Distribution> {
+    // Wheels escape dashes in the project name, so only an sdist name may contain them
+    let pattern = regex::Regex::new(&format!(
+        r"^(?:([A-Za-z0-9_.-]+?)-({0})(\.tar\.gz)|([A-Za-z0-9_.]+)-({0})(-[A-Za-z0-9_.+-]+\.whl))$",
+        RELEASE_PATTERN
+    ))
+    .unwrap();
+    let captures = pattern.captures(filename)?;
+    let part = |sdist: usize| captures.get(sdist).or_else(|| captures.get(sdist + 3));
+    let version = SemanticVersion::from_str(part(2)?.as_str()).ok()?;
+
+    Distribution::from_filename(&format!(
+        "{}-{}{}",
+        normalize_name(part(1)?.as_str()).replace('-', "_"),
+        version,
+        part(3)?.as_str()
+    ))
+}
+
+/// Accepts both `name@version` and pip style `name==version` or `name>=1.0,<2.0` requirements.
+pub(crate) fn parse_requirement(s: &str) -> Result<Package, Box<dyn Error>> {
+    let s = s.trim();
+
+    if s.contains('@') {
+        return Package::from_str(s).map_err(|e| e.into());
+    }
+
+    match s.find(|c| "=!<>~^".contains(c)) {
+        Some(index) => Package::from_str(&format!("{}@{}", s[..index].trim(), &s[index..]))
+            .map_err(|e| e.into()),
+        None => Package::from_str(s).map_err(|e| e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{write_sdist, write_wheel};
+    use crate::storage::memory::MemoryStorageDriver;
+    use bytes::Bytes;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Response, Server, StatusCode};
+    use std::collections::BTreeMap;
+    use std::convert::Infallible;
+    use std::sync::Arc;
+
+    fn link(filename: &str, yanked: bool) -> FileLink {
+        FileLink {
+            filename: filename.to_string(),
+            url: format!("https://example.com/{}", filename),
+            sha256: None,
+            yanked: if yanked { Some(String::new()) } else { None },
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let links = || {
+            vec![
+                link("My.Pkg-1.0.0.tar.gz", false),
+                link("my_pkg-1.1.0.tar.gz", true),
+                link("my_pkg-1.0.1.tar.gz", false),
+                link("my_pkg-2.0.0rc1.tar.gz", false),
+                link("My_Pkg-1.0.1-py3-none-any.whl", false),
+                link("my_pkg-0.9-cp39-cp39-manylinux1_x86_64.whl", false),
+                link("other-3.0.0.tar.gz", false),
+            ]
+        };
+        let version = |x: &str| parse_requirement(x).unwrap().version;
+        let filenames = |candidates: Vec<Candidate>| -> Vec<String> {
+            candidates
+                .into_iter()
+                .map(|x| x.distribution.filename)
+                .collect()
+        };
+
+        assert_eq!(
+            filenames(select("my-pkg", &version("my-pkg"), links()).unwrap()),
+            vec!["my_pkg-1.0.1.tar.gz", "my_pkg-1.0.1-py3-none-any.whl"]
+        );
+        assert_eq!(
+            filenames(select("my-pkg", &version("my-pkg==1.1.0"), links()).unwrap()),
+            vec!["my_pkg-1.1.0.tar.gz"]
+        );
+        // Projects that only publish wheels are imported as well
+        assert_eq!(
+            filenames(select("my-pkg", &version("my-pkg<1.0.0"), links()).unwrap()),
+            vec!["my_pkg-0.9.0-cp39-cp39-manylinux1_x86_64.whl"]
+        );
+
+        assert!(select("my-pkg", &version("my-pkg>=3.0"), links())
+            .unwrap()
+            .is_empty());
+        assert!(select("my-pkg", &version("my-pkg@stable"), links()).is_err());
+    }
+
+    #[test]
+    fn test_parse_upstream_filename() {
+        let key = |filename: &str| parse_upstream_filename(filename).map(|x| x.object_key());
+
+        for filename in [
+            "Django-4.0.1.tar.gz",
+            "django-4.0.1.tar.gz",
+            "DJANGO-4.0.1.tar.gz",
+        ] {
+            assert_eq!(key(filename).unwrap(), "django/django-4.0.1.tar.gz");
+        }
+
+        assert_eq!(
+            key("Zope.Interface-5.4.0.tar.gz").unwrap(),
+            "zope_interface/zope_interface-5.4.0.tar.gz"
+        );
+        assert_eq!(
+            key("Django-4.0.1-py3-none-any.whl").unwrap(),
+            "django/django-4.0.1-py3-none-any.whl"
+        );
+        assert_eq!(
+            key("zope.interface-5.4-1-cp39-cp39-manylinux1_x86_64.whl").unwrap(),
+            "zope_interface/zope_interface-5.4.0-1-cp39-cp39-manylinux1_x86_64.whl"
+        );
+
+        // Releases with fewer segments land on the three segment key sling indexes
+        for (filename, expected) in [
+            ("Django-4.2.tar.gz", "django/django-4.2.0.tar.gz"),
+            ("six-1.tar.gz", "six/six-1.0.0.tar.gz"),
+            ("my-pkg-2.0rc1.tar.gz", "my_pkg/my_pkg-2.0.0rc1.tar.gz"),
+        ] {
+            assert_eq!(key(filename).unwrap(), expected);
+        }
+
+        assert!(key("pkg-1.2.3.4.tar.gz").is_none());
+        assert!(key("pkg-1.0.0.zip").is_none());
+        assert!(key("pkg-1.0.0-py3-none.whl").is_none());
+    }
+
+    /// Serves the given paths the way a simple index would, answering anything else with a 404.
+    async fn start(routes: BTreeMap<String, Bytes>) -> Url {
+        let routes = Arc::new(routes);
+        let make_service = make_service_fn(move |_| {
+            let routes = routes.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: hyper::Request<Body>| {
+                    let body = routes.get(request.uri().path()).cloned();
+                    async move {
+                        Ok::<_, Infallible>(match body {
+                            Some(body) => Response::new(Body::from(body)),
+                            None => Response::builder()
+                                .status(StatusCode::NOT_FOUND)
+                                .body(Body::empty())
+                                .unwrap(),
+                        })
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let url = Url::parse(&format!("http://{}/simple/", server.local_addr())).unwrap();
+
+        tokio::spawn(server);
+
+        url
+    }
+
+    #[tokio::test]
+    async fn test_import() {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let driver = MemoryStorageDriver::default();
+        let config = RuntimeConfig {
+            bucket: Some("bucket".to_string()),
+            ..RuntimeConfig::default()
+        };
+        let files = [
+            write_sdist(dir.path(), "My.Pkg-1.0.tar.gz", "My.Pkg", "1.0"),
+            write_wheel(dir.path(), "my_pkg", "1.0"),
+            write_sdist(dir.path(), "bad-1.0.0.tar.gz", "bad", "1.0.0"),
+            write_sdist(dir.path(), "unhashed-1.0.0.tar.gz", "unhashed", "1.0.0"),
+        ];
+        let mut routes: BTreeMap<String, Bytes> = BTreeMap::new();
+        let mut pages: BTreeMap<String, String> = BTreeMap::new();
+
+        for path in files.iter() {
+            let filename = path.file_name().unwrap().to_str().unwrap();
+            let data = std::fs::read(path).unwrap();
+            let project = parse_upstream_filename(filename).unwrap().name;
+            let fragment = match project.as_str() {
+                "bad" => format!("#sha256={}", digest::sha256(b"tampered")),
+                "unhashed" => String::new(),
+                _ => format!("#sha256={}", digest::sha256(&data)),
+            };
+
+            pages
+                .entry(normalize_name(&project))
+                .or_default()
+                .push_str(&format!(
+                    "<a href=\"../../files/{0}{1}\">{0}</a>\n",
+                    filename, fragment
+                ));
+            routes.insert(format!("/files/{}", filename), Bytes::from(data));
+        }
+
+        for (project, links) in pages {
+            routes.insert(
+                format!("/simple/{}/", project),
+                Bytes::from(format!("<html><body>\n{}</body></html>", links)),
+            );
+        }
+
+        let index_url = start(routes).await;
+        let import = |requirement: &str| {
+            let package = parse_requirement(requirement).unwrap();
+            let (config, driver, index_url) = (&config, &driver, &index_url);
+            async move { import(config, driver, &package, index_url, false, true).await }
+        };
+
+        import("my-pkg").await.unwrap();
+        assert_eq!(
+            driver.keys("bucket"),
+            vec![
+                ".sling/metadata.json",
+                "my_pkg/my_pkg-1.0.0-py3-none-any.whl",
+                "my_pkg/my_pkg-1.0.0.tar.gz",
+            ]
+        );
+
+        let error = import("bad==1.0.0").await.unwrap_err().to_string();
+        assert!(
+            error.starts_with("sha256 mismatch for bad-1.0.0.tar.gz"),
+            "{}",
+            error
+        );
+
+        let error = import("unhashed").await.unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "index did not provide a sha256 digest for unhashed-1.0.0.tar.gz"
+        );
+
+        let error = import("missing").await.unwrap_err().to_string();
+        assert!(error.contains("404"), "{}", error);
+
+        // Nothing but the verified release was published
+        assert_eq!(driver.keys("bucket").len(), 3);
+    }
+}
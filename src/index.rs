@@ -1,9 +1,13 @@
+use crate::bucket_metadata::{BucketMetadata, Yank, LATEST_TAG, METADATA_KEY};
+use crate::distribution::Distribution;
 use crate::package;
 use crate::package::Package;
 use crate::package_version::PackageVersion;
 use crate::semantic_version::SemanticVersion;
+use crate::simple::normalize_name;
 use crate::storage::driver::StorageDriver;
 use crate::storage::object_ref::ObjectRef;
+use crate::version_requirement::VersionRequirement;
 use regex::Regex;
 use std::error::Error;
 use std::str::FromStr;
@@ -12,7 +16,10 @@ use std::str::FromStr;
 pub(crate) struct Entry {
     pub(crate) name: String,
     pub(crate) version: String,
+    /// The file installed when this release is requested, see `Distribution::install_rank`.
     pub(crate) object: ObjectRef,
+    /// Every distribution file of the release, including `object`.
+    pub(crate) files: Vec<ObjectRef>,
 }
 
 impl Entry {
@@ -21,20 +28,52 @@ impl Entry {
             name: name.to_string(),
             version: version.to_string(),
             object: object.clone(),
+            files: vec![object.clone()],
         }
     }
 
+    fn merge(&mut self, other: Entry) {
+        for file in other.files {
+            if !self.files.iter().any(|x| x.key == file.key) {
+                self.files.push(file);
+            }
+        }
+
+        self.files.sort_by(|a, b| {
+            install_rank(a)
+                .cmp(&install_rank(b))
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        self.object = self.files[0].clone();
+    }
+
+    pub(crate) fn size(&self) -> Option<u64> {
+        self.files.iter().map(|x| x.size).sum()
+    }
+
+    pub(crate) fn semantic_version(&self) -> Option<SemanticVersion> {
+        SemanticVersion::from_str(&self.version).ok()
+    }
+
     pub(crate) fn as_package(&self) -> Package {
         Package {
             name: self.name.clone(),
             version: PackageVersion::Literal(self.version.clone()),
+            extras: Vec::new(),
         }
     }
 }
 
+fn install_rank(object: &ObjectRef) -> usize {
+    Distribution::from_filename(object.filename())
+        .map(|x| x.install_rank())
+        .unwrap_or(usize::MAX)
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct Index {
     entries: std::collections::HashMap<String, std::collections::HashMap<String, Entry>>,
+    metadata: BucketMetadata,
 }
 
 impl Index {
@@ -42,20 +81,30 @@ impl Index {
         driver: &'_ dyn StorageDriver,
         bucket: &str,
     ) -> Result<Index, Box<dyn Error>> {
-        let pattern =
-            Regex::new("([0-9a-zA-Z_]+)/[0-9a-zA-Z_]+-(\\d+\\.\\d+\\.\\d+)+\\.tar\\.gz").unwrap();
+        let pattern = Regex::new("^([0-9a-zA-Z_]+)/([^/]+)$").unwrap();
         let mut index = Index::new();
 
-        for object in driver.list(bucket).await? {
-            let fields = if let Some(capture) = pattern.captures(object.key.as_str()) {
-                (capture.get(1), capture.get(2))
-            } else {
+        let objects = driver.list(bucket).await?;
+
+        for object in objects {
+            if object.key == METADATA_KEY {
+                index.metadata = BucketMetadata::load(driver, bucket).await?;
                 continue;
-            };
+            }
 
-            if let (Some(name), Some(version)) = fields {
-                index.add(Entry::new(name.as_str(), version.as_str(), &object));
+            let (name, distribution) = match pattern
+                .captures(object.key.as_str())
+                .and_then(|x| Some((x.get(1)?, Distribution::from_filename(x.get(2)?.as_str())?)))
+            {
+                Some(x) => x,
+                None => continue,
             };
+
+            index.add(Entry::new(
+                name.as_str(),
+                distribution.version.as_str(),
+                &object,
+            ));
         }
 
         Result::Ok(index)
@@ -64,9 +113,18 @@ impl Index {
     pub(crate) fn new() -> Index {
         Index {
             entries: std::collections::HashMap::new(),
+            metadata: BucketMetadata::default(),
         }
     }
 
+    pub(crate) fn metadata(&self) -> &BucketMetadata {
+        &self.metadata
+    }
+
+    pub(crate) fn get_yank(&self, name: &str, version: &str) -> Option<&Yank> {
+        self.metadata.get_yank(name, version)
+    }
+
     pub(crate) fn contains(&self, package: &package::Package) -> bool {
         match self.entries.get(&package.name) {
             None => false,
@@ -86,14 +144,74 @@ impl Index {
                 .insert(entry.name.clone(), std::collections::HashMap::new());
         }
 
+        let versions = self.entries.get_mut(&entry.name).unwrap();
+
+        match versions.get_mut(&entry.version) {
+            Some(existing) => existing.merge(entry),
+            None => {
+                versions.insert(entry.version.clone(), entry);
+            }
+        }
+    }
+
+    /// Finds the release a file belongs to by its object key.
+    pub(crate) fn find_file(&self, key: &str) -> Option<(&Entry, &ObjectRef)> {
         self.entries
-            .get_mut(&entry.name)
-            .unwrap()
-            .insert(entry.clone().version, entry.clone());
+            .values()
+            .flat_map(|x| x.values())
+            .find_map(|entry| Some((entry, entry.files.iter().find(|x| x.key == key)?)))
     }
 
+    /// Prefers the version carrying the `latest` tag, falling back to the highest version.
     pub(crate) fn find_latest(&self, name: &str) -> Option<Entry> {
-        if let Some((first, remaining)) = self.get_available_versions(name).split_first() {
+        self.find_tagged(name, LATEST_TAG)
+            .filter(|x| self.get_yank(name, &x.version).is_none())
+            .or_else(|| self.find_highest(name, |_| true))
+    }
+
+    /// The highest version that isn't yanked or a pre-release, ignoring tags.
+    pub(crate) fn find_highest_release(&self, name: &str) -> Option<Entry> {
+        self.find_highest(name, |x| !x.is_prerelease())
+    }
+
+    pub(crate) fn find_tagged(&self, name: &str, tag: &str) -> Option<Entry> {
+        self.metadata
+            .get_tag(name, tag)
+            .and_then(|version| self.find(name, version))
+    }
+
+    pub(crate) fn find_matching(
+        &self,
+        name: &str,
+        requirement: &VersionRequirement,
+    ) -> Option<Entry> {
+        self.find_highest(name, |x| requirement.matches(x))
+    }
+
+    /// Yanked versions are never selected by version resolution, only by an explicit pin.
+    /// Pre-releases are only selected when no release satisfies the predicate.
+    fn find_highest<F: Fn(&SemanticVersion) -> bool>(
+        &self,
+        name: &str,
+        predicate: F,
+    ) -> Option<Entry> {
+        let matching: Vec<(SemanticVersion, Entry)> = self
+            .get_available_versions(name)
+            .into_iter()
+            .filter(|x| predicate(&x.0) && self.get_yank(name, &x.1.version).is_none())
+            .collect();
+        let releases: Vec<(SemanticVersion, Entry)> = matching
+            .iter()
+            .filter(|x| !x.0.is_prerelease())
+            .cloned()
+            .collect();
+        let candidates = if releases.is_empty() {
+            matching
+        } else {
+            releases
+        };
+
+        if let Some((first, remaining)) = candidates.split_first() {
             let result = remaining
                 .iter()
                 .fold(first, |a, b| if a.0 > b.0 { a } else { b });
@@ -103,6 +221,17 @@ impl Index {
         }
     }
 
+    pub(crate) fn resolve(&self, package: &Package) -> Option<Entry> {
+        match &package.version {
+            PackageVersion::Literal(version) => self.find(&package.name, version),
+            PackageVersion::Latest => self.find_latest(&package.name),
+            PackageVersion::Requirement(requirement) => {
+                self.find_matching(&package.name, requirement)
+            }
+            PackageVersion::Tag(tag) => self.find_tagged(&package.name, tag),
+        }
+    }
+
     pub(crate) fn find(&self, name: &str, version: &str) -> Option<Entry> {
         for x in self.get_available_versions(name) {
             if x.0.to_string() == *version {
@@ -112,6 +241,27 @@ impl Index {
         None
     }
 
+    pub(crate) fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.entries.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Finds the name a project is published under, since requirements don't have to spell it
+    /// the way the bucket does.
+    pub(crate) fn find_name(&self, name: &str) -> Option<String> {
+        self.entries
+            .keys()
+            .find(|x| normalize_name(x) == normalize_name(name))
+            .cloned()
+    }
+
+    pub(crate) fn list_versions(&self, name: &str) -> Vec<Entry> {
+        let mut versions = self.get_available_versions(name);
+        versions.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        versions.into_iter().map(|x| x.1).collect()
+    }
+
     fn get_available_versions(&self, name: &str) -> Vec<(SemanticVersion, Entry)> {
         if let Some(entries) = self.entries.get(name) {
             entries
@@ -1,7 +1,8 @@
 use crate::index::{Entry, Index};
 use crate::package::Package;
 use crate::package_version::PackageVersion;
-use crate::{pip, RuntimeConfig};
+use crate::requirements::{Line, RequirementsFile};
+use crate::{digest, pip, yank, RuntimeConfig};
 use std::error::Error;
 
 use crate::storage::driver::StorageDriver;
@@ -16,7 +17,9 @@ enum InstallError {
     PackageNotFound(String),
     VersionResolutionFailed(String),
     DownloadError(String),
-    PipError(String),
+    UnsupportedHash(String),
+    HashMismatch(String, String),
+    PackagesFailed(Vec<String>),
 }
 
 impl std::fmt::Display for InstallError {
@@ -24,19 +27,99 @@ impl std::fmt::Display for InstallError {
         match self {
             Self::NoBucketDefined => write!(f, "no bucket was provided"),
             Self::PackageNotFound(pkg) => write!(f, "package not found: {}", pkg),
-            Self::VersionResolutionFailed(pkg) => write!(f, "latest version failed: {}", pkg),
+            Self::VersionResolutionFailed(pkg) => write!(f, "version resolution failed: {}", pkg),
             Self::DownloadError(msg) => write!(f, "download error: {}", msg),
-            Self::PipError(msg) => write!(f, "pip error: {}", msg),
+            Self::UnsupportedHash(pkg) => {
+                write!(f, "no sha256 --hash was given for {}", pkg)
+            }
+            Self::HashMismatch(pkg, actual) => write!(
+                f,
+                "sha256 of {} matches none of the given hashes: {}",
+                pkg, actual
+            ),
+            Self::PackagesFailed(packages) => {
+                write!(f, "failed to install {}", packages.join(", "))
+            }
         }
     }
 }
 
 impl std::error::Error for InstallError {}
 
+/// What was installed and what failed, printed once installing stops.
+#[derive(Debug, Default)]
+pub(crate) struct InstallSummary {
+    installed: Vec<String>,
+    failed: Vec<(String, String)>,
+}
+
+impl InstallSummary {
+    pub(crate) fn installed(&mut self, package: String) {
+        self.installed.push(package);
+    }
+
+    /// Records a failure. Returns the error back when installing should stop at the first one.
+    pub(crate) fn failed(
+        &mut self,
+        package: String,
+        error: Box<dyn Error>,
+        keep_going: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let message = error.to_string();
+
+        self.failed.push((
+            package,
+            message.lines().next().unwrap_or_default().to_string(),
+        ));
+
+        if keep_going {
+            println!("Error: {}", message);
+            Result::Ok(())
+        } else {
+            self.print();
+            Result::Err(error)
+        }
+    }
+
+    pub(crate) fn print(&self) {
+        println!(
+            "Installed {} package(s), {} failed",
+            self.installed.len(),
+            self.failed.len()
+        );
+
+        for package in self.installed.iter() {
+            println!("  installed  {}", package);
+        }
+
+        for (package, message) in self.failed.iter() {
+            println!("  failed     {}: {}", package, message);
+        }
+    }
+
+    /// Prints the summary and fails if anything failed to install.
+    pub(crate) fn finish(self) -> Result<(), Box<dyn Error>> {
+        self.print();
+
+        if self.failed.is_empty() {
+            Result::Ok(())
+        } else {
+            Result::Err(
+                InstallError::PackagesFailed(self.failed.into_iter().map(|x| x.0).collect()).into(),
+            )
+        }
+    }
+}
+
+/// Installs the packages, along with the requirements files. Requirements published to the
+/// bucket are installed by sling, the rest are handed to pip with their original options.
+/// With `keep_going`, a package that fails doesn't stop the ones after it.
 pub(crate) async fn install(
     config: &RuntimeConfig,
     driver: &'_ dyn StorageDriver,
     packages: Vec<Package>,
+    requirements: Vec<RequirementsFile>,
+    keep_going: bool,
 ) -> Result<(), std::boxed::Box<dyn std::error::Error>> {
     let bucket = if config.bucket.is_none() {
         return Result::Err(InstallError::NoBucketDefined.into());
@@ -52,40 +135,188 @@ pub(crate) async fn install(
 
     let dir = tempdir::TempDir::new("sling-")?;
     let index = Index::from_storage_bucket(driver, bucket.as_str()).await?;
+    let mut packages: Vec<(Package, Vec<String>)> =
+        packages.into_iter().map(|x| (x, Vec::new())).collect();
+    let mut routed = Vec::new();
+    let mut pip_lines: Vec<String> = Vec::new();
+    let mut pip_options: Vec<String> = Vec::new();
 
-    for package in packages {
-        let object = if let PackageVersion::Literal(version) = package.version {
-            if let Some(result) = index.find(&package.name, &version) {
-                Ok(result)
-            } else {
-                Err(InstallError::PackageNotFound(
-                    package.name.clone().to_string(),
-                ))
+    for file in requirements {
+        pip_options.extend(file.options);
+
+        for line in file.lines {
+            match line {
+                Line::Pip(line) => pip_lines.push(line),
+                Line::Requirement(mut requirement) => {
+                    match index.find_name(&requirement.package.name) {
+                        Some(name) => {
+                            requirement.package.name = name;
+                            routed.push(requirement);
+                        }
+                        None => match requirement.pip_line.take() {
+                            Some(line) => pip_lines.push(line),
+                            None => routed.push(requirement),
+                        },
+                    }
+                }
             }
-        } else if let Some(latest) = index.find_latest(&package.name) {
+        }
+    }
+
+    let markers: Vec<(String, String)> = routed
+        .iter()
+        .filter_map(|x| Some((x.marker.clone()?, String::new())))
+        .collect();
+    let mut evaluated = if markers.is_empty() {
+        Vec::new()
+    } else {
+        pip::evaluate_markers(&python, &markers)?
+    }
+    .into_iter();
+
+    for requirement in routed {
+        if requirement.marker.is_some() && !evaluated.next().unwrap_or(false) {
             println!(
-                "Resolved package version: {}@latest -> {}@{}",
-                package.name, latest.name, latest.version
+                "Skipping {}: marker not satisfied (Marker={})",
+                requirement.package,
+                requirement.marker.unwrap_or_default()
             );
-            Ok(latest)
-        } else {
-            Err(InstallError::VersionResolutionFailed(
-                package.name.clone().to_string(),
-            ))
-        }?;
-
-        let package = object.as_package();
-        let target = dir.path().join(package.filename());
-
-        download_package(driver, object, &target)
-            .await
-            .map_err(|e| {
-                InstallError::DownloadError(format!("failed to download package from s3: {}", e))
-            })?;
-
-        pip::install_package(&python, &pip_args, &target).map_err(|e| {
-            InstallError::PipError(format!("failed to install package with pip: {}", e))
+            continue;
+        }
+
+        packages.push((requirement.package, requirement.hashes));
+    }
+
+    let mut summary = InstallSummary::default();
+
+    for (package, hashes) in packages {
+        match install_one(
+            driver,
+            &index,
+            dir.path(),
+            &python,
+            &pip_args,
+            &package,
+            &hashes,
+        )
+        .await
+        {
+            Result::Ok(installed) => summary.installed(installed),
+            Result::Err(e) => summary.failed(package.to_string(), e, keep_going)?,
+        }
+    }
+
+    install_with_pip(
+        &mut summary,
+        dir.path(),
+        &python,
+        &pip_args,
+        &pip_options,
+        &pip_lines,
+        keep_going,
+    )?;
+
+    summary.finish()
+}
+
+/// Hands the requirements that aren't published to the bucket to pip, written to a requirements
+/// file along with the options of the files they came from.
+pub(crate) fn install_with_pip(
+    summary: &mut InstallSummary,
+    dir: &Path,
+    python: &str,
+    pip_args: &str,
+    options: &[String],
+    lines: &[String],
+    keep_going: bool,
+) -> Result<(), Box<dyn Error>> {
+    if lines.is_empty() {
+        return Result::Ok(());
+    }
+
+    let target = dir.join("requirements.txt");
+    std::fs::write(
+        &target,
+        options
+            .iter()
+            .chain(lines.iter())
+            .map(|x| format!("{}\n", x))
+            .collect::<String>(),
+    )?;
+
+    let requirements = format!("{} requirement(s) from pip", lines.len());
+
+    match pip::install_requirements(python, pip_args, &target) {
+        Result::Ok(_) => summary.installed(requirements),
+        Result::Err(e) => summary.failed(requirements, e, keep_going)?,
+    }
+
+    Result::Ok(())
+}
+
+/// Resolves, downloads and installs a single package, returning the release installed.
+async fn install_one(
+    driver: &'_ dyn StorageDriver,
+    index: &Index,
+    dir: &Path,
+    python: &str,
+    pip_args: &str,
+    package: &Package,
+    hashes: &[String],
+) -> Result<String, Box<dyn Error>> {
+    let object = match index.resolve(package) {
+        Some(result) => Ok(result),
+        None if matches!(package.version, PackageVersion::Literal(_)) => Err(
+            InstallError::PackageNotFound(package.name.clone().to_string()),
+        ),
+        None => Err(InstallError::VersionResolutionFailed(
+            package.name.clone().to_string(),
+        )),
+    }?;
+
+    if !matches!(package.version, PackageVersion::Literal(_)) {
+        println!(
+            "Resolved package version: {} -> {}@{}",
+            package, object.name, object.version
+        );
+    }
+
+    yank::warn_if_yanked(index, &object);
+
+    let installed = format!("{}@{}", object.name, object.version);
+    let target = dir.join(object.object.filename());
+
+    download_package(driver, object, &target)
+        .await
+        .map_err(|e| {
+            InstallError::DownloadError(format!("failed to download package from s3: {}", e))
         })?;
+
+    if !hashes.is_empty() {
+        verify_hashes(package, hashes, &target)?;
+    }
+
+    pip::install_package(python, pip_args, &target, &package.extras)?;
+
+    Result::Ok(installed)
+}
+
+/// Checks a downloaded file against the `--hash` options of its requirement, like pip's
+/// hash-checking mode. Only sha256 is supported.
+fn verify_hashes(package: &Package, hashes: &[String], path: &Path) -> Result<(), Box<dyn Error>> {
+    let expected: Vec<&str> = hashes
+        .iter()
+        .filter_map(|x| x.strip_prefix("sha256:"))
+        .collect();
+
+    if expected.is_empty() {
+        return Result::Err(InstallError::UnsupportedHash(package.to_string()).into());
+    }
+
+    let actual = digest::sha256(&std::fs::read(path)?);
+
+    if !expected.contains(&actual.as_str()) {
+        return Result::Err(InstallError::HashMismatch(package.to_string(), actual).into());
     }
 
     Result::Ok(())
@@ -102,7 +333,40 @@ pub(crate) async fn download_package(
         .get(entry.object.bucket.as_str(), entry.object.key.as_str())
         .await?;
 
-    File::create(&target)?.write_all(data.chunk())?;
+    File::create(target)?.write_all(data.chunk())?;
 
     Result::Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_with_pip_summary() {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let python = pip::stub_python(dir.path(), 1, 1);
+        let lines = vec!["requests==2.31.0".to_string()];
+        let mut summary = InstallSummary::default();
+
+        install_with_pip(&mut summary, dir.path(), &python, "", &[], &lines, true).unwrap();
+        summary.installed("pkg@1.0.0".to_string());
+
+        assert_eq!(summary.installed, vec!["pkg@1.0.0"]);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].0, "1 requirement(s) from pip");
+        assert!(summary.failed[0].1.ends_with("exit status: 1"));
+        assert_eq!(
+            summary.finish().unwrap_err().to_string(),
+            "failed to install 1 requirement(s) from pip"
+        );
+
+        // Without keep_going the first failure is returned as is
+        let mut summary = InstallSummary::default();
+        let error = install_with_pip(&mut summary, dir.path(), &python, "", &[], &lines, false)
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("pip failed to install"));
+        assert!(error.ends_with("  line 1"));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::index::{Entry, Index};
+use crate::{RuntimeConfig, StorageDriver};
+use regex::Regex;
+use serde_derive::Serialize;
+use std::error::Error;
+
+#[derive(Debug)]
+enum ListError {
+    NoBucketDefined,
+    PackageNotFound(String),
+    InvalidPattern(String),
+}
+
+impl std::fmt::Display for ListError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoBucketDefined => write!(f, "no bucket was provided"),
+            Self::PackageNotFound(pkg) => write!(f, "package not found: {}", pkg),
+            Self::InvalidPattern(msg) => write!(f, "invalid search pattern: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ListError {}
+
+#[derive(Debug, Serialize)]
+struct PackageRow {
+    name: String,
+    latest: String,
+    versions: usize,
+    size: Option<u64>,
+    uploaded: Option<String>,
+    url: String,
+}
+
+impl PackageRow {
+    fn new(latest: &Entry, versions: usize) -> PackageRow {
+        PackageRow {
+            name: latest.name.clone(),
+            latest: latest.version.clone(),
+            versions,
+            size: latest.size(),
+            uploaded: latest.object.last_modified.map(|x| x.to_rfc3339()),
+            url: latest.object.get_object_url(),
+        }
+    }
+
+    fn as_cells(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.latest.clone(),
+            self.versions.to_string(),
+            format_optional_size(self.size),
+            format_optional_time(&self.uploaded),
+            self.url.clone(),
+        ]
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct VersionRow {
+    name: String,
+    version: String,
+    yanked: bool,
+    tags: Vec<String>,
+    size: Option<u64>,
+    uploaded: Option<String>,
+    url: String,
+}
+
+impl VersionRow {
+    fn new(index: &Index, entry: &Entry) -> VersionRow {
+        VersionRow {
+            name: entry.name.clone(),
+            version: entry.version.clone(),
+            yanked: index.get_yank(&entry.name, &entry.version).is_some(),
+            tags: index.metadata().get_tags(&entry.name, &entry.version),
+            size: entry.size(),
+            uploaded: entry.object.last_modified.map(|x| x.to_rfc3339()),
+            url: entry.object.get_object_url(),
+        }
+    }
+
+    fn as_cells(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.version.clone(),
+            self.tags.join(","),
+            if self.yanked { "yes" } else { "" }.to_string(),
+            format_optional_size(self.size),
+            format_optional_time(&self.uploaded),
+            self.url.clone(),
+        ]
+    }
+}
+
+pub(crate) async fn list(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    package: Option<String>,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    let index = load_index(config, driver).await?;
+
+    match package {
+        Some(name) => {
+            let rows: Vec<VersionRow> = index
+                .list_versions(&name)
+                .iter()
+                .map(|x| VersionRow::new(&index, x))
+                .collect();
+
+            if rows.is_empty() {
+                return Result::Err(ListError::PackageNotFound(name).into());
+            }
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&rows)?);
+            } else {
+                print_table(
+                    &[
+                        "NAME", "VERSION", "TAGS", "YANKED", "SIZE", "UPLOADED", "URL",
+                    ],
+                    rows.iter().map(|x| x.as_cells()).collect(),
+                );
+            }
+        }
+        None => print_packages(&index, index.names(), json)?,
+    }
+
+    Result::Ok(())
+}
+
+pub(crate) async fn search(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    query: &str,
+    regex: bool,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    let pattern = if regex {
+        query.to_string()
+    } else {
+        format!("(?i){}", regex::escape(query))
+    };
+    let pattern =
+        Regex::new(pattern.as_str()).map_err(|e| ListError::InvalidPattern(format!("{}", e)))?;
+
+    let index = load_index(config, driver).await?;
+    let names = index
+        .names()
+        .into_iter()
+        .filter(|x| pattern.is_match(x))
+        .collect();
+
+    print_packages(&index, names, json)
+}
+
+async fn load_index(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+) -> Result<Index, Box<dyn Error>> {
+    let bucket = if config.bucket.is_none() {
+        return Result::Err(ListError::NoBucketDefined.into());
+    } else {
+        config.bucket.clone().unwrap()
+    };
+
+    Index::from_storage_bucket(driver, bucket.as_str()).await
+}
+
+fn print_packages(index: &Index, names: Vec<String>, json: bool) -> Result<(), Box<dyn Error>> {
+    let rows: Vec<PackageRow> = names
+        .iter()
+        .flat_map(|name| {
+            index
+                .find_latest(name)
+                .map(|latest| PackageRow::new(&latest, index.list_versions(name).len()))
+        })
+        .collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+    } else {
+        print_table(
+            &["NAME", "LATEST", "VERSIONS", "SIZE", "UPLOADED", "URL"],
+            rows.iter().map(|x| x.as_cells()).collect(),
+        );
+    }
+
+    Result::Ok(())
+}
+
+pub(crate) fn print_table(headers: &[&str], rows: Vec<Vec<String>>) {
+    let mut widths: Vec<usize> = headers.iter().map(|x| x.len()).collect();
+
+    for row in rows.iter() {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.len());
+        }
+    }
+
+    let format_row = |cells: Vec<String>| {
+        cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| format!("{:width$}", cell, width = widths[i]))
+            .collect::<Vec<String>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!(
+        "{}",
+        format_row(headers.iter().map(|x| x.to_string()).collect())
+    );
+
+    for row in rows {
+        println!("{}", format_row(row));
+    }
+}
+
+pub(crate) fn format_size(size: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", size, units[unit])
+    } else {
+        format!("{:.1} {}", value, units[unit])
+    }
+}
+
+fn format_optional_size(size: Option<u64>) -> String {
+    size.map(format_size).unwrap_or_else(|| "-".to_string())
+}
+
+fn format_optional_time(time: &Option<String>) -> String {
+    time.as_ref()
+        .and_then(|x| chrono::DateTime::parse_from_rfc3339(x).ok())
+        .map(|x| x.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
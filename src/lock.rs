@@ -0,0 +1,872 @@
+use crate::archive::Archive;
+use crate::digest;
+use crate::import::parse_requirement;
+use crate::index::Index;
+use crate::install::{install_with_pip, InstallSummary};
+use crate::package::Package;
+use crate::package_version::PackageVersion;
+use crate::requirements::{Line, RequirementsFile};
+use crate::simple::normalize_name;
+use crate::{pip, yank, RuntimeConfig, StorageDriver};
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Where `lock` writes and `get --locked` reads the lockfile by default.
+pub(crate) const LOCKFILE: &str = "sling.lock";
+
+const FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug)]
+enum LockError {
+    NoBucketDefined,
+    PackageNotFound(String),
+    DependencyNotFound(String, String),
+    Conflict(String, String, String),
+    InvalidDependency(String, String),
+    UnsupportedFormat(u32),
+    InvalidUrl(String),
+    ObjectMissing(String, String),
+    HashMismatch(String, String, String),
+}
+
+impl std::fmt::Display for LockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoBucketDefined => write!(f, "no bucket was provided"),
+            Self::PackageNotFound(pkg) => write!(f, "package not found: {}", pkg),
+            Self::DependencyNotFound(pkg, parent) => {
+                write!(f, "no version of {} required by {} was found", pkg, parent)
+            }
+            Self::Conflict(pkg, locked, parent) => write!(
+                f,
+                "{} required by {} conflicts with the locked version {}",
+                pkg, parent, locked
+            ),
+            Self::InvalidDependency(dependency, parent) => {
+                write!(f, "invalid dependency of {}: {}", parent, dependency)
+            }
+            Self::UnsupportedFormat(version) => {
+                write!(f, "unsupported lockfile format version: {}", version)
+            }
+            Self::InvalidUrl(url) => write!(f, "invalid object url: {}", url),
+            Self::ObjectMissing(url, msg) => write!(f, "failed to download {}: {}", url, msg),
+            Self::HashMismatch(url, expected, actual) => write!(
+                f,
+                "sha256 mismatch for {}: expected {}, got {}",
+                url, expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LockError {}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Lockfile {
+    format_version: u32,
+    created: String,
+    source: String,
+    /// The packages that were asked for, which everything else is installed as a dependency of.
+    requested: Vec<LockedDependency>,
+    packages: Vec<LockedPackage>,
+    /// Options of the requirements files the `pip_requirements` came from.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pip_options: Vec<String>,
+    /// Requirements that aren't published to the bucket, left to pip as written and not locked.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pip_requirements: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct LockedPackage {
+    name: String,
+    version: String,
+    url: String,
+    sha256: String,
+    size: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    extras: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    dependencies: Vec<LockedDependency>,
+}
+
+/// A package required only where its environment marker holds. Markers are evaluated by
+/// `get --locked`, so a lockfile can be shared between platforms.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+struct LockedDependency {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    marker: Option<String>,
+}
+
+/// A `Requires-Dist` entry: the project name, its extras, the version specifier and the
+/// environment marker.
+#[derive(Debug, PartialEq)]
+struct Dependency {
+    name: String,
+    extras: Vec<String>,
+    specifier: String,
+    marker: Option<String>,
+}
+
+/// Resolves the packages, along with the requirements files, and every dependency of theirs that's
+/// published to the bucket, including the dependencies of requested extras. The exact versions,
+/// object urls and digests are written to a lockfile in install order, keeping environment
+/// markers for `get --locked` to evaluate. Requirements and dependencies that aren't in the bucket
+/// are left to pip.
+pub(crate) async fn lock(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    packages: Vec<Package>,
+    requirements: Vec<RequirementsFile>,
+    output: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let bucket = if config.bucket.is_none() {
+        return Result::Err(LockError::NoBucketDefined.into());
+    } else {
+        config.bucket.clone().unwrap()
+    };
+
+    let index = Index::from_storage_bucket(driver, bucket.as_str()).await?;
+    let mut locked: BTreeMap<String, LockedPackage> = BTreeMap::new();
+    let mut requires: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut requested: Vec<LockedDependency> = Vec::new();
+    let mut queue: VecDeque<(Package, Option<String>)> = VecDeque::new();
+
+    let mut roots: Vec<(Package, Option<String>)> =
+        packages.into_iter().map(|x| (x, None)).collect();
+    let mut pip_options: Vec<String> = Vec::new();
+    let mut pip_requirements: Vec<String> = Vec::new();
+
+    // Requirements that aren't in the bucket are routed to pip the same way `get` does
+    for file in requirements {
+        pip_options.extend(file.options);
+
+        for line in file.lines {
+            match line {
+                Line::Pip(line) => pip_requirements.push(line),
+                Line::Requirement(mut requirement) => {
+                    match (
+                        index.find_name(&requirement.package.name),
+                        requirement.pip_line.take(),
+                    ) {
+                        (None, Some(line)) => {
+                            println!(
+                                "Leaving {} to pip: not published to the bucket",
+                                requirement.package.name
+                            );
+                            pip_requirements.push(line);
+                        }
+                        _ => roots.push((requirement.package, requirement.marker)),
+                    }
+                }
+            }
+        }
+    }
+
+    if pip_requirements.is_empty() {
+        pip_options.clear();
+    }
+
+    for (package, marker) in roots {
+        let package = Package {
+            name: index
+                .find_name(&package.name)
+                .unwrap_or_else(|| package.name.clone()),
+            ..package
+        };
+
+        add_dependency(&mut requested, &package.name, marker);
+        queue.push_back((package, None));
+    }
+
+    while let Some((package, parent)) = queue.pop_front() {
+        // A package that's already locked only needs the dependencies of any new extras
+        let (name, extras, include_base) = match locked.get_mut(&package.name) {
+            Some(existing) => {
+                if !satisfies(&index, &package, &existing.version) {
+                    return Result::Err(
+                        LockError::Conflict(
+                            package.to_string(),
+                            existing.version.clone(),
+                            parent.unwrap_or_else(|| "the command line".to_string()),
+                        )
+                        .into(),
+                    );
+                }
+
+                let added: Vec<String> = package
+                    .extras
+                    .iter()
+                    .filter(|x| !contains_extra(&existing.extras, x))
+                    .cloned()
+                    .collect();
+
+                if added.is_empty() {
+                    continue;
+                }
+
+                existing.extras.extend(added.iter().cloned());
+                (existing.name.clone(), added, false)
+            }
+            None => {
+                let entry = match (index.resolve(&package), &parent) {
+                    (Some(entry), _) => entry,
+                    (None, Some(parent)) => {
+                        return Result::Err(
+                            LockError::DependencyNotFound(package.to_string(), parent.clone())
+                                .into(),
+                        )
+                    }
+                    (None, None) => {
+                        return Result::Err(LockError::PackageNotFound(package.to_string()).into())
+                    }
+                };
+
+                println!("Locking {}@{}", entry.name, entry.version);
+                yank::warn_if_yanked(&index, &entry);
+
+                let data = driver
+                    .get(entry.object.bucket.as_str(), entry.object.key.as_str())
+                    .await?;
+                let metadata = Archive::read(entry.object.filename(), &data)?.core_metadata()?;
+
+                requires.insert(
+                    entry.name.clone(),
+                    metadata
+                        .requires_dist()
+                        .into_iter()
+                        .map(|x| x.to_string())
+                        .collect(),
+                );
+                locked.insert(
+                    entry.name.clone(),
+                    LockedPackage {
+                        name: entry.name.clone(),
+                        version: entry.version.clone(),
+                        url: entry.object.get_object_url(),
+                        sha256: digest::sha256(&data),
+                        size: data.len() as u64,
+                        extras: package.extras.clone(),
+                        dependencies: Vec::new(),
+                    },
+                );
+
+                (entry.name.clone(), package.extras.clone(), true)
+            }
+        };
+
+        let locked_package = locked.get_mut(&name).unwrap();
+        let slug = format!("{}@{}", locked_package.name, locked_package.version);
+
+        for requirement in requires[&name].iter() {
+            let dependency = parse_dependency(requirement)
+                .ok_or_else(|| LockError::InvalidDependency(requirement.clone(), slug.clone()))?;
+            let required_by = marker_extras(dependency.marker.as_deref().unwrap_or_default());
+            let selected = if required_by.is_empty() {
+                include_base
+            } else {
+                required_by.iter().any(|x| contains_extra(&extras, x))
+            };
+
+            if !selected {
+                continue;
+            }
+
+            let dependency_name = match index.find_name(&dependency.name) {
+                Some(name) => name,
+                None => continue,
+            };
+            let mut package =
+                parse_requirement(&format!("{}{}", dependency_name, dependency.specifier))
+                    .map_err(|_| LockError::InvalidDependency(requirement.clone(), slug.clone()))?;
+            package.extras = dependency.extras;
+
+            add_dependency(
+                &mut locked_package.dependencies,
+                &dependency_name,
+                dependency.marker,
+            );
+            queue.push_back((package, Some(slug.clone())));
+        }
+    }
+
+    let lockfile = Lockfile {
+        format_version: FORMAT_VERSION,
+        created: chrono::Utc::now().to_rfc3339(),
+        source: format!("{}://{}", driver.get_protocol(), bucket),
+        packages: install_order(&requested, &locked),
+        requested,
+        pip_options,
+        pip_requirements,
+    };
+
+    std::fs::write(output, serde_json::to_vec_pretty(&lockfile)?)?;
+
+    println!(
+        "Locked {} package(s) to {}",
+        lockfile.packages.len(),
+        output.display()
+    );
+
+    Result::Ok(())
+}
+
+/// Reads the exact versions a lockfile pins, or `None` when the file isn't JSON and so is left
+/// to be read as a requirements file.
+pub(crate) fn read_locked_packages(path: &Path) -> Result<Option<Vec<Package>>, Box<dyn Error>> {
+    let value: serde_json::Value = match serde_json::from_slice(&std::fs::read(path)?) {
+        Result::Ok(value) => value,
+        Result::Err(_) => return Result::Ok(None),
+    };
+    let lockfile = parse_lockfile(value)?;
+
+    Result::Ok(Some(
+        lockfile
+            .packages
+            .into_iter()
+            .map(|x| Package {
+                name: x.name,
+                version: PackageVersion::Literal(x.version),
+                extras: Vec::new(),
+            })
+            .collect(),
+    ))
+}
+
+/// Installs exactly the packages in a lockfile whose environment markers hold. Every object is
+/// downloaded and checked against its digest before anything is installed.
+pub(crate) async fn install_locked(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    path: &Path,
+    keep_going: bool,
+) -> Result<(), Box<dyn Error>> {
+    let lockfile = parse_lockfile(serde_json::from_slice(&std::fs::read(path)?)?)?;
+
+    let python = config
+        .python
+        .clone()
+        .unwrap_or_else(|| String::from("python"));
+    let pip_args = config.pip_args.clone().unwrap_or_else(|| String::from(""));
+
+    let selected = select_packages(&python, &lockfile)?;
+    let packages: Vec<&LockedPackage> = lockfile
+        .packages
+        .iter()
+        .filter(|x| selected.contains(&x.name))
+        .collect();
+
+    let dir = tempdir::TempDir::new("sling-")?;
+    let mut targets: Vec<(PathBuf, &LockedPackage)> = Vec::new();
+
+    for package in packages {
+        let (bucket, key) = package
+            .url
+            .strip_prefix(&format!("{}://", driver.get_protocol()))
+            .and_then(|x| x.split_once('/'))
+            .ok_or_else(|| LockError::InvalidUrl(package.url.clone()))?;
+
+        println!("Downloading {}", package.url);
+
+        let data = driver
+            .get(bucket, key)
+            .await
+            .map_err(|e| LockError::ObjectMissing(package.url.clone(), e.to_string()))?;
+        let actual = digest::sha256(&data);
+
+        if actual != package.sha256 {
+            return Result::Err(
+                LockError::HashMismatch(package.url.clone(), package.sha256.clone(), actual).into(),
+            );
+        }
+
+        let target = dir.path().join(key.rsplit('/').next().unwrap_or(key));
+        std::fs::write(&target, &data)?;
+        targets.push((target, package));
+    }
+
+    println!(
+        "Verified {} package(s) from {}",
+        targets.len(),
+        path.display()
+    );
+
+    let mut summary = InstallSummary::default();
+
+    for (target, package) in targets {
+        let slug = format!("{}@{}", package.name, package.version);
+
+        match pip::install_package(&python, &pip_args, &target, &package.extras) {
+            Result::Ok(_) => summary.installed(slug),
+            Result::Err(e) => summary.failed(slug, e, keep_going)?,
+        }
+    }
+
+    install_with_pip(
+        &mut summary,
+        dir.path(),
+        &python,
+        &pip_args,
+        &lockfile.pip_options,
+        &lockfile.pip_requirements,
+        keep_going,
+    )?;
+
+    summary.finish()
+}
+
+/// Checks the format version before the rest of the lockfile, whose layout depends on it.
+fn parse_lockfile(value: serde_json::Value) -> Result<Lockfile, Box<dyn Error>> {
+    let version = value
+        .get("format_version")
+        .and_then(|x| x.as_u64())
+        .unwrap_or_default() as u32;
+
+    if version != FORMAT_VERSION {
+        return Result::Err(LockError::UnsupportedFormat(version).into());
+    }
+
+    Result::Ok(serde_json::from_value(value)?)
+}
+
+/// Follows the dependencies whose markers hold from the requested packages. A dependency's
+/// marker is evaluated once for each extra of the package requiring it, and once without one.
+fn select_packages(python: &str, lockfile: &Lockfile) -> Result<HashSet<String>, Box<dyn Error>> {
+    let edges: Vec<(Option<&LockedPackage>, &LockedDependency)> = lockfile
+        .requested
+        .iter()
+        .map(|x| (None, x))
+        .chain(
+            lockfile
+                .packages
+                .iter()
+                .flat_map(|x| x.dependencies.iter().map(move |y| (Some(x), y))),
+        )
+        .collect();
+
+    let mut markers: Vec<(String, String)> = Vec::new();
+
+    for (parent, dependency) in edges.iter() {
+        if let Some(marker) = &dependency.marker {
+            for extra in std::iter::once("").chain(
+                parent
+                    .iter()
+                    .flat_map(|x| x.extras.iter().map(|x| x.as_str())),
+            ) {
+                markers.push((marker.clone(), extra.to_string()));
+            }
+        }
+    }
+
+    let mut evaluated = if markers.is_empty() {
+        Vec::new()
+    } else {
+        pip::evaluate_markers(python, &markers)?
+    }
+    .into_iter();
+
+    let mut holds: Vec<bool> = Vec::new();
+
+    for (parent, dependency) in edges.iter() {
+        holds.push(match dependency.marker {
+            Some(_) => {
+                let contexts = 1 + parent.map(|x| x.extras.len()).unwrap_or_default();
+                (0..contexts).fold(false, |a, _| evaluated.next().unwrap_or(false) || a)
+            }
+            None => true,
+        });
+    }
+
+    let mut selected: HashSet<String> = HashSet::new();
+    let mut queue: VecDeque<&str> = VecDeque::new();
+
+    for ((parent, dependency), holds) in edges.iter().zip(holds.iter()) {
+        if parent.is_none() && *holds {
+            queue.push_back(dependency.name.as_str());
+        } else if parent.is_none() {
+            println!(
+                "Skipping {}: marker not satisfied (Marker={})",
+                dependency.name,
+                dependency.marker.clone().unwrap_or_default()
+            );
+        }
+    }
+
+    while let Some(name) = queue.pop_front() {
+        if !selected.insert(name.to_string()) {
+            continue;
+        }
+
+        for ((parent, dependency), holds) in edges.iter().zip(holds.iter()) {
+            if *holds && parent.is_some_and(|x| x.name == name) {
+                queue.push_back(dependency.name.as_str());
+            }
+        }
+    }
+
+    Result::Ok(selected)
+}
+
+/// Adds an edge to the dependency graph. A package required under several markers is required
+/// where any of them holds, and unconditionally once any requirement is.
+fn add_dependency(dependencies: &mut Vec<LockedDependency>, name: &str, marker: Option<String>) {
+    match dependencies.iter_mut().find(|x| x.name == name) {
+        None => dependencies.push(LockedDependency {
+            name: name.to_string(),
+            marker,
+        }),
+        Some(existing) => {
+            existing.marker = match (existing.marker.take(), marker) {
+                (Some(a), Some(b)) if a == b => Some(a),
+                (Some(a), Some(b)) => Some(format!("({}) or ({})", a, b)),
+                _ => None,
+            }
+        }
+    }
+}
+
+fn satisfies(index: &Index, package: &Package, version: &str) -> bool {
+    match &package.version {
+        PackageVersion::Latest => true,
+        PackageVersion::Literal(literal) => literal == version,
+        PackageVersion::Requirement(requirement) => index
+            .find(&package.name, version)
+            .and_then(|x| x.semantic_version())
+            .is_some_and(|x| requirement.matches(&x)),
+        PackageVersion::Tag(tag) => {
+            index
+                .metadata()
+                .get_tag(&package.name, tag)
+                .map(|x| x.as_str())
+                == Some(version)
+        }
+    }
+}
+
+/// Parses `name [extras] (specifier) ; marker` as well as the unparenthesized form.
+fn parse_dependency(s: &str) -> Option<Dependency> {
+    let (requirement, marker) = match s.split_once(';') {
+        Some((requirement, marker)) => (requirement, Some(marker.trim().to_string())),
+        None => (s, None),
+    };
+    let captures = Regex::new(r"^\s*([A-Za-z0-9][A-Za-z0-9._-]*)\s*(?:\[([^\]]*)\])?\s*(.*?)\s*$")
+        .unwrap()
+        .captures(requirement)?;
+    let specifier = captures[3]
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .replace(' ', "");
+
+    Some(Dependency {
+        name: captures[1].to_string(),
+        extras: captures
+            .get(2)
+            .map(|x| {
+                x.as_str()
+                    .split(',')
+                    .map(|x| x.trim().to_string())
+                    .filter(|x| !x.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        specifier,
+        marker,
+    })
+}
+
+/// The extras a marker such as `extra == "gpu"` makes a dependency conditional on.
+fn marker_extras(marker: &str) -> Vec<String> {
+    Regex::new(r#"\bextra\s*==\s*["']([^"']*)["']"#)
+        .unwrap()
+        .captures_iter(marker)
+        .map(|x| x[1].to_string())
+        .collect()
+}
+
+/// Extra names are compared the way project names are, see PEP 685.
+fn contains_extra(extras: &[String], extra: &str) -> bool {
+    extras
+        .iter()
+        .any(|x| normalize_name(x) == normalize_name(extra))
+}
+
+/// Orders the packages so every package comes after the dependencies it pulls from the bucket,
+/// which lets pip find them already installed.
+fn install_order(
+    requested: &[LockedDependency],
+    locked: &BTreeMap<String, LockedPackage>,
+) -> Vec<LockedPackage> {
+    fn visit(
+        name: &str,
+        locked: &BTreeMap<String, LockedPackage>,
+        seen: &mut HashSet<String>,
+        result: &mut Vec<LockedPackage>,
+    ) {
+        if !seen.insert(name.to_string()) {
+            return;
+        }
+
+        if let Some(package) = locked.get(name) {
+            for dependency in package.dependencies.iter() {
+                visit(&dependency.name, locked, seen, result);
+            }
+            result.push(package.clone());
+        }
+    }
+
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut result: Vec<LockedPackage> = Vec::new();
+
+    for dependency in requested {
+        visit(&dependency.name, locked, &mut seen, &mut result);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::write_sdist_requiring;
+    use crate::storage::memory::MemoryStorageDriver;
+    use std::str::FromStr;
+
+    fn config() -> RuntimeConfig {
+        RuntimeConfig {
+            bucket: Some("bucket".to_string()),
+            ..RuntimeConfig::default()
+        }
+    }
+
+    /// Publishes sdists declaring the given dependencies straight to the bucket.
+    async fn bucket(packages: &[(&str, &str, &[&str])]) -> MemoryStorageDriver {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let driver = MemoryStorageDriver::default();
+
+        for (name, version, requires_dist) in packages {
+            let filename = format!("{}-{}.tar.gz", name, version);
+            let path = write_sdist_requiring(dir.path(), &filename, name, version, requires_dist);
+
+            driver
+                .put(
+                    "bucket",
+                    &format!("{}/{}", name, filename),
+                    bytes::Bytes::from(std::fs::read(path).unwrap()),
+                )
+                .await
+                .unwrap();
+        }
+
+        driver
+    }
+
+    #[tokio::test]
+    async fn test_lock() {
+        let driver = bucket(&[
+            (
+                "app",
+                "1.0.0",
+                &[
+                    "lib (>=1.0)",
+                    "gpu-kit ; extra == \"gpu\"",
+                    "requests (>=2.0)",
+                ],
+            ),
+            ("lib", "1.0.0", &[]),
+            ("lib", "1.1.0", &["base", "speedups ; extra == \"fast\""]),
+            ("base", "2.0.0", &[]),
+            ("gpu_kit", "1.0.0", &["lib[fast] (<2.0)"]),
+            ("speedups", "1.0.0", &[]),
+        ])
+        .await;
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let output = dir.path().join(LOCKFILE);
+        let locked = |package: &str| {
+            let (driver, output) = (&driver, &output);
+            let package = Package::from_str(package).unwrap();
+            async move {
+                lock(&config(), driver, vec![package], Vec::new(), output)
+                    .await
+                    .unwrap();
+                let lockfile: Lockfile =
+                    serde_json::from_slice(&std::fs::read(output).unwrap()).unwrap();
+                lockfile
+                    .packages
+                    .into_iter()
+                    .map(|x| format!("{}@{}", x.name, x.version))
+                    .collect::<Vec<String>>()
+            }
+        };
+
+        // Dependencies that aren't in the bucket, like requests, are left to pip
+        assert_eq!(
+            locked("app").await,
+            vec!["base@2.0.0", "lib@1.1.0", "app@1.0.0"]
+        );
+        // The extra pulls in gpu_kit, which in turn asks for an extra of lib
+        assert_eq!(
+            locked("app[gpu]").await,
+            vec![
+                "base@2.0.0",
+                "speedups@1.0.0",
+                "lib@1.1.0",
+                "gpu_kit@1.0.0",
+                "app@1.0.0"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_dependency() {
+        let dependency =
+            |name: &str, extras: &[&str], specifier: &str, marker: Option<&str>| Dependency {
+                name: name.to_string(),
+                extras: extras.iter().map(|x| x.to_string()).collect(),
+                specifier: specifier.to_string(),
+                marker: marker.map(|x| x.to_string()),
+            };
+
+        assert_eq!(
+            parse_dependency("requests (>=2.0, <3)"),
+            Some(dependency("requests", &[], ">=2.0,<3", None))
+        );
+        assert_eq!(
+            parse_dependency("my-lib[gpu]>=1.2; extra == \"cuda\""),
+            Some(dependency(
+                "my-lib",
+                &["gpu"],
+                ">=1.2",
+                Some("extra == \"cuda\"")
+            ))
+        );
+        assert_eq!(
+            parse_dependency("click ; python_version < \"3.8\""),
+            Some(dependency(
+                "click",
+                &[],
+                "",
+                Some("python_version < \"3.8\"")
+            ))
+        );
+        assert_eq!(parse_dependency("(>=1.0)"), None);
+
+        assert_eq!(
+            marker_extras("python_version >= \"3.8\" and (extra == 'gpu' or extra == \"CUDA\")"),
+            vec!["gpu", "CUDA"]
+        );
+        assert!(marker_extras("python_version >= \"3.8\"").is_empty());
+        assert!(contains_extra(
+            &["Cuda_Support".to_string()],
+            "cuda-support"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_install_locked() {
+        let driver = bucket(&[("app", "1.0.0", &["lib (>=1.0)"]), ("lib", "1.0.0", &[])]).await;
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let path = dir.path().join(LOCKFILE);
+        let config = RuntimeConfig {
+            python: Some(pip::stub_python(dir.path(), 0, 0)),
+            ..config()
+        };
+
+        lock(
+            &config,
+            &driver,
+            vec![Package::from_str("app").unwrap()],
+            Vec::new(),
+            &path,
+        )
+        .await
+        .unwrap();
+        install_locked(&config, &driver, &path, false)
+            .await
+            .unwrap();
+
+        driver.insert("bucket", "lib/lib-1.0.0.tar.gz", "tampered");
+        let error = install_locked(&config, &driver, &path, false)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("sha256 mismatch for memory://bucket/lib/lib-1.0.0.tar.gz"),
+            "{}",
+            error
+        );
+
+        driver
+            .delete("bucket", "lib/lib-1.0.0.tar.gz")
+            .await
+            .unwrap();
+        let error = install_locked(&config, &driver, &path, false)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("failed to download memory://bucket/lib/lib-1.0.0.tar.gz"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_read_locked_packages() {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let path = dir.path().join("sling.lock");
+        let read = |content: &str| {
+            std::fs::write(&path, content).unwrap();
+            read_locked_packages(&path)
+        };
+
+        let packages = read(
+            r#"{"format_version": 2, "created": "", "source": "s3://bucket", "requested": [],
+                "packages": [{"name": "pkg", "version": "1.0.0", "url": "s3://bucket/pkg/pkg-1.0.0.tar.gz",
+                              "sha256": "abc", "size": 1}]}"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].to_string(), "pkg@1.0.0");
+
+        assert!(read("pkg==1.0.0\n").unwrap().is_none());
+        assert_eq!(
+            read(r#"{"format_version": 1, "packages": {}}"#)
+                .unwrap_err()
+                .to_string(),
+            "unsupported lockfile format version: 1"
+        );
+        assert!(read(r#"{"format_version": 2}"#).is_err());
+    }
+
+    #[test]
+    fn test_add_dependency() {
+        let win = "sys_platform == \"win32\"";
+        let old = "python_version < \"3.8\"";
+        let mut dependencies: Vec<LockedDependency> = Vec::new();
+
+        add_dependency(&mut dependencies, "winlib", Some(win.to_string()));
+        add_dependency(&mut dependencies, "winlib", Some(win.to_string()));
+        assert_eq!(dependencies[0].marker.as_deref(), Some(win));
+
+        add_dependency(&mut dependencies, "winlib", Some(old.to_string()));
+        assert_eq!(
+            dependencies[0].marker,
+            Some(format!("({}) or ({})", win, old))
+        );
+
+        add_dependency(&mut dependencies, "winlib", None);
+        add_dependency(&mut dependencies, "winlib", Some(win.to_string()));
+        assert_eq!(
+            dependencies,
+            vec![LockedDependency {
+                name: "winlib".to_string(),
+                marker: None,
+            }]
+        );
+    }
+}
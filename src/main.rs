@@ -1,61 +1,58 @@
 mod active_config;
+mod archive;
 mod aws;
+mod bucket_metadata;
+mod bundle;
+mod check;
 mod config;
+mod delete;
+mod digest;
+mod distribution;
+mod duration;
 mod gcp;
+mod http;
+mod import;
 mod index;
 mod install;
+mod list;
+mod lock;
+mod metadata;
 mod package;
 mod package_version;
+mod pattern;
 mod pip;
+mod presign;
+mod project;
+mod promote;
+mod prune;
 mod publish;
+mod requirements;
 mod runtime_config;
 mod semantic_version;
+mod serve;
+mod show;
+mod simple;
 mod storage;
+mod sync;
+mod tag;
+mod ticket;
+mod upload;
+mod version_requirement;
 mod yaml;
+mod yank;
 
 use clap::Parser;
 use clap::Subcommand;
 use std::borrow::Borrow;
-use std::fmt::Formatter;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use crate::active_config::ActiveConfig;
 use crate::config::Config;
 use crate::runtime_config::RuntimeConfig;
 use crate::storage::driver::StorageDriver;
-use crate::storage::gs::GoogleStorageDriver;
-use crate::storage::s3::S3StorageDriver;
-
-#[derive(Clone, Debug)]
-enum StorageDriverParseError {
-    InvalidStorageDriver,
-}
-
-impl std::error::Error for StorageDriverParseError {}
-
-impl std::fmt::Display for StorageDriverParseError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
-    }
-}
-
-#[derive(Clone, Debug)]
-enum AvailableStorageDrivers {
-    GS,
-    S3,
-}
-
-impl FromStr for AvailableStorageDrivers {
-    type Err = StorageDriverParseError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_uppercase().as_str() {
-            "GS" => Ok(AvailableStorageDrivers::GS),
-            "S3" => Ok(AvailableStorageDrivers::S3),
-            _ => Err(StorageDriverParseError::InvalidStorageDriver),
-        }
-    }
-}
+use crate::storage::location::StorageLocation;
+use crate::storage::AvailableStorageDrivers;
 
 #[derive(Parser, Clone, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -67,23 +64,271 @@ struct Args {
     command: Commands,
 
     #[clap(short, long)]
-    driver: AvailableStorageDrivers,
+    driver: Option<AvailableStorageDrivers>,
+
+    #[clap(long)]
+    profile: Option<String>,
 }
 
 #[derive(Subcommand, Clone, Debug)]
 enum Commands {
+    Bundle {
+        #[clap(subcommand)]
+        command: BundleCommands,
+    },
+
+    Delete {
+        #[clap(long)]
+        all: bool,
+
+        #[clap(long)]
+        dry_run: bool,
+
+        #[clap(long)]
+        force: bool,
+
+        #[clap(short, long)]
+        yes: bool,
+
+        package: package::Package,
+    },
+
     Get {
         #[clap(short, long)]
         text_files: Vec<String>,
 
+        #[clap(long)]
+        project: Option<String>,
+
+        #[clap(long)]
+        keep_going: bool,
+
+        #[clap(long, conflicts_with_all = &["text-files", "project", "packages"])]
+        locked: bool,
+
         packages: Vec<package::Package>,
     },
 
+    GrantUpload {
+        #[clap(long, default_value = "30m", parse(try_from_str = duration::parse_duration))]
+        expires: std::time::Duration,
+
+        package: package::Package,
+    },
+
+    List {
+        #[clap(long)]
+        json: bool,
+
+        package: Option<String>,
+    },
+
+    Lock {
+        #[clap(short = 'r', long = "requirement")]
+        requirement_files: Vec<String>,
+
+        #[clap(short, long, default_value = lock::LOCKFILE)]
+        output: String,
+
+        packages: Vec<package::Package>,
+    },
+
+    Promote {
+        #[clap(long)]
+        from: Option<String>,
+
+        #[clap(long)]
+        to: String,
+
+        #[clap(short = 'y', long)]
+        overwrite: bool,
+
+        #[clap(long)]
+        no_latest: bool,
+
+        package: package::Package,
+    },
+
+    Prune {
+        #[clap(long)]
+        keep_last: Option<usize>,
+
+        #[clap(long, parse(try_from_str = duration::parse_duration))]
+        prerelease_max_age: Option<std::time::Duration>,
+
+        #[clap(long)]
+        keep_pinned: Vec<String>,
+
+        #[clap(long)]
+        dry_run: bool,
+
+        #[clap(short, long)]
+        yes: bool,
+
+        packages: Vec<String>,
+    },
+
+    Import {
+        #[clap(long, default_value = "https://pypi.org/simple/")]
+        from: url::Url,
+
+        #[clap(short = 'y', long)]
+        overwrite: bool,
+
+        #[clap(long)]
+        no_latest: bool,
+
+        requirement: String,
+    },
+
+    Publish {
+        #[clap(short = 'y', long)]
+        overwrite: bool,
+
+        #[clap(long)]
+        no_latest: bool,
+
+        #[clap(short, long, default_value_t = 4)]
+        jobs: usize,
+
+        #[clap(default_value = ".")]
+        path: String,
+    },
+
     Put {
         #[clap(short = 'y', long)]
         overwrite: bool,
 
-        package_path: String,
+        #[clap(long)]
+        no_latest: bool,
+
+        #[clap(short, long, default_value_t = 4)]
+        jobs: usize,
+
+        #[clap(long)]
+        ticket: Option<url::Url>,
+
+        #[clap(required = true)]
+        package_paths: Vec<String>,
+    },
+
+    Search {
+        #[clap(short, long)]
+        regex: bool,
+
+        #[clap(long)]
+        json: bool,
+
+        query: String,
+    },
+
+    Serve {
+        #[clap(long, default_value = "127.0.0.1")]
+        host: String,
+
+        #[clap(long, default_value_t = 8080)]
+        port: u16,
+
+        #[clap(long)]
+        upstream: Option<url::Url>,
+
+        #[clap(long)]
+        allow: Vec<String>,
+
+        #[clap(long, default_value = "100", parse(try_from_str = upload::parse_max_size))]
+        max_upload_size: u64,
+    },
+
+    Show {
+        package: package::Package,
+    },
+
+    Sync {
+        #[clap(long)]
+        from: String,
+
+        #[clap(long)]
+        to: String,
+
+        #[clap(long)]
+        package: Vec<String>,
+
+        #[clap(long)]
+        version: Option<version_requirement::VersionRequirement>,
+
+        #[clap(short, long, default_value_t = 4)]
+        jobs: usize,
+
+        #[clap(long)]
+        dry_run: bool,
+
+        #[clap(long)]
+        delete: bool,
+    },
+
+    Tag {
+        package: package::Package,
+
+        tag: String,
+    },
+
+    Untag {
+        name: String,
+
+        tag: String,
+    },
+
+    Unyank {
+        package: package::Package,
+    },
+
+    Url {
+        #[clap(long, default_value = "1h", parse(try_from_str = duration::parse_duration))]
+        expires: std::time::Duration,
+
+        #[clap(long)]
+        requirements: bool,
+
+        #[clap(short = 'r', long = "requirement")]
+        requirement_files: Vec<String>,
+
+        #[clap(short, long)]
+        output: Option<String>,
+
+        packages: Vec<package::Package>,
+    },
+
+    Yank {
+        #[clap(long)]
+        reason: Option<String>,
+
+        package: package::Package,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+enum BundleCommands {
+    Export {
+        #[clap(short = 'r', long = "requirement")]
+        requirements: Vec<String>,
+
+        #[clap(short, long)]
+        output: String,
+
+        packages: Vec<package::Package>,
+    },
+
+    Import {
+        #[clap(long)]
+        install: bool,
+
+        #[clap(short = 'y', long)]
+        overwrite: bool,
+
+        #[clap(long)]
+        no_latest: bool,
+
+        path: String,
     },
 }
 
@@ -122,31 +367,46 @@ async fn main() -> Result<(), String> {
         )
     })?;
 
+    let profile = match &args.profile {
+        Some(name) => config
+            .get()
+            .profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Profile not found. Name={}", name))?,
+        None => config::Profile::default(),
+    };
+
     let runtime_config = RuntimeConfig::resolve(
         args.runtime.clone(),
-        config.get().as_runtime_config(),
+        profile
+            .as_runtime_config()
+            .or(config.get().as_runtime_config()),
         RuntimeConfig::default(),
     );
 
-    let driver: Box<dyn StorageDriver> = match args.driver {
-        AvailableStorageDrivers::GS => Box::new(GoogleStorageDriver::new(
-            gcp::get_gs_client()
-                .await
-                .map_err(|e| format!("failed to initialize gs driver: {}", e))?,
-        )),
-        AvailableStorageDrivers::S3 => {
-            Box::new(S3StorageDriver::new(aws::get_s3_client().await.map_err(
-                |e| format!("failed to initialize aws driver: {}", e),
-            )?))
+    let driver_kind = match (&args.driver, &profile.driver) {
+        (Some(driver), _) => Some(driver.clone()),
+        (None, Some(driver)) => {
+            Some(AvailableStorageDrivers::from_str(driver).map_err(|e| {
+                format!("Invalid driver in profile. Driver={}, Error={}", driver, e)
+            })?)
         }
+        (None, None) => None,
     };
 
     match args.command {
-        Commands::Get {
-            text_files,
-            mut packages,
+        Commands::Bundle {
+            command:
+                BundleCommands::Export {
+                    requirements,
+                    output,
+                    mut packages,
+                },
         } => {
-            for f in text_files.iter().map(std::path::PathBuf::from) {
+            let driver = connect(&driver_kind, &profile).await?;
+
+            for f in requirements.iter().map(std::path::PathBuf::from) {
                 match package::read_packages_from_file(&f) {
                     Result::Ok(mut new) => packages.append(&mut new),
                     Result::Err(e) => {
@@ -158,34 +418,563 @@ async fn main() -> Result<(), String> {
                 }
             }
 
-            match install::install(&runtime_config, driver.borrow(), packages).await {
+            let output = std::path::PathBuf::from(output);
+
+            match bundle::export(&runtime_config, driver.borrow(), packages, &output).await {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to export bundle. Error={}", e))
+                }
+            }
+        }
+        Commands::Bundle {
+            command:
+                BundleCommands::Import {
+                    install,
+                    overwrite,
+                    no_latest,
+                    path,
+                },
+        } => {
+            let driver = if install {
+                None
+            } else {
+                Some(connect(&driver_kind, &profile).await?)
+            };
+
+            let path = std::path::PathBuf::from(path);
+
+            match bundle::import(
+                &runtime_config,
+                driver.as_deref(),
+                &path,
+                overwrite,
+                !no_latest,
+            )
+            .await
+            {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to import bundle. Error={}", e))
+                }
+            }
+        }
+        Commands::Delete {
+            all,
+            dry_run,
+            force,
+            yes,
+            package,
+        } => {
+            let driver = connect(&driver_kind, &profile).await?;
+
+            let options = delete::DeleteOptions {
+                all,
+                dry_run,
+                force,
+                yes,
+            };
+
+            match delete::delete(&runtime_config, driver.borrow(), &package, options).await {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to delete package. Error={}", e))
+                }
+            }
+        }
+        Commands::Get {
+            locked: true,
+            keep_going,
+            ..
+        } => {
+            let driver = connect(&driver_kind, &profile).await?;
+            let path = std::path::PathBuf::from(lock::LOCKFILE);
+
+            match lock::install_locked(&runtime_config, driver.borrow(), &path, keep_going).await {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!(
+                        "Failed to install locked packages. Path={:?}, Error={}",
+                        path, e
+                    ))
+                }
+            }
+        }
+        Commands::Get {
+            text_files,
+            project,
+            keep_going,
+            packages,
+            ..
+        } => {
+            let driver = connect(&driver_kind, &profile).await?;
+            let mut requirements = Vec::new();
+
+            for f in text_files.iter().map(std::path::PathBuf::from) {
+                match requirements::RequirementsFile::read(&f) {
+                    Result::Ok(new) => requirements.push(new),
+                    Result::Err(e) => {
+                        return Result::Err(format!(
+                            "Failed to read package file. Path={:?}, Error={}",
+                            f, e
+                        ))
+                    }
+                }
+            }
+
+            if let Some(dir) = project.map(std::path::PathBuf::from) {
+                match project::read_dependencies(&dir) {
+                    Result::Ok(new) => requirements.push(new),
+                    Result::Err(e) => {
+                        return Result::Err(format!(
+                            "Failed to read project dependencies. Path={:?}, Error={}",
+                            dir, e
+                        ))
+                    }
+                }
+            }
+
+            match install::install(
+                &runtime_config,
+                driver.borrow(),
+                packages,
+                requirements,
+                keep_going,
+            )
+            .await
+            {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to install package. Error={}", e))
+                }
+            }
+        }
+        Commands::GrantUpload { expires, package } => {
+            let driver = connect(&driver_kind, &profile).await?;
+
+            match ticket::grant_upload(&runtime_config, driver.borrow(), &package, expires).await {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to grant upload. Error={}", e))
+                }
+            }
+        }
+        Commands::Promote {
+            from,
+            to,
+            overwrite,
+            no_latest,
+            package,
+        } => {
+            let source = match from {
+                Some(from) => StorageLocation::resolve(&from, config.get())
+                    .map_err(|e| format!("Invalid source. Error={}", e))?,
+                None => match (&driver_kind, &runtime_config.bucket) {
+                    (Some(driver), Some(bucket)) => StorageLocation {
+                        driver: driver.clone(),
+                        bucket: bucket.clone(),
+                        profile: None,
+                    },
+                    _ => {
+                        return Result::Err(
+                            "No source was provided. Use --from or --driver and --bucket."
+                                .to_string(),
+                        )
+                    }
+                },
+            };
+            let target = StorageLocation::resolve(&to, config.get())
+                .map_err(|e| format!("Invalid target. Error={}", e))?;
+            let source_driver =
+                storage::create_driver(&source.driver, source.profile.as_ref().unwrap_or(&profile))
+                    .await?;
+            let target_driver =
+                storage::create_driver(&target.driver, target.profile.as_ref().unwrap_or(&profile))
+                    .await?;
+            let options = promote::PromoteOptions {
+                overwrite,
+                update_latest: !no_latest,
+            };
+
+            match promote::promote(
+                &source,
+                source_driver.borrow(),
+                &target,
+                target_driver.borrow(),
+                &package,
+                options,
+            )
+            .await
+            {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to promote package. Error={}", e))
+                }
+            }
+        }
+        Commands::Prune {
+            keep_last,
+            prerelease_max_age,
+            keep_pinned,
+            dry_run,
+            yes,
+            packages,
+        } => {
+            let driver = connect(&driver_kind, &profile).await?;
+
+            let policy = prune::PrunePolicy {
+                keep_last,
+                prerelease_max_age,
+                keep_pinned: keep_pinned.iter().map(std::path::PathBuf::from).collect(),
+            };
+            let options = prune::PruneOptions {
+                packages,
+                dry_run,
+                yes,
+            };
+
+            match prune::prune(&runtime_config, driver.borrow(), policy, options).await {
                 Result::Ok(_) => (),
                 Result::Err(e) => {
-                    return Result::Err(format!("Failed to install package. Error={:?}", e))
+                    return Result::Err(format!("Failed to prune packages. Error={}", e))
+                }
+            }
+        }
+        Commands::Import {
+            from,
+            overwrite,
+            no_latest,
+            requirement,
+        } => {
+            let driver = connect(&driver_kind, &profile).await?;
+            let package = import::parse_requirement(&requirement)
+                .map_err(|e| format!("Invalid requirement. Error={}", e))?;
+
+            match import::import(
+                &runtime_config,
+                driver.borrow(),
+                &package,
+                &from,
+                overwrite,
+                !no_latest,
+            )
+            .await
+            {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to import package. Error={}", e))
+                }
+            }
+        }
+        Commands::Publish {
+            overwrite,
+            no_latest,
+            jobs,
+            path,
+        } => {
+            let driver = connect(&driver_kind, &profile).await?;
+
+            let path = std::path::PathBuf::from(path);
+            let options = publish::PublishOptions {
+                overwrite,
+                update_latest: !no_latest,
+                jobs,
+            };
+
+            match publish::publish_project(&runtime_config, driver.borrow(), &path, options).await {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to publish project. Error={}", e))
+                }
+            }
+        }
+        Commands::Put {
+            ticket: Some(ticket),
+            package_paths,
+            ..
+        } => {
+            let path = match package_paths.as_slice() {
+                [path] => std::path::PathBuf::from(path),
+                _ => {
+                    return Result::Err(
+                        "Exactly one package file can be uploaded with a ticket.".to_string(),
+                    )
+                }
+            };
+
+            match ticket::upload(&path, &ticket).await {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to upload package. Error={}", e))
                 }
             }
         }
         Commands::Put {
             overwrite,
-            package_path,
+            no_latest,
+            jobs,
+            ticket: None,
+            package_paths,
         } => {
-            let path = std::path::PathBuf::from(package_path);
+            let driver = connect(&driver_kind, &profile).await?;
 
-            if !path.exists() {
+            let paths = publish::collect_files(&package_paths)
+                .map_err(|e| format!("Failed to read package paths. Error={}", e))?;
+
+            if let Some(path) = paths.iter().find(|x| !x.exists()) {
                 return Result::Err(format!(
                     "Python package not found at provided location. Path={:?}",
                     path
                 ));
             }
 
-            match publish::publish(&runtime_config, driver.borrow(), &path, overwrite).await {
+            let options = publish::PublishOptions {
+                overwrite,
+                update_latest: !no_latest,
+                jobs,
+            };
+
+            match publish::publish(&runtime_config, driver.borrow(), &paths, options).await {
                 Result::Ok(_) => (),
                 Result::Err(e) => {
                     return Result::Err(format!("Failed to publish package. Error={}", e))
                 }
             }
         }
+        Commands::List { json, package } => {
+            let driver = connect(&driver_kind, &profile).await?;
+
+            match list::list(&runtime_config, driver.borrow(), package, json).await {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to list packages. Error={}", e))
+                }
+            }
+        }
+        Commands::Lock {
+            requirement_files,
+            output,
+            packages,
+        } => {
+            let driver = connect(&driver_kind, &profile).await?;
+            let mut requirements = Vec::new();
+
+            for f in requirement_files.iter().map(std::path::PathBuf::from) {
+                match requirements::RequirementsFile::read(&f) {
+                    Result::Ok(new) => requirements.push(new),
+                    Result::Err(e) => {
+                        return Result::Err(format!(
+                            "Failed to read package file. Path={:?}, Error={}",
+                            f, e
+                        ))
+                    }
+                }
+            }
+
+            let output = std::path::PathBuf::from(output);
+
+            match lock::lock(
+                &runtime_config,
+                driver.borrow(),
+                packages,
+                requirements,
+                &output,
+            )
+            .await
+            {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to lock packages. Error={}", e))
+                }
+            }
+        }
+        Commands::Search { regex, json, query } => {
+            let driver = connect(&driver_kind, &profile).await?;
+
+            match list::search(&runtime_config, driver.borrow(), &query, regex, json).await {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to search packages. Error={}", e))
+                }
+            }
+        }
+        Commands::Show { package } => {
+            let driver = connect(&driver_kind, &profile).await?;
+
+            match show::show(&runtime_config, driver.borrow(), package).await {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to show package. Error={}", e))
+                }
+            }
+        }
+        Commands::Serve {
+            host,
+            port,
+            upstream,
+            allow,
+            max_upload_size,
+        } => {
+            let driver = connect(&driver_kind, &profile).await?;
+
+            let address = format!("{}:{}", host, port)
+                .parse()
+                .map_err(|e| format!("Invalid server address. Error={}", e))?;
+
+            let upstream = match upstream {
+                Some(url) => Some(
+                    serve::Upstream::new(url, &allow)
+                        .map_err(|e| format!("Invalid upstream. Error={}", e))?,
+                ),
+                None => None,
+            };
+
+            match serve::serve(
+                &runtime_config,
+                Arc::from(driver),
+                address,
+                upstream,
+                config.get().upload_tokens.clone(),
+                max_upload_size,
+            )
+            .await
+            {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to serve index. Error={}", e))
+                }
+            }
+        }
+        Commands::Sync {
+            from,
+            to,
+            package,
+            version,
+            jobs,
+            dry_run,
+            delete,
+        } => {
+            let source = StorageLocation::resolve(&from, config.get())
+                .map_err(|e| format!("Invalid source. Error={}", e))?;
+            let target = StorageLocation::resolve(&to, config.get())
+                .map_err(|e| format!("Invalid target. Error={}", e))?;
+            let source_driver =
+                storage::create_driver(&source.driver, source.profile.as_ref().unwrap_or(&profile))
+                    .await?;
+            let target_driver =
+                storage::create_driver(&target.driver, target.profile.as_ref().unwrap_or(&profile))
+                    .await?;
+            let options = sync::SyncOptions {
+                packages: package,
+                version,
+                jobs,
+                dry_run,
+                delete,
+            };
+
+            match sync::sync(
+                &source,
+                source_driver.borrow(),
+                &target,
+                target_driver.borrow(),
+                options,
+            )
+            .await
+            {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to sync buckets. Error={}", e))
+                }
+            }
+        }
+        Commands::Tag { package, tag } => {
+            let driver = connect(&driver_kind, &profile).await?;
+
+            match tag::tag(&runtime_config, driver.borrow(), &package, &tag).await {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to tag package. Error={}", e))
+                }
+            }
+        }
+        Commands::Untag { name, tag } => {
+            let driver = connect(&driver_kind, &profile).await?;
+
+            match tag::untag(&runtime_config, driver.borrow(), &name, &tag).await {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to untag package. Error={}", e))
+                }
+            }
+        }
+        Commands::Unyank { package } => {
+            let driver = connect(&driver_kind, &profile).await?;
+
+            match yank::unyank(&runtime_config, driver.borrow(), &package).await {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to unyank package. Error={}", e))
+                }
+            }
+        }
+        Commands::Url {
+            expires,
+            requirements,
+            requirement_files,
+            output,
+            mut packages,
+        } => {
+            let driver = connect(&driver_kind, &profile).await?;
+
+            for f in requirement_files.iter().map(std::path::PathBuf::from) {
+                match package::read_packages_from_file(&f) {
+                    Result::Ok(mut new) => packages.append(&mut new),
+                    Result::Err(e) => {
+                        return Result::Err(format!(
+                            "Failed to read package file. Path={:?}, Error={:?}",
+                            f, e
+                        ))
+                    }
+                }
+            }
+
+            let options = presign::UrlOptions {
+                expires,
+                requirements,
+                output: output.map(std::path::PathBuf::from),
+            };
+
+            match presign::urls(&runtime_config, driver.borrow(), packages, options).await {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to create urls. Error={}", e))
+                }
+            }
+        }
+        Commands::Yank { reason, package } => {
+            let driver = connect(&driver_kind, &profile).await?;
+
+            match yank::yank(&runtime_config, driver.borrow(), &package, reason).await {
+                Result::Ok(_) => (),
+                Result::Err(e) => {
+                    return Result::Err(format!("Failed to yank package. Error={}", e))
+                }
+            }
+        }
     }
 
     Ok(())
 }
+
+async fn connect(
+    driver: &Option<AvailableStorageDrivers>,
+    profile: &config::Profile,
+) -> Result<Box<dyn StorageDriver>, String> {
+    match driver {
+        Some(driver) => storage::create_driver(driver, profile).await,
+        None => Result::Err(
+            "No storage driver was provided. Use --driver or a --profile that defines one."
+                .to_string(),
+        ),
+    }
+}
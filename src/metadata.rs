@@ -0,0 +1,131 @@
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct CoreMetadata {
+    headers: Vec<(String, String)>,
+    pub(crate) description: Option<String>,
+}
+
+impl CoreMetadata {
+    pub(crate) fn parse(content: &str) -> CoreMetadata {
+        let mut headers: Vec<(String, String)> = Vec::new();
+        let mut lines = content.lines();
+
+        for line in lines.by_ref() {
+            if line.trim().is_empty() {
+                break;
+            }
+
+            if line.starts_with(' ') || line.starts_with('\t') {
+                if let Some(last) = headers.last_mut() {
+                    last.1.push('\n');
+                    last.1.push_str(line.trim());
+                }
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once(':') {
+                headers.push((key.trim().to_string(), value.trim().to_string()));
+            }
+        }
+
+        let body = lines.collect::<Vec<&str>>().join("\n");
+
+        CoreMetadata {
+            headers,
+            description: if body.trim().is_empty() {
+                None
+            } else {
+                Some(body)
+            },
+        }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|x| x.0.eq_ignore_ascii_case(key))
+            .map(|x| x.1.as_str())
+    }
+
+    pub(crate) fn get_all(&self, key: &str) -> Vec<&str> {
+        self.headers
+            .iter()
+            .filter(|x| x.0.eq_ignore_ascii_case(key))
+            .map(|x| x.1.as_str())
+            .collect()
+    }
+
+    pub(crate) fn metadata_version(&self) -> Option<&str> {
+        self.get("Metadata-Version")
+    }
+
+    pub(crate) fn name(&self) -> Option<&str> {
+        self.get("Name")
+    }
+
+    pub(crate) fn version(&self) -> Option<&str> {
+        self.get("Version")
+    }
+
+    pub(crate) fn summary(&self) -> Option<&str> {
+        self.get("Summary")
+    }
+
+    pub(crate) fn author(&self) -> Option<&str> {
+        self.get("Author").or_else(|| self.get("Author-email"))
+    }
+
+    pub(crate) fn license(&self) -> Option<&str> {
+        self.get("License-Expression")
+            .or_else(|| self.get("License"))
+    }
+
+    pub(crate) fn requires_python(&self) -> Option<&str> {
+        self.get("Requires-Python")
+    }
+
+    pub(crate) fn requires_dist(&self) -> Vec<&str> {
+        self.get_all("Requires-Dist")
+    }
+
+    pub(crate) fn classifiers(&self) -> Vec<&str> {
+        self.get_all("Classifier")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let metadata = CoreMetadata::parse(
+            "Metadata-Version: 2.1\n\
+             Name: pkg\n\
+             Version: 1.2.3\n\
+             Summary: A package\n\
+             License: MIT\n\
+             \x20       with a continuation\n\
+             Requires-Dist: requests (>=2.0)\n\
+             Requires-Dist: click\n\
+             Classifier: Programming Language :: Python :: 3\n\
+             \n\
+             # pkg\n\
+             \n\
+             The long description.\n",
+        );
+
+        assert_eq!(metadata.metadata_version(), Some("2.1"));
+        assert_eq!(metadata.name(), Some("pkg"));
+        assert_eq!(metadata.version(), Some("1.2.3"));
+        assert_eq!(metadata.license(), Some("MIT\nwith a continuation"));
+        assert_eq!(metadata.requires_dist(), vec!["requests (>=2.0)", "click"]);
+        assert_eq!(
+            metadata.classifiers(),
+            vec!["Programming Language :: Python :: 3"]
+        );
+        assert_eq!(
+            metadata.description,
+            Some("# pkg\n\nThe long description.".to_string())
+        );
+    }
+}
@@ -1,8 +1,7 @@
 use crate::package_version;
 use crate::package_version::PackageVersion;
-use std::fs::File;
-use std::io::BufRead;
-use std::path::Path;
+use crate::requirements::RequirementsFile;
+use crate::version_requirement::VersionRequirement;
 use std::str::FromStr;
 
 #[derive(Debug)]
@@ -13,7 +12,10 @@ pub(crate) enum ParsePackageError {
 impl ParsePackageError {
     pub(crate) fn description(&self) -> String {
         match self {
-            Self::InvalidFormat => "InvalidFormat: Use the format PKG[@(x.y.z|latest)]".to_string(),
+            Self::InvalidFormat => {
+                "InvalidFormat: Use the format PKG[@(x.y.z|latest|TAG|REQUIREMENT)], with extras as PKG[EXTRA,...]"
+                    .to_string()
+            }
         }
     }
 }
@@ -30,23 +32,11 @@ impl std::error::Error for ParsePackageError {}
 pub(crate) struct Package {
     pub(crate) name: String,
     pub(crate) version: package_version::PackageVersion,
+    /// Optional features of the project, installed along with their dependencies.
+    pub(crate) extras: Vec<String>,
 }
 
 impl Package {
-    pub(crate) fn from_file(path: &Path) -> Option<Package> {
-        let pattern =
-            regex::Regex::new("([a-zA-Z0-9_]+)-(\\d+\\.\\d+\\.\\d+)(\\.[a-zA-Z0-9]+)+$").unwrap();
-
-        pattern
-            .captures(path.file_name().unwrap().to_str().unwrap())
-            .map(|captures| Package {
-                name: captures.get(1).unwrap().clone().as_str().to_string(),
-                version: PackageVersion::Literal(
-                    captures.get(2).unwrap().clone().as_str().to_string(),
-                ),
-            })
-    }
-
     pub(crate) fn object_key(&self) -> String {
         format!("{}/{}", self.name, self.filename())
     }
@@ -62,7 +52,17 @@ impl Package {
 
 impl std::fmt::Display for Package {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}@{}", self.name, self.version)
+        if self.extras.is_empty() {
+            write!(f, "{}@{}", self.name, self.version)
+        } else {
+            write!(
+                f,
+                "{}[{}]@{}",
+                self.name,
+                self.extras.join(","),
+                self.version
+            )
+        }
     }
 }
 
@@ -70,31 +70,102 @@ impl FromStr for Package {
     type Err = ParsePackageError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<String> = s.split('@').into_iter().map(|s| s.to_string()).collect();
+        let parts: Vec<String> = s.split('@').map(|s| s.to_string()).collect();
         let (name, version) = match parts {
-            parts if parts.len() == 2 => (
-                parts[0].clone(),
-                package_version::PackageVersion::Literal(parts[1].clone()),
-            ),
+            parts if parts.len() == 2 => (parts[0].clone(), parse_version(parts[1].trim())?),
             parts if parts.len() == 1 => {
                 (parts[0].clone(), package_version::PackageVersion::Latest)
             }
             _ => return Result::Err(ParsePackageError::InvalidFormat),
         };
 
-        Result::Ok(Package { name, version })
+        let (name, extras) = parse_extras(name.trim())?;
+
+        Result::Ok(Package {
+            name,
+            version,
+            extras,
+        })
+    }
+}
+
+/// Splits `name[extra,...]` into the name and its extras.
+fn parse_extras(s: &str) -> Result<(String, Vec<String>), ParsePackageError> {
+    let (name, extras) = match s.strip_suffix(']').and_then(|x| x.split_once('[')) {
+        Some((name, extras)) => (
+            name.trim(),
+            extras
+                .split(',')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect(),
+        ),
+        None => (s, Vec::new()),
+    };
+
+    if name.is_empty() || name.contains(['[', ']']) {
+        return Result::Err(ParsePackageError::InvalidFormat);
+    }
+
+    Result::Ok((name.to_string(), extras))
+}
+
+fn parse_version(s: &str) -> Result<PackageVersion, ParsePackageError> {
+    if s == "latest" {
+        return Result::Ok(PackageVersion::Latest);
+    }
+
+    if !VersionRequirement::is_requirement(s) {
+        return Result::Ok(if s.starts_with(|c: char| c.is_ascii_digit()) {
+            PackageVersion::Literal(s.to_string())
+        } else {
+            PackageVersion::Tag(s.to_string())
+        });
     }
+
+    let requirement =
+        VersionRequirement::from_str(s).map_err(|_| ParsePackageError::InvalidFormat)?;
+
+    // An explicit `==x.y.z` pin behaves exactly like a literal version
+    Result::Ok(match requirement.as_exact() {
+        Some(version) => PackageVersion::Literal(version.to_string()),
+        None => PackageVersion::Requirement(requirement),
+    })
 }
 
+/// Reads the packages listed in a requirements file, see `RequirementsFile`. Urls, paths and
+/// options are skipped.
 pub(crate) fn read_packages_from_file(
     path: &std::path::Path,
 ) -> Result<Vec<Package>, std::boxed::Box<dyn std::error::Error>> {
-    let file = File::open(path)?;
-    let mut packages: Vec<Package> = Vec::new();
+    Result::Ok(RequirementsFile::read(path)?.packages())
+}
 
-    for line in std::io::BufReader::new(file).lines() {
-        packages.push(Package::from_str(line?.trim())?);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            parse_version("1.2.0").unwrap(),
+            PackageVersion::Literal("1.2.0".to_string())
+        );
+        assert_eq!(
+            parse_version("stable").unwrap(),
+            PackageVersion::Tag("stable".to_string())
+        );
+        assert_eq!(
+            parse_version("==1.2.0").unwrap(),
+            PackageVersion::Literal("1.2.0".to_string())
+        );
+        assert!(matches!(
+            parse_version("1.0,<2.0").unwrap(),
+            PackageVersion::Requirement(_)
+        ));
+        assert!(matches!(
+            parse_version(">=1.0").unwrap(),
+            PackageVersion::Requirement(_)
+        ));
     }
-
-    Result::Ok(packages)
 }
@@ -1,7 +1,11 @@
+use crate::version_requirement::VersionRequirement;
+
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) enum PackageVersion {
     Latest,
     Literal(String),
+    Requirement(VersionRequirement),
+    Tag(String),
 }
 
 impl std::fmt::Display for PackageVersion {
@@ -9,6 +13,8 @@ impl std::fmt::Display for PackageVersion {
         match self {
             Self::Latest => write!(f, "latest"),
             Self::Literal(v) => write!(f, "{}", v),
+            Self::Requirement(r) => write!(f, "{}", r),
+            Self::Tag(t) => write!(f, "{}", t),
         }
     }
 }
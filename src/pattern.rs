@@ -0,0 +1,32 @@
+use regex::Regex;
+
+/// Converts a shell style glob supporting `*` and `?` into an anchored regular expression.
+pub(crate) fn glob_to_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    let mut expression = String::from("^");
+
+    for c in pattern.chars() {
+        match c {
+            '*' => expression.push_str(".*"),
+            '?' => expression.push('.'),
+            c => expression.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    expression.push('$');
+    Regex::new(&expression)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_to_regex() {
+        let pattern = glob_to_regex("my_pkg*").unwrap();
+        assert!(pattern.is_match("my_pkg"));
+        assert!(pattern.is_match("my_pkg_extra"));
+        assert!(!pattern.is_match("other_my_pkg"));
+        assert!(glob_to_regex("pkg?").unwrap().is_match("pkg1"));
+        assert!(!glob_to_regex("a.b").unwrap().is_match("axb"));
+    }
+}
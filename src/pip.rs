@@ -1,13 +1,96 @@
+use std::collections::VecDeque;
 use std::error::Error;
+use std::io::BufRead;
 use std::path::Path;
-use std::process::Command;
+use std::process::{Command, Stdio};
 
+/// How many lines of pip's stderr are kept for the error of a failed install.
+const STDERR_TAIL_LINES: usize = 20;
+
+#[derive(Debug)]
+enum PipError {
+    BuildFailed(String),
+    InstallFailed(String, String, Vec<String>),
+    MarkerError(String),
+}
+
+impl std::fmt::Display for PipError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::BuildFailed(status) => write!(f, "python -m build failed: {}", status),
+            Self::InstallFailed(requirement, status, tail) => {
+                write!(f, "pip failed to install {}: {}", requirement, status)?;
+
+                for line in tail {
+                    write!(f, "\n  {}", line)?;
+                }
+
+                Result::Ok(())
+            }
+            Self::MarkerError(msg) => {
+                write!(f, "failed to evaluate environment markers: {}", msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipError {}
+
+/// Installs a downloaded package along with the dependencies of its `extras`.
 pub(crate) fn install_package(
     python: &str,
     pip_args: &str,
     path: &Path,
+    extras: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let requirement = with_extras(path.to_str().unwrap(), extras);
+    let filename = path
+        .file_name()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    println!("Installing {} (Interpreter={})", requirement, python);
+
+    pip_install(
+        python,
+        pip_args,
+        &[requirement.as_str()],
+        &with_extras(&filename, extras),
+    )
+}
+
+pub(crate) fn with_extras(requirement: &str, extras: &[String]) -> String {
+    if extras.is_empty() {
+        requirement.to_string()
+    } else {
+        format!("{}[{}]", requirement, extras.join(","))
+    }
+}
+
+/// Installs the requirements listed in a requirements file.
+pub(crate) fn install_requirements(
+    python: &str,
+    pip_args: &str,
+    path: &Path,
 ) -> Result<(), Box<dyn Error>> {
     let path = path.to_str().unwrap();
+
+    println!(
+        "Installing requirements from {} (Interpreter={})",
+        path, python
+    );
+
+    pip_install(python, pip_args, &["-r", path], path)
+}
+
+/// Runs `pip install`, passing its stderr through while keeping the last lines for the error
+/// returned when pip exits unsuccessfully.
+fn pip_install(
+    python: &str,
+    pip_args: &str,
+    args: &[&str],
+    requirement: &str,
+) -> Result<(), Box<dyn Error>> {
     let extra: Vec<&str> = pip_args
         .split_whitespace()
         .fold(Vec::new(), |mut result, x| {
@@ -18,18 +101,148 @@ pub(crate) fn install_package(
             result
         });
 
-    println!("Installing {} (Interpreter={})", path, python);
-
     let mut child = Command::new(python)
         .args(
             ["-m", "pip", "install", "--upgrade"]
                 .iter()
                 .chain(extra.iter())
-                .chain([path].iter()),
+                .chain(args.iter()),
         )
+        .stderr(Stdio::piped())
         .spawn()?;
 
-    child.wait()?;
+    let mut tail: VecDeque<String> = VecDeque::new();
+
+    if let Some(stderr) = child.stderr.take() {
+        for line in std::io::BufReader::new(stderr).split(b'\n') {
+            let line = String::from_utf8_lossy(&line?).trim_end().to_string();
+            eprintln!("{}", line);
+
+            if tail.len() == STDERR_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line);
+        }
+    }
+
+    let status = child.wait()?;
+
+    if !status.success() {
+        return Result::Err(
+            PipError::InstallFailed(
+                requirement.to_string(),
+                status.to_string(),
+                tail.into_iter().collect(),
+            )
+            .into(),
+        );
+    }
+
+    Result::Ok(())
+}
+
+/// Evaluates PEP 508 environment markers against the interpreter with the `packaging` library
+/// vendored by pip. Each marker is paired with the value `extra` takes while evaluating it.
+pub(crate) fn evaluate_markers(
+    python: &str,
+    markers: &[(String, String)],
+) -> Result<Vec<bool>, Box<dyn Error>> {
+    let output = Command::new(python)
+        .args([
+            "-c",
+            "import sys\n\
+             from pip._vendor.packaging.markers import Marker\n\
+             args = sys.argv[1:]\n\
+             for marker, extra in zip(args[::2], args[1::2]):\n    \
+             print(Marker(marker).evaluate({'extra': extra}))",
+        ])
+        .args(markers.iter().flat_map(|(marker, extra)| [marker, extra]))
+        .output()?;
+
+    if !output.status.success() {
+        return Result::Err(
+            PipError::MarkerError(String::from_utf8_lossy(&output.stderr).trim().to_string())
+                .into(),
+        );
+    }
+
+    Result::Ok(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|x| x.trim() == "True")
+            .collect(),
+    )
+}
+
+/// Builds the sdist and wheel of the project in `dir` with the `build` frontend.
+pub(crate) fn build_project(
+    python: &str,
+    dir: &Path,
+    out_dir: &Path,
+) -> Result<(), Box<dyn Error>> {
+    println!("Building {} (Interpreter={})", dir.display(), python);
+
+    let status = Command::new(python)
+        .args(["-m", "build", "--outdir"])
+        .arg(out_dir)
+        .arg(dir)
+        .status()?;
+
+    if !status.success() {
+        return Result::Err(PipError::BuildFailed(status.to_string()).into());
+    }
 
     Result::Ok(())
 }
+
+/// Writes a stand-in for python that prints `lines` to stderr and exits with `code`.
+#[cfg(test)]
+pub(crate) fn stub_python(dir: &Path, lines: usize, code: i32) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = dir.join(format!("python-{}", code));
+    std::fs::write(
+        &path,
+        format!(
+            "#!/bin/sh\nfor i in $(seq 1 {}); do echo \"line $i\" >&2; done\nexit {}\n",
+            lines, code
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    path.to_str().unwrap().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_exit_status() {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let requirements = dir.path().join("requirements.txt");
+        std::fs::write(&requirements, "pkg==1.0.0\n").unwrap();
+
+        let python = stub_python(dir.path(), 0, 0);
+        assert!(install_requirements(&python, "", &requirements).is_ok());
+
+        let python = stub_python(dir.path(), STDERR_TAIL_LINES + 5, 1);
+        let error = install_requirements(&python, "--quiet", &requirements)
+            .unwrap_err()
+            .to_string();
+        let lines: Vec<&str> = error.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            format!(
+                "pip failed to install {}: exit status: 1",
+                requirements.display()
+            )
+        );
+        // Only the end of stderr is kept
+        assert_eq!(lines.len(), STDERR_TAIL_LINES + 1);
+        assert_eq!(lines[1], "  line 6");
+        assert_eq!(lines[STDERR_TAIL_LINES], "  line 25");
+    }
+}
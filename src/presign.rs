@@ -0,0 +1,134 @@
+use crate::duration::format_duration;
+use crate::index::Index;
+use crate::package::Package;
+use crate::{pip, yank, RuntimeConfig, StorageDriver};
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug)]
+enum PresignError {
+    NoBucketDefined,
+    NoPackagesGiven,
+    PackageNotFound(String),
+}
+
+impl std::fmt::Display for PresignError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoBucketDefined => write!(f, "no bucket was provided"),
+            Self::NoPackagesGiven => write!(f, "no packages were given"),
+            Self::PackageNotFound(pkg) => write!(f, "package not found: {}", pkg),
+        }
+    }
+}
+
+impl std::error::Error for PresignError {}
+
+pub(crate) struct UrlOptions {
+    pub(crate) expires: Duration,
+    /// Emit `name[extras] @ url` direct references that pip can install as a requirements file.
+    pub(crate) requirements: bool,
+    pub(crate) output: Option<PathBuf>,
+}
+
+/// Resolves packages and prints presigned download urls for them, so they can be installed
+/// without sling or cloud credentials.
+pub(crate) async fn urls(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    packages: Vec<Package>,
+    options: UrlOptions,
+) -> Result<(), Box<dyn Error>> {
+    let bucket = if config.bucket.is_none() {
+        return Result::Err(PresignError::NoBucketDefined.into());
+    } else {
+        config.bucket.clone().unwrap()
+    };
+
+    if packages.is_empty() {
+        return Result::Err(PresignError::NoPackagesGiven.into());
+    }
+
+    let index = Index::from_storage_bucket(driver, bucket.as_str()).await?;
+    let mut lines: Vec<String> = Vec::new();
+
+    for package in packages {
+        let entry = index
+            .resolve(&package)
+            .ok_or_else(|| PresignError::PackageNotFound(package.to_string()))?;
+
+        yank::warn_if_yanked(&index, &entry);
+
+        let url = driver
+            .presign_get(bucket.as_str(), entry.object.key.as_str(), options.expires)
+            .await?;
+
+        lines.push(if options.requirements {
+            format!(
+                "{} @ {}",
+                pip::with_extras(&entry.name, &package.extras),
+                url
+            )
+        } else {
+            url
+        });
+    }
+
+    match options.output {
+        Some(path) => {
+            std::fs::write(&path, format!("{}\n", lines.join("\n")))?;
+            println!(
+                "Wrote {} url(s) to {} (Expires={})",
+                lines.len(),
+                path.display(),
+                format_duration(options.expires)
+            );
+        }
+        None => {
+            for line in lines {
+                println!("{}", line);
+            }
+        }
+    }
+
+    Result::Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryStorageDriver;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_urls_requirements() {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let driver = MemoryStorageDriver::default();
+        let config = RuntimeConfig {
+            bucket: Some("bucket".to_string()),
+            ..RuntimeConfig::default()
+        };
+        let output = dir.path().join("requirements.txt");
+
+        driver.insert("bucket", "pkg/pkg-1.0.0.tar.gz", "content");
+        driver.insert("bucket", "other/other-2.0.0.tar.gz", "content");
+
+        let packages = vec![
+            Package::from_str("pkg[security, socks]@1.0.0").unwrap(),
+            Package::from_str("other").unwrap(),
+        ];
+        let options = UrlOptions {
+            expires: Duration::from_secs(60),
+            requirements: true,
+            output: Some(output.clone()),
+        };
+        urls(&config, &driver, packages, options).await.unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "pkg[security,socks] @ memory://bucket/pkg/pkg-1.0.0.tar.gz?expires=60\n\
+             other @ memory://bucket/other/other-2.0.0.tar.gz?expires=60\n"
+        );
+    }
+}
@@ -0,0 +1,816 @@
+use crate::requirements::RequirementsFile;
+use crate::simple::normalize_name;
+use crate::version_requirement::VersionRequirement;
+use regex::Regex;
+use serde_derive::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Files `read_dependencies` looks for in a project directory, in order of preference.
+const DEPENDENCY_FILES: [&str; 3] = ["poetry.lock", "Pipfile.lock", "pyproject.toml"];
+
+#[derive(Debug)]
+enum ProjectError {
+    NameNotFound(String),
+    NoDependencyFile(String),
+    UnsupportedConstraint(String, String),
+    GroupsNotFound(String),
+}
+
+impl std::fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NameNotFound(path) => write!(f, "no project name found in {}", path),
+            Self::NoDependencyFile(path) => {
+                write!(f, "no {} found in {}", DEPENDENCY_FILES.join(", "), path)
+            }
+            Self::UnsupportedConstraint(name, constraint) => {
+                write!(
+                    f,
+                    "unsupported version constraint for {}: {}",
+                    name, constraint
+                )
+            }
+            Self::GroupsNotFound(path) => write!(
+                f,
+                "{} does not record dependency groups and has no pyproject.toml next to it",
+                path
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProjectError {}
+
+#[derive(Debug, Default, Deserialize)]
+struct PyProjectFile {
+    project: Option<ProjectTable>,
+    tool: Option<ToolTable>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ProjectTable {
+    name: Option<String>,
+    version: Option<String>,
+    #[serde(default)]
+    dynamic: Vec<String>,
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ToolTable {
+    poetry: Option<PoetryTable>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PoetryTable {
+    name: Option<String>,
+    version: Option<String>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PipfileLock {
+    #[serde(rename = "_meta", default)]
+    meta: PipfileMeta,
+    #[serde(default)]
+    default: BTreeMap<String, PipfileLockEntry>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PipfileMeta {
+    #[serde(default)]
+    sources: Vec<PipfileSource>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PipfileSource {
+    url: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PipfileLockEntry {
+    version: Option<String>,
+    #[serde(default)]
+    hashes: Vec<String>,
+    markers: Option<String>,
+    #[serde(default)]
+    extras: Vec<String>,
+    #[serde(default)]
+    editable: bool,
+    path: Option<String>,
+    file: Option<String>,
+    git: Option<String>,
+    #[serde(rename = "ref")]
+    reference: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PoetryLock {
+    #[serde(default)]
+    package: Vec<PoetryLockPackage>,
+    #[serde(default)]
+    metadata: PoetryLockMetadata,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PoetryLockMetadata {
+    /// Where lockfiles written before Poetry 1.2 keep the file hashes.
+    #[serde(default)]
+    files: BTreeMap<String, Vec<PoetryLockFile>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PoetryLockPackage {
+    name: String,
+    version: String,
+    #[serde(default)]
+    optional: bool,
+    category: Option<String>,
+    groups: Option<Vec<String>>,
+    markers: Option<toml::Value>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, toml::Value>,
+    #[serde(default)]
+    extras: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    files: Vec<PoetryLockFile>,
+    source: Option<PoetryLockSource>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PoetryLockFile {
+    hash: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PoetryLockSource {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    resolved_reference: Option<String>,
+}
+
+/// The parts of a `pyproject.toml` sling needs, read from the PEP 621 `[project]` table or,
+/// failing that, from `[tool.poetry]`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PyProject {
+    pub(crate) name: String,
+    /// Unset when the version is computed by the build backend.
+    pub(crate) version: Option<String>,
+}
+
+impl PyProject {
+    pub(crate) fn load(dir: &Path) -> Result<PyProject, Box<dyn Error>> {
+        let path = dir.join("pyproject.toml");
+        let content = std::fs::read_to_string(&path)?;
+
+        PyProject::parse(&content)
+            .ok_or_else(|| ProjectError::NameNotFound(path.display().to_string()).into())
+    }
+
+    fn parse(content: &str) -> Option<PyProject> {
+        let file: PyProjectFile = toml::from_str(content).ok()?;
+        let poetry = file.tool.and_then(|x| x.poetry).unwrap_or_default();
+
+        match file.project {
+            Some(project) if project.name.is_some() => Some(PyProject {
+                name: project.name.unwrap(),
+                version: if project.dynamic.iter().any(|x| x == "version") {
+                    None
+                } else {
+                    project.version.or(poetry.version)
+                },
+            }),
+            _ => Some(PyProject {
+                name: poetry.name?,
+                version: poetry.version,
+            }),
+        }
+    }
+}
+
+/// Reads the runtime dependencies of a project as requirements, from `poetry.lock`,
+/// `Pipfile.lock` or the PEP 621 or Poetry tables of `pyproject.toml`. `path` is either the
+/// project directory, where the lockfiles are preferred, or one of those files.
+pub(crate) fn read_dependencies(path: &Path) -> Result<RequirementsFile, Box<dyn Error>> {
+    let file = if path.is_dir() {
+        DEPENDENCY_FILES
+            .iter()
+            .map(|x| path.join(x))
+            .find(|x| x.is_file())
+            .ok_or_else(|| ProjectError::NoDependencyFile(path.display().to_string()))?
+    } else {
+        path.to_path_buf()
+    };
+    let base = file
+        .canonicalize()?
+        .parent()
+        .map(|x| x.to_path_buf())
+        .unwrap_or_else(|| PathBuf::from("."));
+    let content = std::fs::read_to_string(&file)?;
+
+    println!("Reading dependencies from {}", file.display());
+
+    let lines = match file.file_name().and_then(|x| x.to_str()) {
+        Some("poetry.lock") => poetry_lock_requirements(&toml::from_str(&content)?, &base)?,
+        Some("Pipfile.lock") => pipfile_lock_requirements(&serde_json::from_str(&content)?, &base),
+        Some("pyproject.toml") => pyproject_requirements(&toml::from_str(&content)?, &base)?,
+        _ => return Result::Err(ProjectError::NoDependencyFile(file.display().to_string()).into()),
+    };
+
+    RequirementsFile::parse(&lines.join("\n"), &file.display().to_string(), &base)
+}
+
+fn pyproject_requirements(
+    file: &PyProjectFile,
+    base: &Path,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    if let Some(project) = file.project.as_ref().filter(|x| !x.dependencies.is_empty()) {
+        return Result::Ok(project.dependencies.clone());
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+
+    if let Some(poetry) = file.tool.as_ref().and_then(|x| x.poetry.as_ref()) {
+        for (name, value) in poetry.dependencies.iter().filter(|x| x.0 != "python") {
+            lines.append(&mut poetry_requirements(name, value, base)?);
+        }
+    }
+
+    Result::Ok(lines)
+}
+
+/// Converts a `[tool.poetry.dependencies]` entry, which is either a constraint, a table or a list
+/// of tables with markers. Optional dependencies are only installed with an extra.
+fn poetry_requirements(
+    name: &str,
+    value: &toml::Value,
+    base: &Path,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let table = match value {
+        toml::Value::String(constraint) => {
+            return Result::Ok(vec![format!(
+                "{}{}",
+                name,
+                pip_constraint(name, constraint)?
+            )])
+        }
+        toml::Value::Array(items) => {
+            let mut lines: Vec<String> = Vec::new();
+
+            for item in items {
+                lines.append(&mut poetry_requirements(name, item, base)?);
+            }
+
+            return Result::Ok(lines);
+        }
+        toml::Value::Table(table) => table,
+        _ => {
+            return Result::Err(
+                ProjectError::UnsupportedConstraint(name.to_string(), value.to_string()).into(),
+            )
+        }
+    };
+
+    let get = |key: &str| table.get(key).and_then(|x| x.as_str());
+    let flag = |key: &str| table.get(key).and_then(|x| x.as_bool()) == Some(true);
+
+    if flag("optional") {
+        return Result::Ok(Vec::new());
+    }
+
+    let extras = table
+        .get("extras")
+        .and_then(|x| x.as_array())
+        .map(|x| x.iter().filter_map(|x| x.as_str()).collect::<Vec<&str>>())
+        .filter(|x| !x.is_empty())
+        .map(|x| format!("[{}]", x.join(",")))
+        .unwrap_or_default();
+
+    let requirement = if let Some(path) = get("path") {
+        let path = format!("{}{}", base.join(path).display(), extras);
+
+        if flag("develop") {
+            return Result::Ok(vec![format!("-e {}", path)]);
+        }
+        path
+    } else if let Some(url) = get("git") {
+        let reference = get("rev")
+            .or_else(|| get("tag"))
+            .or_else(|| get("branch"))
+            .map(|x| format!("@{}", x))
+            .unwrap_or_default();
+        format!("{}{} @ git+{}{}", name, extras, url, reference)
+    } else if let Some(url) = get("url") {
+        format!("{}{} @ {}", name, extras, url)
+    } else {
+        let version = get("version").unwrap_or("*");
+        format!("{}{}{}", name, extras, pip_constraint(name, version)?)
+    };
+
+    Result::Ok(vec![match get("markers") {
+        Some(marker) => format!("{} ; {}", requirement, marker),
+        None => requirement,
+    }])
+}
+
+/// Fails on constraints `poetry_constraint` can't convert rather than dropping their bounds.
+fn pip_constraint(name: &str, constraint: &str) -> Result<String, ProjectError> {
+    poetry_constraint(constraint).ok_or_else(|| {
+        ProjectError::UnsupportedConstraint(name.to_string(), constraint.to_string())
+    })
+}
+
+/// Poetry's `^` and `~` constraints are turned into PEP 440 bounds for pip and bare versions into
+/// exact pins. Returns `None` for constraints pip can't express, such as `||` alternatives.
+fn poetry_constraint(constraint: &str) -> Option<String> {
+    let constraint = constraint.trim();
+
+    if constraint.is_empty() || constraint == "*" {
+        return Some(String::new());
+    }
+
+    if let Result::Ok(requirement) = VersionRequirement::from_str(constraint) {
+        return Some(requirement.to_pep440());
+    }
+
+    // Clauses are separated by commas or spaces, and a space may follow the operator
+    let constraint = Regex::new(r"(~=|===?|!=|<=|>=|<|>|\^|~)\s+")
+        .unwrap()
+        .replace_all(constraint, "$1");
+    let clause = Regex::new(r"^(~=|===?|!=|<=|>=|<|>)?\d[\w.*+!-]*$").unwrap();
+
+    constraint
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|x| !x.is_empty())
+        .map(|x| match VersionRequirement::from_str(x) {
+            Result::Ok(requirement) => Some(requirement.to_pep440()),
+            Result::Err(_) if x.starts_with(|c: char| c.is_ascii_digit()) && clause.is_match(x) => {
+                Some(format!("=={}", x))
+            }
+            Result::Err(_) if clause.is_match(x) => Some(x.to_string()),
+            Result::Err(_) => None,
+        })
+        .collect::<Option<Vec<String>>>()
+        .map(|x| x.join(","))
+}
+
+fn pipfile_lock_requirements(file: &PipfileLock, base: &Path) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+
+    for (position, source) in file.meta.sources.iter().enumerate() {
+        lines.push(match position {
+            0 => format!("--index-url {}", source.url),
+            _ => format!("--extra-index-url {}", source.url),
+        });
+    }
+
+    // pip only checks hashes when every requirement has them
+    let hashed = file.default.values().all(|x| !x.hashes.is_empty());
+
+    for (name, entry) in file.default.iter() {
+        let extras = if entry.extras.is_empty() {
+            String::new()
+        } else {
+            format!("[{}]", entry.extras.join(","))
+        };
+
+        let requirement = if let Some(path) = entry.path.as_ref() {
+            let path = format!("{}{}", base.join(path).display(), extras);
+
+            if entry.editable {
+                lines.push(format!("-e {}", path));
+                continue;
+            }
+            path
+        } else if let Some(url) = entry.git.as_ref() {
+            let reference = entry
+                .reference
+                .as_ref()
+                .map(|x| format!("@{}", x))
+                .unwrap_or_default();
+            format!("{}{} @ git+{}{}", name, extras, url, reference)
+        } else if let Some(url) = entry.file.as_ref() {
+            format!("{}{} @ {}", name, extras, url)
+        } else {
+            format!(
+                "{}{}{}",
+                name,
+                extras,
+                entry.version.as_deref().filter(|x| *x != "*").unwrap_or("")
+            )
+        };
+
+        lines.push(format_requirement(
+            requirement,
+            entry.markers.as_deref(),
+            if hashed { &entry.hashes } else { &[] },
+        ));
+    }
+
+    lines
+}
+
+/// Lockfiles of Poetry 1.5 to 1.8 (lock-version 2.0) record neither the category nor the groups
+/// of a package, so their main packages are the ones the `pyproject.toml` next to them pulls in.
+fn poetry_lock_requirements(file: &PoetryLock, base: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let main = if !file.package.is_empty()
+        && file
+            .package
+            .iter()
+            .all(|x| x.category.is_none() && x.groups.is_none())
+    {
+        let path = base.join("pyproject.toml");
+        let content = std::fs::read_to_string(&path).map_err(|_| {
+            ProjectError::GroupsNotFound(base.join("poetry.lock").display().to_string())
+        })?;
+
+        Some(poetry_lock_main(file, &toml::from_str(&content)?))
+    } else {
+        None
+    };
+    let packages: Vec<&PoetryLockPackage> = file
+        .package
+        .iter()
+        .filter(|x| match main.as_ref() {
+            Some(main) => main.contains(&normalize_name(&x.name)),
+            None => {
+                !x.optional
+                    && x.category.as_deref().unwrap_or("main") == "main"
+                    && x.groups
+                        .as_ref()
+                        .map(|x| x.iter().any(|x| x == "main"))
+                        .unwrap_or(true)
+            }
+        })
+        .collect();
+    let package_hashes = |package: &PoetryLockPackage| -> Vec<String> {
+        package
+            .files
+            .iter()
+            .chain(file.metadata.files.get(&package.name).into_iter().flatten())
+            .map(|x| x.hash.clone())
+            .collect()
+    };
+    let hashed = packages.iter().all(|x| {
+        x.source.as_ref().is_none_or(|x| x.kind == "legacy") && !package_hashes(x).is_empty()
+    });
+    let mut lines: Vec<String> = Vec::new();
+
+    for package in packages {
+        let marker = match package.markers.as_ref() {
+            Some(toml::Value::String(marker)) => Some(marker.as_str()),
+            Some(toml::Value::Table(markers)) => markers.get("main").and_then(|x| x.as_str()),
+            _ => None,
+        };
+
+        let requirement = match package.source.as_ref() {
+            Some(source) if source.kind == "legacy" => {
+                let option = format!("--extra-index-url {}", source.url);
+
+                if !lines.contains(&option) {
+                    lines.insert(0, option);
+                }
+                format!("{}=={}", package.name, package.version)
+            }
+            Some(source) if source.kind == "git" => format!(
+                "{} @ git+{}@{}",
+                package.name,
+                source.url,
+                source.resolved_reference.as_deref().unwrap_or("HEAD")
+            ),
+            Some(source) if source.kind == "url" => format!("{} @ {}", package.name, source.url),
+            Some(source) => base.join(&source.url).display().to_string(),
+            None => format!("{}=={}", package.name, package.version),
+        };
+
+        let hashes = if hashed {
+            package_hashes(package)
+        } else {
+            Vec::new()
+        };
+
+        lines.push(format_requirement(requirement, marker, &hashes));
+    }
+
+    Result::Ok(lines)
+}
+
+/// Follows the dependencies of the lockfile from the main dependencies of `pyproject`, returning
+/// the normalized names of every package reached. Optional dependencies are only followed for
+/// the extras that are asked for.
+fn poetry_lock_main(file: &PoetryLock, pyproject: &PyProjectFile) -> BTreeSet<String> {
+    let packages: BTreeMap<String, &PoetryLockPackage> = file
+        .package
+        .iter()
+        .map(|x| (normalize_name(&x.name), x))
+        .collect();
+    let mut pending: Vec<(String, Vec<String>)> = Vec::new();
+
+    if let Some(project) = pyproject.project.as_ref() {
+        pending.extend(
+            project
+                .dependencies
+                .iter()
+                .filter_map(|x| dependency_name(x)),
+        );
+    }
+
+    if let Some(poetry) = pyproject.tool.as_ref().and_then(|x| x.poetry.as_ref()) {
+        for (name, value) in poetry.dependencies.iter().filter(|x| x.0 != "python") {
+            if let Some(extras) = poetry_dependency_extras(value) {
+                pending.push((name.clone(), extras));
+            }
+        }
+    }
+
+    let mut main: BTreeSet<String> = BTreeSet::new();
+    let mut followed_extras: BTreeSet<(String, String)> = BTreeSet::new();
+
+    while let Some((name, extras)) = pending.pop() {
+        let name = normalize_name(&name);
+        let package = match packages.get(&name) {
+            Some(package) => package,
+            None => continue,
+        };
+
+        if main.insert(name.clone()) {
+            for (dependency, value) in package.dependencies.iter() {
+                if let Some(extras) = poetry_dependency_extras(value) {
+                    pending.push((dependency.clone(), extras));
+                }
+            }
+        }
+
+        for extra in extras {
+            if followed_extras.insert((name.clone(), extra.clone())) {
+                pending.extend(
+                    package
+                        .extras
+                        .get(&extra)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|x| dependency_name(x)),
+                );
+            }
+        }
+    }
+
+    main
+}
+
+/// The extras a Poetry dependency entry asks for, or `None` when it is optional.
+fn poetry_dependency_extras(value: &toml::Value) -> Option<Vec<String>> {
+    let tables: Vec<&toml::value::Table> = match value {
+        toml::Value::Table(table) => vec![table],
+        toml::Value::Array(items) => items.iter().filter_map(|x| x.as_table()).collect(),
+        _ => return Some(Vec::new()),
+    };
+
+    if !tables.is_empty()
+        && tables
+            .iter()
+            .all(|x| x.get("optional").and_then(|x| x.as_bool()) == Some(true))
+    {
+        return None;
+    }
+
+    Some(
+        tables
+            .iter()
+            .filter_map(|x| x.get("extras").and_then(|x| x.as_array()))
+            .flatten()
+            .filter_map(|x| x.as_str())
+            .map(|x| x.to_string())
+            .collect(),
+    )
+}
+
+/// The name and extras of a PEP 508 requirement such as `name[extra] (>=1.0)`.
+fn dependency_name(requirement: &str) -> Option<(String, Vec<String>)> {
+    let captures = Regex::new(r"^\s*([A-Za-z0-9][A-Za-z0-9._-]*)\s*(?:\[([^\]]*)\])?")
+        .unwrap()
+        .captures(requirement)?;
+    let extras = captures
+        .get(2)
+        .map(|x| {
+            x.as_str()
+                .split(',')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Some((captures[1].to_string(), extras))
+}
+
+fn format_requirement(requirement: String, marker: Option<&str>, hashes: &[String]) -> String {
+    let mut line = requirement;
+
+    if let Some(marker) = marker {
+        line.push_str(&format!(" ; {}", marker));
+    }
+
+    for hash in hashes {
+        line.push_str(&format!(" --hash={}", hash));
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requirements::Line;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            PyProject::parse("[project]\nname = \"my-pkg\"\nversion = \"1.2.0\"\n"),
+            Some(PyProject {
+                name: "my-pkg".to_string(),
+                version: Some("1.2.0".to_string()),
+            })
+        );
+        assert_eq!(
+            PyProject::parse("[project]\nname = \"my-pkg\"\ndynamic = [\"version\"]\n"),
+            Some(PyProject {
+                name: "my-pkg".to_string(),
+                version: None,
+            })
+        );
+        assert_eq!(
+            PyProject::parse("[tool.poetry]\nname = \"my-pkg\"\nversion = \"0.3.1\"\n"),
+            Some(PyProject {
+                name: "my-pkg".to_string(),
+                version: Some("0.3.1".to_string()),
+            })
+        );
+        assert_eq!(PyProject::parse("[build-system]\nrequires = []\n"), None);
+    }
+
+    #[test]
+    fn test_read_dependencies() {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let base = dir.path().canonicalize().unwrap();
+        let read = |name: &str, content: &str| -> (Vec<String>, Vec<String>) {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            let file = read_dependencies(&path).unwrap();
+            let lines = file
+                .lines
+                .into_iter()
+                .map(|x| match x {
+                    Line::Requirement(requirement) => requirement.pip_line.unwrap(),
+                    Line::Pip(line) => line,
+                })
+                .collect();
+            (file.options, lines)
+        };
+
+        let (options, lines) = read(
+            "pyproject.toml",
+            "[tool.poetry]\n\
+             name = \"app\"\n\
+             [tool.poetry.dependencies]\n\
+             python = \"^3.8\"\n\
+             my-lib = { version = \"^1.2\", extras = [\"gpu\"], markers = \"sys_platform == 'linux'\" }\n\
+             plugin = { version = \"*\", optional = true }\n\
+             local = { path = \"../local\", develop = true }\n\
+             wild = \"1.2.*\"\n\
+             post = { version = \"1.2.3.post1\" }\n\
+             spaced = \">= 1.0 <2.0\"\n",
+        );
+        assert!(options.is_empty());
+        assert_eq!(
+            lines,
+            vec![
+                format!("-e {}", base.join("../local").display()),
+                "my-lib[gpu]>=1.2.0,<2.0.0 ; sys_platform == 'linux'".to_string(),
+                "post==1.2.3.post1".to_string(),
+                "spaced>=1.0.0,<2.0.0".to_string(),
+                "wild==1.2.*".to_string(),
+            ]
+        );
+
+        // pip has no way to express `||`, which must not silently widen to any version
+        for dependency in [
+            "either = \">=1.0 || ^2.0\"",
+            "alt = { version = \"^1.0 || ^2.0\", extras = [\"x\"] }",
+        ] {
+            let path = dir.path().join("pyproject.toml");
+            std::fs::write(
+                &path,
+                format!(
+                    "[tool.poetry]\nname = \"app\"\n[tool.poetry.dependencies]\n{}\n",
+                    dependency
+                ),
+            )
+            .unwrap();
+
+            assert!(read_dependencies(&path)
+                .unwrap_err()
+                .to_string()
+                .starts_with("unsupported version constraint for "));
+        }
+
+        let (options, lines) = read(
+            "Pipfile.lock",
+            r#"{"_meta": {"sources": [{"url": "https://pypi.org/simple"}]},
+                "default": {"my-lib": {"version": "==1.2.0", "hashes": ["sha256:abc"]}},
+                "develop": {"pytest": {"version": "==7.0.0"}}}"#,
+        );
+        assert_eq!(options, vec!["--index-url https://pypi.org/simple"]);
+        assert_eq!(lines, vec!["my-lib==1.2.0 --hash=sha256:abc"]);
+
+        let (options, lines) = read(
+            "poetry.lock",
+            "[[package]]\n\
+             name = \"my-lib\"\n\
+             version = \"1.2.0\"\n\
+             category = \"main\"\n\
+             [package.source]\n\
+             type = \"legacy\"\n\
+             url = \"http://127.0.0.1:8080/simple\"\n\
+             [[package]]\n\
+             name = \"pytest\"\n\
+             version = \"7.0.0\"\n\
+             category = \"dev\"\n\
+             [metadata.files]\n\
+             my-lib = [{file = \"my_lib-1.2.0.tar.gz\", hash = \"sha256:abc\"}]\n",
+        );
+        assert_eq!(
+            options,
+            vec!["--extra-index-url http://127.0.0.1:8080/simple"]
+        );
+        assert_eq!(lines, vec!["my-lib==1.2.0 --hash=sha256:abc"]);
+
+        // Poetry 1.5 to 1.8 leave the groups out, they are told apart by pyproject.toml
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            "[tool.poetry]\n\
+             name = \"app\"\n\
+             [tool.poetry.dependencies]\n\
+             python = \"^3.8\"\n\
+             My_Lib = { version = \"^1.2\", extras = [\"gpu\"] }\n\
+             [tool.poetry.group.dev.dependencies]\n\
+             pytest = \"^7.0\"\n",
+        )
+        .unwrap();
+
+        let (options, lines) = read(
+            "poetry.lock",
+            "[[package]]\n\
+             name = \"cupy\"\n\
+             version = \"12.0.0\"\n\
+             optional = true\n\
+             files = [{file = \"cupy-12.0.0.tar.gz\", hash = \"sha256:c\"}]\n\
+             [[package]]\n\
+             name = \"my-lib\"\n\
+             version = \"1.2.0\"\n\
+             optional = false\n\
+             files = [{file = \"my_lib-1.2.0.tar.gz\", hash = \"sha256:abc\"}]\n\
+             [package.dependencies]\n\
+             cupy = {version = \">=12.0\", optional = true}\n\
+             six = \">=1.0\"\n\
+             [package.extras]\n\
+             gpu = [\"cupy (>=12.0)\"]\n\
+             [[package]]\n\
+             name = \"pluggy\"\n\
+             version = \"1.0.0\"\n\
+             optional = false\n\
+             files = [{file = \"pluggy-1.0.0.tar.gz\", hash = \"sha256:p\"}]\n\
+             [[package]]\n\
+             name = \"pytest\"\n\
+             version = \"7.0.0\"\n\
+             optional = false\n\
+             files = [{file = \"pytest-7.0.0.tar.gz\", hash = \"sha256:t\"}]\n\
+             [package.dependencies]\n\
+             pluggy = \">=1.0\"\n\
+             [[package]]\n\
+             name = \"six\"\n\
+             version = \"1.16.0\"\n\
+             optional = false\n\
+             files = [{file = \"six-1.16.0.tar.gz\", hash = \"sha256:s\"}]\n\
+             [metadata]\n\
+             lock-version = \"2.0\"\n\
+             python-versions = \"^3.8\"\n\
+             content-hash = \"0123\"\n",
+        );
+        assert!(options.is_empty());
+        assert_eq!(
+            lines,
+            vec![
+                "cupy==12.0.0 --hash=sha256:c",
+                "my-lib==1.2.0 --hash=sha256:abc",
+                "six==1.16.0 --hash=sha256:s",
+            ]
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use crate::bucket_metadata::{BucketMetadata, LATEST_TAG};
+use crate::digest;
+use crate::index::{Entry, Index};
+use crate::package::Package;
+use crate::storage::location::StorageLocation;
+use crate::storage::object_ref::ObjectRef;
+use crate::{publish, yank, StorageDriver};
+use std::error::Error;
+
+#[derive(Debug)]
+enum PromoteError {
+    PackageNotFound(String),
+    OverwriteDisallowedError(String),
+    HashMismatch(String),
+}
+
+impl std::fmt::Display for PromoteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::PackageNotFound(pkg) => write!(f, "package not found: {}", pkg),
+            Self::OverwriteDisallowedError(pkg) => {
+                write!(f, "refusing to overwrite published package: {}", pkg)
+            }
+            Self::HashMismatch(url) => write!(f, "copied package does not match source: {}", url),
+        }
+    }
+}
+
+impl std::error::Error for PromoteError {}
+
+pub(crate) struct PromoteOptions {
+    pub(crate) overwrite: bool,
+    pub(crate) update_latest: bool,
+}
+
+pub(crate) async fn promote(
+    source: &StorageLocation,
+    source_driver: &'_ dyn StorageDriver,
+    target: &StorageLocation,
+    target_driver: &'_ dyn StorageDriver,
+    package: &Package,
+    options: PromoteOptions,
+) -> Result<(), Box<dyn Error>> {
+    let source_index = Index::from_storage_bucket(source_driver, source.bucket.as_str()).await?;
+    let entry = source_index
+        .resolve(package)
+        .ok_or_else(|| PromoteError::PackageNotFound(format!("{} in {}", package, source)))?;
+
+    yank::warn_if_yanked(&source_index, &entry);
+
+    let target_index = Index::from_storage_bucket(target_driver, target.bucket.as_str()).await?;
+
+    if !options.overwrite && target_index.contains(&entry.as_package()) {
+        return Result::Err(
+            PromoteError::OverwriteDisallowedError(format!(
+                "{}@{} in {}",
+                entry.name, entry.version, target
+            ))
+            .into(),
+        );
+    }
+
+    println!(
+        "Promoting {}@{}: {} -> {}",
+        entry.name, entry.version, source, target
+    );
+
+    copy_entry(source, source_driver, target, target_driver, &entry).await?;
+
+    let mut tagged: Vec<String> = Vec::new();
+
+    BucketMetadata::update(target_driver, target.bucket.as_str(), |metadata| {
+        tagged = copy_metadata(source_index.metadata(), metadata, &entry);
+    })
+    .await?;
+
+    for tag in tagged {
+        println!("Tagged {}@{} as {}", entry.name, entry.version, tag);
+    }
+
+    if options.update_latest {
+        publish::update_latest_tags(
+            target_driver,
+            target.bucket.as_str(),
+            &target_index,
+            &[entry.as_package()],
+        )
+        .await?;
+    }
+
+    Result::Ok(())
+}
+
+/// Carries the yank status and tags of a release over to the target bucket. `latest` is left to
+/// `PromoteOptions::update_latest`. Returns the tags that were moved onto the release.
+pub(crate) fn copy_metadata(
+    source: &BucketMetadata,
+    target: &mut BucketMetadata,
+    entry: &Entry,
+) -> Vec<String> {
+    let mut tagged: Vec<String> = Vec::new();
+
+    match source.get_yank(&entry.name, &entry.version) {
+        Some(yank) => target.yank(&entry.name, &entry.version, yank.reason.clone()),
+        None => {
+            target.unyank(&entry.name, &entry.version);
+        }
+    }
+
+    for tag in source.get_tags(&entry.name, &entry.version) {
+        if tag != LATEST_TAG && target.get_tag(&entry.name, &tag) != Some(&entry.version) {
+            target.tag(&entry.name, &entry.version, &tag);
+            tagged.push(tag);
+        }
+    }
+
+    tagged
+}
+
+/// Copies every file of a release between buckets.
+pub(crate) async fn copy_entry(
+    source: &StorageLocation,
+    source_driver: &'_ dyn StorageDriver,
+    target: &StorageLocation,
+    target_driver: &'_ dyn StorageDriver,
+    entry: &Entry,
+) -> Result<(), Box<dyn Error>> {
+    for file in entry.files.iter() {
+        copy_file(
+            source,
+            source_driver,
+            target,
+            target_driver,
+            file.key.as_str(),
+        )
+        .await?;
+    }
+
+    Result::Ok(())
+}
+
+/// Copies a package file between buckets, using a server-side copy when both buckets are on
+/// the same provider and streaming the file through sling otherwise. The copy is checked against
+/// the size and md5 digest the providers report rather than downloaded again.
+async fn copy_file(
+    source: &StorageLocation,
+    source_driver: &'_ dyn StorageDriver,
+    target: &StorageLocation,
+    target_driver: &'_ dyn StorageDriver,
+    key: &str,
+) -> Result<(), Box<dyn Error>> {
+    let original = source_driver.stat(source.bucket.as_str(), key).await?;
+
+    let copied_server_side = source.driver == target.driver
+        && match target_driver
+            .copy(source.bucket.as_str(), key, target.bucket.as_str(), key)
+            .await
+        {
+            Result::Ok(_) => true,
+            Result::Err(e) => {
+                println!("Server-side copy failed, streaming instead (Error={})", e);
+                false
+            }
+        };
+
+    let expected = if copied_server_side {
+        original
+    } else {
+        let content = source_driver.get(source.bucket.as_str(), key).await?;
+        let expected = ObjectRef {
+            size: Some(content.len() as u64),
+            md5: Some(digest::md5(&content)),
+            ..original
+        };
+
+        target_driver
+            .put(target.bucket.as_str(), key, content)
+            .await?;
+        expected
+    };
+
+    let copied = target_driver.stat(target.bucket.as_str(), key).await?;
+
+    if !same_content(&expected, &copied) {
+        return Result::Err(PromoteError::HashMismatch(copied.get_object_url()).into());
+    }
+
+    match copied.md5 {
+        Some(md5) => println!("Verified {} (md5={})", key, md5),
+        None => println!(
+            "Verified {} (size={})",
+            key,
+            copied.size.unwrap_or_default()
+        ),
+    }
+
+    Result::Ok(())
+}
+
+/// Compares the md5 digests of two objects where both are known, and their sizes otherwise.
+fn same_content(a: &ObjectRef, b: &ObjectRef) -> bool {
+    let same_size = match (a.size, b.size) {
+        (Some(x), Some(y)) => x == y,
+        _ => true,
+    };
+    let same_md5 = match (&a.md5, &b.md5) {
+        (Some(x), Some(y)) => x == y,
+        _ => true,
+    };
+
+    same_size && same_md5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryStorageDriver;
+    use crate::storage::AvailableStorageDrivers;
+
+    #[test]
+    fn test_copy_metadata() {
+        let entry = Entry::new(
+            "pkg",
+            "1.2.0",
+            &ObjectRef {
+                bucket: "staging".to_string(),
+                key: "pkg/pkg-1.2.0.tar.gz".to_string(),
+                protocol: "s3".to_string(),
+                size: None,
+                last_modified: None,
+                md5: None,
+            },
+        );
+        let mut source = BucketMetadata::default();
+        source.yank("pkg", "1.2.0", Some("broken build".to_string()));
+        source.tag("pkg", "1.2.0", "stable");
+        source.tag("pkg", "1.2.0", LATEST_TAG);
+
+        let mut target = BucketMetadata::default();
+        target.tag("pkg", "1.1.0", "stable");
+        target.tag("pkg", "1.1.0", LATEST_TAG);
+
+        assert_eq!(copy_metadata(&source, &mut target, &entry), vec!["stable"]);
+        assert_eq!(
+            target.get_yank("pkg", "1.2.0").unwrap().reason.as_deref(),
+            Some("broken build")
+        );
+        assert_eq!(target.get_tag("pkg", "stable").unwrap(), "1.2.0");
+        assert_eq!(target.get_tag("pkg", LATEST_TAG).unwrap(), "1.1.0");
+
+        // Tags the release already carries are not reported again
+        source.unyank("pkg", "1.2.0");
+        assert!(copy_metadata(&source, &mut target, &entry).is_empty());
+        assert!(target.get_yank("pkg", "1.2.0").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_copy_entry() {
+        let driver = MemoryStorageDriver::default();
+        let location = |bucket: &str| StorageLocation {
+            driver: AvailableStorageDrivers::S3,
+            bucket: bucket.to_string(),
+            profile: None,
+        };
+
+        driver.insert("staging", "pkg/pkg-1.2.0.tar.gz", "sdist");
+        driver.insert("staging", "pkg/pkg-1.2.0-py3-none-any.whl", "wheel");
+
+        let index = Index::from_storage_bucket(&driver, "staging")
+            .await
+            .unwrap();
+        let entry = index.find("pkg", "1.2.0").unwrap();
+
+        copy_entry(
+            &location("staging"),
+            &driver,
+            &location("prod"),
+            &driver,
+            &entry,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(driver.keys("prod"), driver.keys("staging"));
+        assert_eq!(
+            driver.get("prod", "pkg/pkg-1.2.0.tar.gz").await.unwrap(),
+            "sdist"
+        );
+    }
+
+    #[test]
+    fn test_same_content() {
+        let object = |size: Option<u64>, md5: Option<&str>| ObjectRef {
+            bucket: "bucket".to_string(),
+            key: "pkg/pkg-1.2.0.tar.gz".to_string(),
+            protocol: "s3".to_string(),
+            size,
+            last_modified: None,
+            md5: md5.map(|x| x.to_string()),
+        };
+
+        assert!(same_content(
+            &object(Some(5), Some("aa")),
+            &object(Some(5), Some("aa"))
+        ));
+        assert!(same_content(
+            &object(Some(5), Some("aa")),
+            &object(Some(5), None)
+        ));
+        assert!(!same_content(
+            &object(Some(5), Some("aa")),
+            &object(Some(5), Some("bb"))
+        ));
+        assert!(!same_content(
+            &object(Some(5), None),
+            &object(Some(6), None)
+        ));
+    }
+}
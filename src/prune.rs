@@ -0,0 +1,324 @@
+use crate::delete::{confirm, delete_entries};
+use crate::duration::format_duration;
+use crate::index::{Entry, Index};
+use crate::list::{format_size, print_table};
+use crate::lock::read_locked_packages;
+use crate::package::{read_packages_from_file, Package};
+use crate::package_version::PackageVersion;
+use crate::simple::normalize_name;
+use crate::{RuntimeConfig, StorageDriver};
+use chrono::{DateTime, Utc};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug)]
+enum PruneError {
+    NoBucketDefined,
+    NoPolicy,
+    PinnedFileError(String),
+}
+
+impl std::fmt::Display for PruneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoBucketDefined => write!(f, "no bucket was provided"),
+            Self::NoPolicy => write!(
+                f,
+                "no pruning policy was provided (use --keep-last or --prerelease-max-age)"
+            ),
+            Self::PinnedFileError(msg) => write!(f, "failed to read pinned packages: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PruneError {}
+
+#[derive(Clone, Debug)]
+pub(crate) struct PrunePolicy {
+    pub(crate) keep_last: Option<usize>,
+    pub(crate) prerelease_max_age: Option<Duration>,
+    pub(crate) keep_pinned: Vec<PathBuf>,
+}
+
+pub(crate) struct PruneOptions {
+    pub(crate) packages: Vec<String>,
+    pub(crate) dry_run: bool,
+    pub(crate) yes: bool,
+}
+
+pub(crate) async fn prune(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    policy: PrunePolicy,
+    options: PruneOptions,
+) -> Result<(), Box<dyn Error>> {
+    let bucket = if config.bucket.is_none() {
+        return Result::Err(PruneError::NoBucketDefined.into());
+    } else {
+        config.bucket.clone().unwrap()
+    };
+
+    if policy.keep_last.is_none() && policy.prerelease_max_age.is_none() {
+        return Result::Err(PruneError::NoPolicy.into());
+    }
+
+    let mut pinned: Vec<Package> = Vec::new();
+
+    for path in policy.keep_pinned.iter() {
+        let mut packages = read_pinned_packages(path)
+            .map_err(|e| PruneError::PinnedFileError(format!("{:?}: {}", path, e)))?;
+        pinned.append(&mut packages);
+    }
+
+    let index = Index::from_storage_bucket(driver, bucket.as_str()).await?;
+    let names = if options.packages.is_empty() {
+        index.names()
+    } else {
+        options
+            .packages
+            .iter()
+            .map(|x| index.find_name(x).unwrap_or_else(|| x.clone()))
+            .collect()
+    };
+    let candidates = evaluate(&index, &names, &policy, &pinned, Utc::now());
+
+    if candidates.is_empty() {
+        println!("Nothing to prune");
+        return Result::Ok(());
+    }
+
+    let reclaimed: u64 = candidates.iter().flat_map(|x| x.0.size()).sum();
+    let files: usize = candidates.iter().map(|x| x.0.files.len()).sum();
+
+    print_table(
+        &["NAME", "VERSION", "SIZE", "UPLOADED", "REASON"],
+        candidates
+            .iter()
+            .map(|(entry, reason)| {
+                vec![
+                    entry.name.clone(),
+                    entry.version.clone(),
+                    entry
+                        .size()
+                        .map(format_size)
+                        .unwrap_or_else(|| "-".to_string()),
+                    entry
+                        .object
+                        .last_modified
+                        .map(|x| x.format("%Y-%m-%d %H:%M:%S").to_string())
+                        .unwrap_or_else(|| "-".to_string()),
+                    reason.clone(),
+                ]
+            })
+            .collect(),
+    );
+    println!(
+        "{}{} version(s) in {} file(s), {} reclaimed",
+        if options.dry_run { "[dry-run] " } else { "" },
+        candidates.len(),
+        files,
+        format_size(reclaimed)
+    );
+
+    if options.dry_run {
+        return Result::Ok(());
+    }
+
+    if !options.yes && !confirm(&format!("Delete {} version(s)?", candidates.len()))? {
+        println!("Aborted");
+        return Result::Ok(());
+    }
+
+    let entries: Vec<Entry> = candidates.into_iter().map(|x| x.0).collect();
+
+    delete_entries(driver, bucket.as_str(), &entries).await
+}
+
+/// Reads the versions pinned by a lockfile written by `lock`, or by a requirements file.
+fn read_pinned_packages(path: &Path) -> Result<Vec<Package>, Box<dyn Error>> {
+    match read_locked_packages(path)? {
+        Some(packages) => Result::Ok(packages),
+        None => read_packages_from_file(path),
+    }
+}
+
+/// Returns every entry selected by the policy along with the reason it was selected. The
+/// latest version of a package, tagged versions and versions pinned by the provided files are
+/// never selected.
+fn evaluate(
+    index: &Index,
+    names: &[String],
+    policy: &PrunePolicy,
+    pinned: &[Package],
+    now: DateTime<Utc>,
+) -> Vec<(Entry, String)> {
+    let mut result: Vec<(Entry, String)> = Vec::new();
+
+    for name in names {
+        let versions = index.list_versions(name);
+        let latest = index.find_latest(name).map(|x| x.version);
+
+        for (position, entry) in versions.iter().rev().enumerate() {
+            let is_pinned = pinned.iter().any(|x| {
+                normalize_name(&x.name) == normalize_name(&entry.name)
+                    && x.version == PackageVersion::Literal(entry.version.clone())
+            });
+
+            let is_tagged = !index
+                .metadata()
+                .get_tags(&entry.name, &entry.version)
+                .is_empty();
+
+            if is_pinned || is_tagged || latest.as_ref() == Some(&entry.version) {
+                continue;
+            }
+
+            let mut reasons: Vec<String> = Vec::new();
+
+            if let Some(keep_last) = policy.keep_last {
+                if position >= keep_last {
+                    reasons.push(format!("not among the last {} versions", keep_last));
+                }
+            }
+
+            if let (Some(max_age), Some(uploaded)) =
+                (policy.prerelease_max_age, entry.object.last_modified)
+            {
+                let is_prerelease = entry.semantic_version().is_some_and(|x| x.is_prerelease());
+                let expired = chrono::Duration::from_std(max_age)
+                    .map(|x| uploaded < now - x)
+                    .unwrap_or(false);
+
+                if is_prerelease && expired {
+                    reasons.push(format!(
+                        "pre-release older than {}",
+                        format_duration(max_age)
+                    ));
+                }
+            }
+
+            if !reasons.is_empty() {
+                result.push((entry.clone(), reasons.join(", ")));
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::object_ref::ObjectRef;
+    use std::str::FromStr;
+
+    fn entry(version: &str, age_days: i64, now: DateTime<Utc>) -> Entry {
+        let key = format!("pkg/pkg-{}.tar.gz", version);
+        Entry::new(
+            "pkg",
+            version,
+            &ObjectRef {
+                bucket: "bucket".to_string(),
+                key,
+                protocol: "s3".to_string(),
+                size: Some(10),
+                last_modified: Some(now - chrono::Duration::days(age_days)),
+                md5: None,
+            },
+        )
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let now = Utc::now();
+        let mut index = Index::new();
+
+        for (version, age) in [
+            ("1.0.0", 90),
+            ("1.1.0.dev1", 60),
+            ("1.1.0.dev2", 5),
+            ("1.1.0", 4),
+            ("1.2.0.dev1", 40),
+        ] {
+            index.add(entry(version, age, now));
+        }
+
+        let policy = PrunePolicy {
+            keep_last: Some(3),
+            prerelease_max_age: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+            keep_pinned: Vec::new(),
+        };
+        let pinned = vec![Package::from_str("pkg@1.0.0").unwrap()];
+        let names = vec!["pkg".to_string()];
+        let selected: Vec<String> = evaluate(&index, &names, &policy, &pinned, now)
+            .into_iter()
+            .map(|x| x.0.version)
+            .collect();
+
+        assert_eq!(selected, vec!["1.2.0.dev1", "1.1.0.dev1"]);
+    }
+
+    #[test]
+    fn test_evaluate_pinned_name() {
+        let now = Utc::now();
+        let mut index = Index::new();
+
+        for version in ["1.0.0", "1.1.0", "1.2.0"] {
+            index.add(Entry {
+                name: "my_pkg".to_string(),
+                ..entry(version, 10, now)
+            });
+        }
+
+        let policy = PrunePolicy {
+            keep_last: Some(1),
+            prerelease_max_age: None,
+            keep_pinned: Vec::new(),
+        };
+        let pinned = vec![Package::from_str("My-Pkg@1.0.0").unwrap()];
+        let names = vec!["my_pkg".to_string()];
+        let selected: Vec<String> = evaluate(&index, &names, &policy, &pinned, now)
+            .into_iter()
+            .map(|x| x.0.version)
+            .collect();
+
+        assert_eq!(selected, vec!["1.1.0"]);
+    }
+
+    #[test]
+    fn test_read_pinned_packages() {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let lockfile = dir.path().join("sling.lock");
+        let requirements = dir.path().join("requirements.txt");
+
+        std::fs::write(
+            &lockfile,
+            serde_json::json!({
+                "format_version": 2,
+                "created": "2022-03-01T12:00:00Z",
+                "source": "s3://bucket",
+                "requested": [{"name": "pkg"}],
+                "packages": [{
+                    "name": "pkg",
+                    "version": "1.0.0",
+                    "url": "s3://bucket/pkg/pkg-1.0.0.tar.gz",
+                    "sha256": "0",
+                    "size": 10,
+                }],
+            })
+            .to_string(),
+        )
+        .unwrap();
+        std::fs::write(&requirements, "other==2.0.0\n").unwrap();
+
+        assert_eq!(
+            read_pinned_packages(&lockfile).unwrap(),
+            vec![Package::from_str("pkg@1.0.0").unwrap()]
+        );
+        assert_eq!(
+            read_pinned_packages(&requirements).unwrap(),
+            vec![Package::from_str("other@2.0.0").unwrap()]
+        );
+    }
+}
@@ -1,28 +1,48 @@
+use crate::archive::Archive;
+use crate::bucket_metadata::{BucketMetadata, LATEST_TAG};
+use crate::check;
+use crate::distribution::Distribution;
 use crate::index::Index;
 use crate::package::Package;
-use crate::{RuntimeConfig, StorageDriver};
+use crate::project::PyProject;
+use crate::semantic_version::SemanticVersion;
+use crate::simple::normalize_name;
+use crate::{pip, RuntimeConfig, StorageDriver};
 use bytes::Bytes;
+use futures::stream::{self, StreamExt};
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 #[derive(Debug)]
 enum PublishError {
     NoBucketDefined,
-    InvalidPackage(String),
-    OverwriteDisallowedError,
-    UploadError(String),
+    NoPackagesFound,
+    VersionExists(String),
+    InvalidPackages(Vec<String>),
+    OverwriteDisallowedError(Vec<String>),
+    UploadError(Vec<String>),
 }
 
 impl std::fmt::Display for PublishError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Self::NoBucketDefined => write!(f, "no bucket was provided"),
-            Self::InvalidPackage(path) => write!(f, "invalid package file: {}", path),
-            Self::UploadError(msg) => write!(f, "package upload failed: {}", msg),
-            Self::OverwriteDisallowedError => {
-                write!(f, "{:?}: refusing to overwrite published package", self)
+            Self::NoPackagesFound => write!(f, "no package files found"),
+            Self::VersionExists(package) => {
+                write!(f, "refusing to overwrite published package: {}", package)
+            }
+            Self::InvalidPackages(errors) => {
+                write!(f, "invalid package file(s): {}", errors.join("; "))
+            }
+            Self::OverwriteDisallowedError(keys) => write!(
+                f,
+                "refusing to overwrite published package file(s): {}",
+                keys.join(", ")
+            ),
+            Self::UploadError(errors) => {
+                write!(f, "package upload failed: {}", errors.join("; "))
             }
         }
     }
@@ -30,11 +50,42 @@ impl std::fmt::Display for PublishError {
 
 impl std::error::Error for PublishError {}
 
+/// Whether a publish failed because a file or version already exists in the bucket.
+pub(crate) fn is_conflict(e: &(dyn Error + 'static)) -> bool {
+    matches!(
+        e.downcast_ref::<PublishError>(),
+        Some(PublishError::OverwriteDisallowedError(_)) | Some(PublishError::VersionExists(_))
+    )
+}
+
+/// Whether a publish failed because the files themselves were rejected.
+pub(crate) fn is_invalid(e: &(dyn Error + 'static)) -> bool {
+    matches!(
+        e.downcast_ref::<PublishError>(),
+        Some(PublishError::InvalidPackages(_)) | Some(PublishError::NoPackagesFound)
+    )
+}
+
+pub(crate) struct PublishOptions {
+    pub(crate) overwrite: bool,
+    pub(crate) update_latest: bool,
+    pub(crate) jobs: usize,
+}
+
+struct Upload {
+    path: PathBuf,
+    distribution: Distribution,
+    key: String,
+    data: Bytes,
+}
+
+/// Publishes a set of distribution files as one unit. Every file is validated and checked for
+/// conflicts before anything is uploaded, and files uploaded before a failure are removed again.
 pub(crate) async fn publish(
     config: &RuntimeConfig,
     driver: &'_ dyn StorageDriver,
-    path: &Path,
-    overwrite: bool,
+    paths: &[PathBuf],
+    options: PublishOptions,
 ) -> Result<(), Box<dyn Error>> {
     let bucket = if config.bucket.is_none() {
         return Result::Err(PublishError::NoBucketDefined.into());
@@ -42,46 +93,423 @@ pub(crate) async fn publish(
         config.bucket.clone().unwrap()
     };
 
+    if paths.is_empty() {
+        return Result::Err(PublishError::NoPackagesFound.into());
+    }
+
+    let mut uploads: Vec<Upload> = Vec::new();
+    let mut errors: Vec<String> = Vec::new();
+
+    for path in paths {
+        match validate(path) {
+            Result::Ok(upload) => uploads.push(upload),
+            Result::Err(e) => errors.push(format!("{} ({})", path.display(), e)),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Result::Err(PublishError::InvalidPackages(errors).into());
+    }
+
     let index = Index::from_storage_bucket(driver, bucket.as_str()).await?;
-    let package = match Package::from_file(path) {
-        Some(x) => x,
-        None => {
-            return Result::Err(
-                PublishError::InvalidPackage(path.to_str().unwrap().to_string()).into(),
-            );
+
+    // Files of one project share a directory even when their filenames spell the name differently
+    uploads.sort_by_key(|x| x.distribution.install_rank());
+    let mut names: Vec<String> = index.names();
+
+    for upload in uploads.iter_mut() {
+        let name = match names
+            .iter()
+            .find(|x| normalize_name(x) == normalize_name(&upload.distribution.name))
+        {
+            Some(name) => name.clone(),
+            None => {
+                names.push(upload.distribution.name.clone());
+                upload.distribution.name.clone()
+            }
+        };
+
+        upload.distribution.name = name;
+        upload.key = upload.distribution.object_key();
+    }
+
+    for (i, upload) in uploads.iter().enumerate() {
+        if uploads[..i].iter().any(|x| x.key == upload.key) {
+            errors.push(format!(
+                "{} (duplicate of {})",
+                upload.path.display(),
+                upload.key
+            ));
         }
-    };
+    }
 
-    if !overwrite && index.contains(&package) {
-        return Result::Err(PublishError::OverwriteDisallowedError.into());
+    if !errors.is_empty() {
+        return Result::Err(PublishError::InvalidPackages(errors).into());
     }
 
-    upload_package(driver, bucket.as_str(), path, &package).await?;
+    let existing: Vec<String> = uploads
+        .iter()
+        .filter(|x| index.find_file(&x.key).is_some())
+        .map(|x| x.key.clone())
+        .collect();
+
+    if !options.overwrite && !existing.is_empty() {
+        return Result::Err(PublishError::OverwriteDisallowedError(existing).into());
+    }
+
+    let transfers: Vec<(PathBuf, String, Bytes)> = uploads
+        .iter()
+        .map(|x| (x.path.clone(), x.key.clone(), x.data.clone()))
+        .collect();
+
+    let results: Vec<(String, Result<(), String>)> = stream::iter(transfers)
+        .map(|(path, key, data)| {
+            let bucket = bucket.as_str();
+
+            async move {
+                println!(
+                    "Uploading {} -> {}",
+                    path.display(),
+                    driver.get_object_ref(bucket, &key).get_object_url()
+                );
+
+                let result = driver
+                    .put(bucket, key.as_str(), data)
+                    .await
+                    .map_err(|e| format!("{}: {}", key, e));
+
+                (key, result)
+            }
+        })
+        .buffer_unordered(options.jobs.max(1))
+        .collect()
+        .await;
+
+    let failures: Vec<String> = results.iter().filter_map(|x| x.1.clone().err()).collect();
+
+    if !failures.is_empty() {
+        rollback(driver, bucket.as_str(), &results, &existing).await;
+        return Result::Err(PublishError::UploadError(failures).into());
+    }
+
+    if options.update_latest {
+        let packages: Vec<Package> = uploads
+            .iter()
+            .map(|x| x.distribution.as_package())
+            .collect();
+        update_latest_tags(driver, bucket.as_str(), &index, &packages).await?;
+    }
 
     Result::Ok(())
 }
 
-async fn upload_package(
+/// Builds the project in `dir` and publishes the distributions it produces. When the version is
+/// declared statically in `pyproject.toml`, an existing release is detected before building.
+pub(crate) async fn publish_project(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    dir: &Path,
+    options: PublishOptions,
+) -> Result<(), Box<dyn Error>> {
+    let bucket = if config.bucket.is_none() {
+        return Result::Err(PublishError::NoBucketDefined.into());
+    } else {
+        config.bucket.clone().unwrap()
+    };
+
+    let project = PyProject::load(dir)?;
+
+    match &project.version {
+        Some(version) if !options.overwrite => {
+            let index = Index::from_storage_bucket(driver, bucket.as_str()).await?;
+            let semantic_version = SemanticVersion::from_str(version).ok();
+            let exists = index
+                .names()
+                .iter()
+                .filter(|x| normalize_name(x) == normalize_name(&project.name))
+                .flat_map(|x| index.list_versions(x))
+                .any(|x| {
+                    x.version == *version
+                        || (semantic_version.is_some() && x.semantic_version() == semantic_version)
+                });
+
+            if exists {
+                return Result::Err(
+                    PublishError::VersionExists(format!("{}@{}", project.name, version)).into(),
+                );
+            }
+        }
+        Some(_) => (),
+        None => println!(
+            "Version of {} is dynamic, checking for conflicts after the build",
+            project.name
+        ),
+    }
+
+    let python = config
+        .python
+        .clone()
+        .unwrap_or_else(|| String::from("python"));
+    let out_dir = tempdir::TempDir::new("sling-")?;
+
+    pip::build_project(&python, dir, out_dir.path())?;
+
+    let paths = collect_files(&[out_dir.path().to_string_lossy().to_string()])?;
+
+    publish(config, driver, &paths, options).await
+}
+
+/// Points the `latest` tag of each package at the highest of the given versions, unless the
+/// current tag or a published release is higher. Pre-releases are never promoted.
+pub(crate) async fn update_latest_tags(
     driver: &'_ dyn StorageDriver,
     bucket: &str,
-    path: &Path,
-    package: &Package,
+    index: &Index,
+    packages: &[Package],
 ) -> Result<(), Box<dyn Error>> {
-    println!(
-        "Uploading package to S3: {} -> s3://{}/{}",
-        path.to_str().unwrap(),
-        bucket,
-        package.object_key()
-    );
-
-    let mut data = Vec::new();
-    let mut file = File::open(path)
-        .map_err(|e| PublishError::UploadError(format!("failed to read package file: {}", e)))?;
-
-    file.read_to_end(&mut data)?;
-    driver
-        .put(bucket, package.object_key().as_str(), Bytes::from(data))
-        .await?;
+    let mut latest: BTreeMap<String, (SemanticVersion, String)> = BTreeMap::new();
+
+    for package in packages {
+        let version = package.version.to_string();
+        let semantic_version = match SemanticVersion::from_str(&version) {
+            Result::Ok(x) if !x.is_prerelease() => x,
+            _ => continue,
+        };
+
+        if latest
+            .get(&package.name)
+            .is_none_or(|x| x.0 < semantic_version)
+        {
+            latest.insert(package.name.clone(), (semantic_version, version));
+        }
+    }
+
+    if latest.is_empty() {
+        return Result::Ok(());
+    }
+
+    let mut messages: Vec<String> = Vec::new();
+
+    BucketMetadata::update(driver, bucket, |metadata| {
+        messages.clear();
+
+        for (name, (semantic_version, version)) in latest.iter() {
+            match higher_latest(metadata, index, name, semantic_version) {
+                Some(current) => messages.push(format!(
+                    "Kept {} tag of {} on the higher version {}",
+                    LATEST_TAG, name, current
+                )),
+                None => {
+                    metadata.tag(name, version, LATEST_TAG);
+                    messages.push(format!("Tagged {}@{} as {}", name, version, LATEST_TAG));
+                }
+            }
+        }
+    })
+    .await?;
+
+    for message in messages {
+        println!("{}", message);
+    }
 
     Result::Ok(())
 }
+
+/// Returns the version `latest` of `name` has to stay on rather than move back to `version`:
+/// the version it currently tags unless that is yanked, or the highest release in the index.
+pub(crate) fn higher_latest(
+    metadata: &BucketMetadata,
+    index: &Index,
+    name: &str,
+    version: &SemanticVersion,
+) -> Option<SemanticVersion> {
+    let tagged = metadata
+        .get_tag(name, LATEST_TAG)
+        .filter(|x| metadata.get_yank(name, x).is_none())
+        .and_then(|x| SemanticVersion::from_str(x).ok());
+
+    tagged
+        .into_iter()
+        .chain(
+            index
+                .find_highest_release(name)
+                .and_then(|x| x.semantic_version()),
+        )
+        .filter(|x| x > version)
+        .fold(None, |a: Option<SemanticVersion>, b| match a {
+            Some(a) if a > b => Some(a),
+            _ => Some(b),
+        })
+}
+
+/// Expands the arguments of `put`. Directories contribute the distribution files they contain
+/// and arguments containing wildcards are expanded as globs.
+pub(crate) fn collect_files(args: &[String]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+
+    for arg in args {
+        if arg.contains(['*', '?', '[']) {
+            for path in glob::glob(arg)? {
+                let path = path?;
+
+                if path.is_file() {
+                    paths.push(path);
+                }
+            }
+        } else if Path::new(arg).is_dir() {
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(arg)?
+                .map(|x| x.map(|x| x.path()))
+                .collect::<Result<Vec<PathBuf>, std::io::Error>>()?
+                .into_iter()
+                .filter(|x| {
+                    x.is_file()
+                        && x.file_name()
+                            .and_then(|x| x.to_str())
+                            .and_then(Distribution::from_filename)
+                            .is_some()
+                })
+                .collect();
+            entries.sort();
+            paths.append(&mut entries);
+        } else {
+            paths.push(PathBuf::from(arg));
+        }
+    }
+
+    paths.dedup();
+
+    Result::Ok(paths)
+}
+
+/// Reads a distribution file and runs the checks `publish` applies before uploading it.
+pub(crate) fn read_distribution(path: &Path) -> Result<(Distribution, Bytes), Box<dyn Error>> {
+    let upload = validate(path)?;
+
+    Result::Ok((upload.distribution, upload.data))
+}
+
+fn validate(path: &Path) -> Result<Upload, Box<dyn Error>> {
+    let filename = path
+        .file_name()
+        .and_then(|x| x.to_str())
+        .unwrap_or_default()
+        .to_string();
+    let distribution = Distribution::from_filename(&filename)
+        .ok_or("not a source distribution or wheel filename")?;
+    let data = std::fs::read(path)?;
+    let problems = check::check(&distribution, &Archive::read(&filename, &data)?);
+
+    if !problems.is_empty() {
+        return Result::Err(problems.join(", ").into());
+    }
+
+    Result::Ok(Upload {
+        path: path.to_path_buf(),
+        key: distribution.object_key(),
+        distribution,
+        data: Bytes::from(data),
+    })
+}
+
+/// Removes the files a failed publish managed to upload. Files that replaced an existing object
+/// cannot be restored and are left in place.
+async fn rollback(
+    driver: &'_ dyn StorageDriver,
+    bucket: &str,
+    results: &[(String, Result<(), String>)],
+    existing: &[String],
+) {
+    for (key, _) in results.iter().filter(|x| x.1.is_ok()) {
+        if existing.contains(key) {
+            println!("Cannot roll back overwritten file {}", key);
+            continue;
+        }
+
+        match driver.delete(bucket, key).await {
+            Result::Ok(_) => println!("Rolled back {}", key),
+            Result::Err(e) => println!("Failed to roll back {} (Error={})", key, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::write_sdist;
+    use crate::storage::memory::MemoryStorageDriver;
+
+    fn sdist(dir: &Path, name: &str, version: &str) -> PathBuf {
+        write_sdist(dir, &format!("{}-{}.tar.gz", name, version), name, version)
+    }
+
+    fn config() -> RuntimeConfig {
+        RuntimeConfig {
+            bucket: Some("bucket".to_string()),
+            ..RuntimeConfig::default()
+        }
+    }
+
+    fn options() -> PublishOptions {
+        PublishOptions {
+            overwrite: false,
+            update_latest: true,
+            jobs: 4,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_keeps_latest() {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let driver = MemoryStorageDriver::default();
+        let latest = || async {
+            BucketMetadata::load(&driver, "bucket")
+                .await
+                .unwrap()
+                .get_tag("pkg", LATEST_TAG)
+                .cloned()
+        };
+
+        for version in ["2.0.0", "1.9.0"] {
+            let paths = vec![sdist(dir.path(), "pkg", version)];
+            publish(&config(), &driver, &paths, options())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(latest().await.unwrap(), "2.0.0");
+
+        let paths = vec![sdist(dir.path(), "pkg", "2.1.0")];
+        publish(&config(), &driver, &paths, options())
+            .await
+            .unwrap();
+
+        assert_eq!(latest().await.unwrap(), "2.1.0");
+    }
+
+    #[tokio::test]
+    async fn test_publish_rolls_back() {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let driver = MemoryStorageDriver::default();
+
+        driver.insert("bucket", "pkg/pkg-1.0.0.tar.gz", "previous");
+        driver.fail_put("other/other-1.0.0.tar.gz");
+
+        let paths = vec![
+            sdist(dir.path(), "pkg", "1.0.0"),
+            sdist(dir.path(), "pkg", "1.1.0"),
+            sdist(dir.path(), "other", "1.0.0"),
+        ];
+        let options = PublishOptions {
+            overwrite: true,
+            ..options()
+        };
+        let error = publish(&config(), &driver, &paths, options)
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("other/other-1.0.0.tar.gz"));
+        // The new file is removed again, the overwritten one can't be restored and stays
+        assert_eq!(driver.keys("bucket"), vec!["pkg/pkg-1.0.0.tar.gz"]);
+        assert!(BucketMetadata::load(&driver, "bucket").await.is_err());
+    }
+}
@@ -0,0 +1,353 @@
+use crate::import::parse_requirement;
+use crate::package::Package;
+use regex::Regex;
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+enum RequirementsError {
+    InvalidRequirement(String, String),
+    MissingValue(String, String),
+    RecursiveInclude(String),
+}
+
+impl std::fmt::Display for RequirementsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::InvalidRequirement(location, line) => {
+                write!(f, "invalid requirement at {}: {}", location, line)
+            }
+            Self::MissingValue(location, option) => {
+                write!(f, "{} requires a value at {}", option, location)
+            }
+            Self::RecursiveInclude(path) => write!(f, "{} includes itself", path),
+        }
+    }
+}
+
+impl std::error::Error for RequirementsError {}
+
+/// A requirement sling can look up in the bucket.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Requirement {
+    pub(crate) package: Package,
+    pub(crate) marker: Option<String>,
+    /// `--hash` values, i.e. `sha256:<hex digest>`.
+    pub(crate) hashes: Vec<String>,
+    /// The line as written, handed to pip when the project isn't published to the bucket. Lines
+    /// in the `name@version` format are only ever resolved by sling.
+    pub(crate) pip_line: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Line {
+    Requirement(Requirement),
+    /// Urls, paths and editable installs, which only pip can install.
+    Pip(String),
+}
+
+/// A pip requirements file with its `-r` includes expanded. Sling's own `name@version` lines
+/// are accepted as well.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct RequirementsFile {
+    pub(crate) lines: Vec<Line>,
+    /// Global options such as `--index-url` or `-c constraints.txt`, passed on to pip.
+    pub(crate) options: Vec<String>,
+}
+
+impl RequirementsFile {
+    pub(crate) fn read(path: &Path) -> Result<RequirementsFile, Box<dyn Error>> {
+        let mut file = RequirementsFile::default();
+        let mut seen: HashSet<PathBuf> = HashSet::new();
+
+        file.include(path, &mut seen)?;
+
+        Result::Ok(file)
+    }
+
+    /// Parses requirements generated from another format, resolving relative paths against
+    /// `base`. `source` names the origin in error messages.
+    pub(crate) fn parse(
+        content: &str,
+        source: &str,
+        base: &Path,
+    ) -> Result<RequirementsFile, Box<dyn Error>> {
+        let mut file = RequirementsFile::default();
+        let mut seen: HashSet<PathBuf> = HashSet::new();
+
+        file.add_lines(content, source, base, &mut seen)?;
+
+        Result::Ok(file)
+    }
+
+    pub(crate) fn packages(&self) -> Vec<Package> {
+        self.lines
+            .iter()
+            .filter_map(|x| match x {
+                Line::Requirement(requirement) => Some(requirement.package.clone()),
+                Line::Pip(_) => None,
+            })
+            .collect()
+    }
+
+    fn include(&mut self, path: &Path, seen: &mut HashSet<PathBuf>) -> Result<(), Box<dyn Error>> {
+        let canonical = path.canonicalize()?;
+
+        if !seen.insert(canonical.clone()) {
+            return Result::Err(
+                RequirementsError::RecursiveInclude(path.display().to_string()).into(),
+            );
+        }
+
+        let content = std::fs::read_to_string(path)?;
+        let base = canonical.parent().unwrap_or_else(|| Path::new("."));
+
+        self.add_lines(&content, &path.display().to_string(), base, seen)?;
+
+        seen.remove(&canonical);
+
+        Result::Ok(())
+    }
+
+    fn add_lines(
+        &mut self,
+        content: &str,
+        source: &str,
+        base: &Path,
+        seen: &mut HashSet<PathBuf>,
+    ) -> Result<(), Box<dyn Error>> {
+        for (number, line) in logical_lines(content) {
+            let location = format!("{}:{}", source, number);
+            let line = expand_variables(&strip_comment(&line));
+            let line = line.trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if !line.starts_with('-') {
+                self.lines.push(parse_line(line).ok_or_else(|| {
+                    RequirementsError::InvalidRequirement(location, line.to_string())
+                })?);
+                continue;
+            }
+
+            let (option, value) = match line.split_once(|c: char| c == '=' || c.is_whitespace()) {
+                Some((option, value)) => (option, Some(value.trim())),
+                None => (line, None),
+            };
+            let value = || {
+                value
+                    .filter(|x| !x.is_empty())
+                    .ok_or_else(|| RequirementsError::MissingValue(location.clone(), option.into()))
+            };
+
+            match option {
+                "-r" | "--requirement" => {
+                    self.include(&base.join(value()?), seen)?;
+                }
+                "-c" | "--constraint" | "-f" | "--find-links" => {
+                    self.options
+                        .push(format!("{} {}", option, resolve_path(base, value()?)));
+                }
+                "-e" | "--editable" => self.lines.push(Line::Pip(line.to_string())),
+                _ => self.options.push(line.to_string()),
+            }
+        }
+
+        Result::Ok(())
+    }
+}
+
+/// Joins lines ending with a backslash, keeping the number of the line each one starts on.
+fn logical_lines(content: &str) -> Vec<(usize, String)> {
+    let mut result: Vec<(usize, String)> = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for (index, line) in content.lines().enumerate() {
+        let (number, mut text) = current.take().unwrap_or((index + 1, String::new()));
+
+        match line.strip_suffix('\\') {
+            Some(start) => {
+                text.push_str(start);
+                current = Some((number, text));
+            }
+            None => {
+                text.push_str(line);
+                result.push((number, text));
+            }
+        }
+    }
+
+    result.extend(current);
+    result
+}
+
+fn strip_comment(line: &str) -> String {
+    Regex::new(r"(^|\s)#.*$")
+        .unwrap()
+        .replace(line, "")
+        .to_string()
+}
+
+/// Expands `${NAME}` the way pip does, leaving unset variables untouched.
+fn expand_variables(line: &str) -> String {
+    Regex::new(r"\$\{([A-Z0-9_]+)\}")
+        .unwrap()
+        .replace_all(line, |captures: &regex::Captures| {
+            std::env::var(&captures[1]).unwrap_or_else(|_| captures[0].to_string())
+        })
+        .to_string()
+}
+
+fn resolve_path(base: &Path, value: &str) -> String {
+    let path = base.join(value);
+
+    if !value.contains("://") && path.exists() {
+        path.display().to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn parse_line(line: &str) -> Option<Line> {
+    // Per-requirement options such as `--hash` follow the requirement itself
+    let (requirement, options) = match Regex::new(r"\s--?[A-Za-z]").unwrap().find(line) {
+        Some(m) => (&line[..m.start()], &line[m.start()..]),
+        None => (line, ""),
+    };
+    let (requirement, marker) = match requirement.split_once(';') {
+        Some((requirement, marker)) => (requirement.trim(), Some(marker.trim().to_string())),
+        None => (requirement.trim(), None),
+    };
+    let captures = Regex::new(r"^([A-Za-z0-9][A-Za-z0-9._-]*)\s*(?:\[([^\]]*)\])?\s*(.*)$")
+        .unwrap()
+        .captures(requirement);
+
+    let (name, extras, specifier) = match captures {
+        Some(captures) => (
+            captures[1].to_string(),
+            captures
+                .get(2)
+                .map(|x| {
+                    x.as_str()
+                        .split(',')
+                        .map(|x| x.trim().to_string())
+                        .filter(|x| !x.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            captures[3].replace(' ', ""),
+        ),
+        // Paths and urls
+        None => return Some(Line::Pip(line.to_string())),
+    };
+
+    let is_direct_reference = specifier.starts_with('@')
+        && (specifier.contains("://") || specifier.starts_with("@file:"));
+    let is_specifier = specifier.is_empty() || specifier.starts_with(|c| "@=!<>~^(".contains(c));
+
+    if is_direct_reference || !is_specifier {
+        return Some(Line::Pip(line.to_string()));
+    }
+
+    let specifier = specifier.trim_start_matches('(').trim_end_matches(')');
+    let mut package = match parse_requirement(&format!("{}{}", name, specifier)) {
+        Result::Ok(package) => package,
+        // Versions sling can't resolve, such as `==1.*`, are still fine for pip
+        Result::Err(_) if !specifier.starts_with('@') => return Some(Line::Pip(line.to_string())),
+        Result::Err(_) => return None,
+    };
+    package.extras = extras;
+    let hashes = Regex::new(r"--hash[=\s]\s*(\S+)")
+        .unwrap()
+        .captures_iter(options)
+        .map(|x| x[1].to_string())
+        .collect();
+
+    Some(Line::Requirement(Requirement {
+        package,
+        marker,
+        hashes,
+        pip_line: if specifier.starts_with('@') {
+            None
+        } else {
+            Some(line.to_string())
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_read() {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        std::fs::write(dir.path().join("constraints.txt"), "").unwrap();
+        std::fs::write(
+            dir.path().join("base.txt"),
+            "# shared\nmy-lib[gpu,cuda] == 1.2.0 ; python_version >= \"3.8\" \\\n    --hash=sha256:abc --hash sha256:def\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("requirements.txt"),
+            "--index-url https://pypi.example.com/simple/\n\
+             -c constraints.txt\n\
+             -r base.txt\n\
+             \n\
+             my_tool@stable  # a sling tag\n\
+             requests>=2.0,<3\n\
+             -e ./local\n\
+             other @ https://example.com/other-1.0.tar.gz\n",
+        )
+        .unwrap();
+
+        let file = RequirementsFile::read(&dir.path().join("requirements.txt")).unwrap();
+
+        assert_eq!(
+            file.options,
+            vec![
+                "--index-url https://pypi.example.com/simple/".to_string(),
+                format!(
+                    "-c {}",
+                    dir.path()
+                        .canonicalize()
+                        .unwrap()
+                        .join("constraints.txt")
+                        .display()
+                ),
+            ]
+        );
+        assert_eq!(
+            file.lines[0],
+            Line::Requirement(Requirement {
+                package: Package::from_str("my-lib[gpu,cuda]@1.2.0").unwrap(),
+                marker: Some("python_version >= \"3.8\"".to_string()),
+                hashes: vec!["sha256:abc".to_string(), "sha256:def".to_string()],
+                pip_line: Some(
+                    "my-lib[gpu,cuda] == 1.2.0 ; python_version >= \"3.8\"     --hash=sha256:abc --hash sha256:def"
+                        .to_string()
+                ),
+            })
+        );
+        assert_eq!(
+            file.packages()[1..],
+            [
+                Package::from_str("my_tool@stable").unwrap(),
+                Package::from_str("requests@>=2.0,<3").unwrap(),
+            ]
+        );
+        assert!(matches!(&file.lines[1], Line::Requirement(x) if x.pip_line.is_none()));
+        assert_eq!(file.lines[3], Line::Pip("-e ./local".to_string()));
+        assert_eq!(
+            file.lines[4],
+            Line::Pip("other @ https://example.com/other-1.0.tar.gz".to_string())
+        );
+
+        std::fs::write(dir.path().join("base.txt"), "-r requirements.txt\n").unwrap();
+        assert!(RequirementsFile::read(&dir.path().join("requirements.txt")).is_err());
+    }
+}
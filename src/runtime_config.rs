@@ -26,10 +26,14 @@ impl RuntimeConfig {
         stored: RuntimeConfig,
         defaults: RuntimeConfig,
     ) -> RuntimeConfig {
+        supplied.or(stored).or(defaults)
+    }
+
+    pub(crate) fn or(self, other: RuntimeConfig) -> RuntimeConfig {
         RuntimeConfig {
-            bucket: supplied.bucket.or(stored.bucket).or(defaults.bucket),
-            pip_args: supplied.pip_args.or(stored.pip_args).or(defaults.pip_args),
-            python: supplied.python.or(stored.python).or(defaults.python),
+            bucket: self.bucket.or(other.bucket),
+            pip_args: self.pip_args.or(other.pip_args),
+            python: self.python.or(other.python),
         }
     }
 }
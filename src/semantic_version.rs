@@ -1,20 +1,96 @@
 use std::fmt::Formatter;
 use std::str::FromStr;
 
+/// Matches the version strings accepted by `SemanticVersion::from_str` in package filenames.
+pub(crate) const VERSION_PATTERN: &str = "\\d+\\.\\d+\\.\\d+(?:(?:a|b|rc)\\d+|\\.dev\\d+)?";
+
 #[derive(Debug)]
 pub(crate) enum ParseSemanticVersionError {
     InvalidFormat,
     NonNumericSegment,
 }
 
+/// PEP 440 pre-release segments, ordered from least to most mature.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub(crate) enum PreRelease {
+    Dev(usize),
+    Alpha(usize),
+    Beta(usize),
+    Candidate(usize),
+}
+
+impl FromStr for PreRelease {
+    type Err = ParseSemanticVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (constructor, number): (fn(usize) -> PreRelease, &str) =
+            if let Some(x) = s.strip_prefix(".dev") {
+                (PreRelease::Dev, x)
+            } else if let Some(x) = s.strip_prefix("rc") {
+                (PreRelease::Candidate, x)
+            } else if let Some(x) = s.strip_prefix('a') {
+                (PreRelease::Alpha, x)
+            } else if let Some(x) = s.strip_prefix('b') {
+                (PreRelease::Beta, x)
+            } else {
+                return Result::Err(ParseSemanticVersionError::InvalidFormat);
+            };
+
+        number
+            .parse()
+            .map(constructor)
+            .map_err(|_| ParseSemanticVersionError::NonNumericSegment)
+    }
+}
+
+impl std::fmt::Display for PreRelease {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Dev(n) => write!(f, ".dev{}", n),
+            Self::Alpha(n) => write!(f, "a{}", n),
+            Self::Beta(n) => write!(f, "b{}", n),
+            Self::Candidate(n) => write!(f, "rc{}", n),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct SemanticVersion(usize, usize, usize);
+pub(crate) struct SemanticVersion {
+    major: usize,
+    minor: usize,
+    patch: usize,
+    pre: Option<PreRelease>,
+}
+
+impl SemanticVersion {
+    pub(crate) fn new(major: usize, minor: usize, patch: usize) -> SemanticVersion {
+        SemanticVersion {
+            major,
+            minor,
+            patch,
+            pre: None,
+        }
+    }
+
+    pub(crate) fn segments(&self) -> (usize, usize, usize) {
+        (self.major, self.minor, self.patch)
+    }
+
+    pub(crate) fn is_prerelease(&self) -> bool {
+        self.pre.is_some()
+    }
+}
 
 impl FromStr for SemanticVersion {
     type Err = ParseSemanticVersionError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<String> = s.split('.').into_iter().map(|s| s.to_string()).collect();
+        let split = s
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .map(|i| if s[..i].ends_with('.') { i - 1 } else { i })
+            .unwrap_or(s.len());
+        let (release, suffix) = s.split_at(split);
+        let parts: Vec<String> = release.split('.').map(|s| s.to_string()).collect();
         let mut major: usize = 0;
         let mut minor: usize = 0;
         let mut patch: usize = 0;
@@ -40,24 +116,76 @@ impl FromStr for SemanticVersion {
             }
         }
 
-        Result::Ok(SemanticVersion(major, minor, patch))
+        let pre = if suffix.is_empty() {
+            None
+        } else {
+            Some(PreRelease::from_str(suffix)?)
+        };
+
+        Result::Ok(SemanticVersion {
+            major,
+            minor,
+            patch,
+            pre,
+        })
     }
 }
 
 impl PartialOrd for SemanticVersion {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        if other.0 != self.0 {
-            Some(self.0.cmp(&other.0))
-        } else if other.1 != self.1 {
-            Some(self.1.cmp(&other.1))
+        if other.major != self.major {
+            Some(self.major.cmp(&other.major))
+        } else if other.minor != self.minor {
+            Some(self.minor.cmp(&other.minor))
+        } else if other.patch != self.patch {
+            Some(self.patch.cmp(&other.patch))
         } else {
-            Some(self.2.cmp(&other.2))
+            // A release sorts after any of its pre-releases
+            match (&self.pre, &other.pre) {
+                (None, None) => Some(std::cmp::Ordering::Equal),
+                (None, Some(_)) => Some(std::cmp::Ordering::Greater),
+                (Some(_), None) => Some(std::cmp::Ordering::Less),
+                (Some(a), Some(b)) => a.partial_cmp(b),
+            }
         }
     }
 }
 
 impl std::fmt::Display for SemanticVersion {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+
+        if let Some(pre) = &self.pre {
+            write!(f, "{}", pre)?;
+        }
+
+        Result::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(s: &str) -> SemanticVersion {
+        SemanticVersion::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_prerelease_round_trip() {
+        for s in ["1.2.3", "1.2.3a1", "1.2.3b2", "1.2.3rc1", "1.2.3.dev45"] {
+            assert_eq!(version(s).to_string(), s);
+        }
+        assert!(SemanticVersion::from_str("1.2.3-beta").is_err());
+    }
+
+    #[test]
+    fn test_prerelease_ordering() {
+        assert!(version("1.2.3.dev1") < version("1.2.3a1"));
+        assert!(version("1.2.3a1") < version("1.2.3b1"));
+        assert!(version("1.2.3b1") < version("1.2.3rc1"));
+        assert!(version("1.2.3rc1") < version("1.2.3"));
+        assert!(version("1.2.3") < version("1.2.4.dev1"));
+        assert!(version("1.2.3rc1") < version("1.2.3rc2"));
     }
 }
@@ -0,0 +1,479 @@
+use crate::bucket_metadata::BucketMetadata;
+use crate::digest;
+use crate::http;
+use crate::import::{parse_upstream_filename, project_url};
+use crate::index::Index;
+use crate::pattern::glob_to_regex;
+use crate::simple::{
+    normalize_name, parse_project_page, render_project_list, render_project_page, FileLink,
+};
+use crate::upload::{self, UploadError};
+use crate::{RuntimeConfig, StorageDriver};
+use bytes::Bytes;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
+use regex::Regex;
+use std::convert::Infallible;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use url::Url;
+
+#[derive(Debug)]
+enum ServeError {
+    NoBucketDefined,
+    EmptyAllowlist,
+}
+
+impl std::fmt::Display for ServeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoBucketDefined => write!(f, "no bucket was provided"),
+            Self::EmptyAllowlist => {
+                write!(f, "an upstream index requires at least one allowed project")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ServeError {}
+
+/// An index that projects missing from the bucket are fetched from. Only projects matching the
+/// allowlist are ever proxied, and never ones published to the bucket directly, so a public
+/// package cannot shadow a private name. See `proxied_upstream`.
+pub(crate) struct Upstream {
+    url: Url,
+    allow: Vec<Regex>,
+}
+
+impl Upstream {
+    pub(crate) fn new(url: Url, allow: &[String]) -> Result<Upstream, Box<dyn Error>> {
+        if allow.is_empty() {
+            return Result::Err(ServeError::EmptyAllowlist.into());
+        }
+
+        let allow = allow
+            .iter()
+            .map(|x| glob_to_regex(&normalize_name(x)))
+            .collect::<Result<Vec<Regex>, regex::Error>>()?;
+
+        Result::Ok(Upstream { url, allow })
+    }
+
+    fn allows(&self, project: &str) -> bool {
+        let project = normalize_name(project);
+        self.allow.iter().any(|x| x.is_match(&project))
+    }
+
+    async fn files(&self, project: &str) -> Result<Vec<FileLink>, Box<dyn Error>> {
+        let (url, page) = http::get(&project_url(&self.url, project)?).await?;
+        Result::Ok(parse_project_page(
+            String::from_utf8_lossy(&page).as_ref(),
+            &url,
+        ))
+    }
+}
+
+struct ServeState {
+    config: RuntimeConfig,
+    driver: Arc<dyn StorageDriver>,
+    bucket: String,
+    upstream: Option<Upstream>,
+    upload_tokens: Vec<String>,
+    max_upload_size: u64,
+}
+
+pub(crate) async fn serve(
+    config: &RuntimeConfig,
+    driver: Arc<dyn StorageDriver>,
+    address: SocketAddr,
+    upstream: Option<Upstream>,
+    upload_tokens: Vec<String>,
+    max_upload_size: u64,
+) -> Result<(), Box<dyn Error>> {
+    let bucket = if config.bucket.is_none() {
+        return Result::Err(ServeError::NoBucketDefined.into());
+    } else {
+        config.bucket.clone().unwrap()
+    };
+
+    if let Some(upstream) = &upstream {
+        println!("Proxying allowed projects from {}", upstream.url);
+    }
+
+    if !upload_tokens.is_empty() {
+        println!("Accepting uploads at http://{}/", address);
+    }
+
+    let state = Arc::new(ServeState {
+        config: config.clone(),
+        driver,
+        bucket,
+        upstream,
+        upload_tokens,
+        max_upload_size,
+    });
+    let service = make_service_fn(move |_| {
+        let state = state.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+    });
+
+    println!("Serving simple index at http://{}/simple/", address);
+
+    Server::try_bind(&address)?
+        .http1_header_read_timeout(std::time::Duration::from_secs(30))
+        .serve(service)
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await?;
+
+    Result::Ok(())
+}
+
+async fn handle(state: Arc<ServeState>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+
+    if method == Method::POST && matches!(path.as_str(), "/" | "/legacy/") {
+        let response = handle_upload(&state, req).await;
+        println!("{} {} {}", method, path, response.status().as_u16());
+        return Result::Ok(response);
+    }
+
+    let response = match route(&state, &method, &path).await {
+        Result::Ok(response) => response,
+        Result::Err(e) => {
+            println!("Request failed: {} {} (Error={})", method, path, e);
+            status_response(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    };
+
+    println!("{} {} {}", method, path, response.status().as_u16());
+
+    Result::Ok(response)
+}
+
+async fn handle_upload(state: &ServeState, req: Request<Body>) -> Response<Body> {
+    if state.upload_tokens.is_empty() {
+        return status_response(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    let result = upload::upload(
+        &state.config,
+        state.driver.as_ref(),
+        &state.upload_tokens,
+        state.max_upload_size,
+        req,
+    )
+    .await;
+
+    match result {
+        Result::Ok(filename) => {
+            println!("Accepted upload of {}", filename);
+            status_response(StatusCode::OK)
+        }
+        Result::Err(e) => {
+            println!("Upload rejected: {}", e);
+
+            let mut response = Response::new(Body::from(e.to_string()));
+            *response.status_mut() = e.status();
+
+            if matches!(e, UploadError::Unauthorized) {
+                response.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    header::HeaderValue::from_static("Basic realm=\"sling\""),
+                );
+            }
+
+            response
+        }
+    }
+}
+
+async fn route(
+    state: &ServeState,
+    method: &Method,
+    path: &str,
+) -> Result<Response<Body>, Box<dyn Error>> {
+    if method != Method::GET {
+        return Result::Ok(status_response(StatusCode::METHOD_NOT_ALLOWED));
+    }
+
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match segments.as_slice() {
+        [""] | ["simple"] => {
+            let index = load_index(state).await?;
+            Result::Ok(html_response(render_project_list(&index.names())))
+        }
+        ["simple", project] => {
+            let index = load_index(state).await?;
+            let local = bucket_files(&index, project);
+
+            let (name, files) = match proxied_upstream(state.upstream.as_ref(), &index, project) {
+                Some(upstream) => {
+                    let (name, mut files) =
+                        local.unwrap_or_else(|| (project.to_string(), Vec::new()));
+
+                    match upstream.files(project).await {
+                        Result::Ok(remote) => merge_upstream_files(project, &mut files, remote),
+                        Result::Err(e) => println!(
+                            "Upstream unavailable, serving cached files only: {} (Error={})",
+                            project, e
+                        ),
+                    }
+
+                    (name, files)
+                }
+                None => match local {
+                    Some(x) => x,
+                    None => return Result::Ok(status_response(StatusCode::NOT_FOUND)),
+                },
+            };
+
+            if files.is_empty() {
+                return Result::Ok(status_response(StatusCode::NOT_FOUND));
+            }
+
+            Result::Ok(html_response(render_project_page(&name, &files)))
+        }
+        ["proxy", project, filename] => {
+            let filename = urlencoding::decode(filename)?.into_owned();
+            let index = load_index(state).await?;
+
+            match proxied_upstream(state.upstream.as_ref(), &index, project) {
+                Some(upstream) => proxy_file(state, upstream, project, &filename).await,
+                None => Result::Ok(status_response(StatusCode::NOT_FOUND)),
+            }
+        }
+        ["files", ..] => {
+            let key = urlencoding::decode(&path["/files/".len()..])?.into_owned();
+            let index = load_index(state).await?;
+            if index.find_file(&key).is_none() {
+                return Result::Ok(status_response(StatusCode::NOT_FOUND));
+            }
+
+            let data = state
+                .driver
+                .get(state.bucket.as_str(), key.as_str())
+                .await?;
+
+            Result::Ok(
+                Response::builder()
+                    .header(header::CONTENT_TYPE, "application/octet-stream")
+                    .body(Body::from(data))?,
+            )
+        }
+        _ => Result::Ok(status_response(StatusCode::NOT_FOUND)),
+    }
+}
+
+/// The upstream a project may be fetched from. Projects published to the bucket directly are
+/// never proxied, only allowed ones missing from the bucket or cached from upstream before.
+fn proxied_upstream<'a>(
+    upstream: Option<&'a Upstream>,
+    index: &Index,
+    project: &str,
+) -> Option<&'a Upstream> {
+    let upstream = upstream.filter(|x| x.allows(project))?;
+
+    match index.find_name(project) {
+        Some(name) if index.metadata().get_upstream(&name).is_none() => None,
+        _ => Some(upstream),
+    }
+}
+
+async fn load_index(state: &ServeState) -> Result<Index, Box<dyn Error>> {
+    Index::from_storage_bucket(state.driver.as_ref(), state.bucket.as_str()).await
+}
+
+/// Fetches a file from the upstream index and writes it back to the bucket once its digest has
+/// been verified, so later requests are served from the bucket.
+async fn proxy_file(
+    state: &ServeState,
+    upstream: &Upstream,
+    project: &str,
+    filename: &str,
+) -> Result<Response<Body>, Box<dyn Error>> {
+    let link = match upstream
+        .files(project)
+        .await?
+        .into_iter()
+        .find(|x| x.filename == filename)
+    {
+        Some(link) => link,
+        None => return Result::Ok(status_response(StatusCode::NOT_FOUND)),
+    };
+
+    let (_, data) = http::get(&Url::parse(&link.url)?).await?;
+    let actual = digest::sha256(&data);
+
+    match &link.sha256 {
+        Some(expected) if *expected != actual => {
+            println!(
+                "Upstream digest mismatch: {} (Expected={}, Actual={})",
+                filename, expected, actual
+            );
+            return Result::Ok(status_response(StatusCode::BAD_GATEWAY));
+        }
+        Some(_) => cache_file(state, upstream, filename, data.clone()).await,
+        None => println!(
+            "Not caching {}: upstream did not provide a digest",
+            filename
+        ),
+    }
+
+    Result::Ok(
+        Response::builder()
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .body(Body::from(data))?,
+    )
+}
+
+async fn cache_file(state: &ServeState, upstream: &Upstream, filename: &str, data: Bytes) {
+    let key = match parse_upstream_filename(filename).map(|x| x.object_key()) {
+        Some(key) => key,
+        None => {
+            println!("Not caching {}: unsupported distribution format", filename);
+            return;
+        }
+    };
+
+    // Recorded first, so the project is never seen in the bucket without the marker
+    if let Result::Err(e) = record_upstream(state, upstream, &key).await {
+        println!(
+            "Not caching {}: failed to record it (Error={})",
+            filename, e
+        );
+        return;
+    }
+
+    match state.driver.put(state.bucket.as_str(), &key, data).await {
+        Result::Ok(_) => println!("Cached {} -> {}", filename, key),
+        Result::Err(e) => println!("Failed to cache {} (Error={})", filename, e),
+    }
+}
+
+/// Marks the project of a cached file as coming from upstream, see `proxied_upstream`.
+async fn record_upstream(
+    state: &ServeState,
+    upstream: &Upstream,
+    key: &str,
+) -> Result<(), Box<dyn Error>> {
+    let name = key.split('/').next().unwrap_or_default();
+
+    BucketMetadata::update(state.driver.as_ref(), state.bucket.as_str(), |metadata| {
+        if metadata.get_upstream(name).is_none() {
+            metadata.set_upstream(name, upstream.url.as_str());
+        }
+    })
+    .await?;
+
+    Result::Ok(())
+}
+
+/// Adds the upstream files the bucket does not already hold, pointing them at the proxy route.
+fn merge_upstream_files(project: &str, files: &mut Vec<FileLink>, remote: Vec<FileLink>) {
+    for link in remote {
+        // normalize_name folds the separators pip treats as equivalent in filenames
+        if files
+            .iter()
+            .any(|x| normalize_name(&x.filename) == normalize_name(&link.filename))
+        {
+            continue;
+        }
+
+        files.push(FileLink {
+            url: format!(
+                "/proxy/{}/{}",
+                normalize_name(project),
+                urlencoding::encode(&link.filename)
+            ),
+            ..link
+        });
+    }
+}
+
+fn bucket_files(index: &Index, project: &str) -> Option<(String, Vec<FileLink>)> {
+    let name = index
+        .names()
+        .into_iter()
+        .find(|x| normalize_name(x) == normalize_name(project))?;
+
+    let files: Vec<FileLink> = index
+        .list_versions(&name)
+        .iter()
+        .flat_map(|entry| {
+            entry.files.iter().map(move |file| FileLink {
+                filename: file.filename().to_string(),
+                url: format!(
+                    "/files/{}",
+                    file.key
+                        .split('/')
+                        .map(|x| urlencoding::encode(x).into_owned())
+                        .collect::<Vec<String>>()
+                        .join("/")
+                ),
+                sha256: None,
+                yanked: index
+                    .get_yank(&entry.name, &entry.version)
+                    .map(|x| x.reason.clone().unwrap_or_default()),
+            })
+        })
+        .collect();
+
+    Some((name, files))
+}
+
+fn html_response(page: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(page));
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    response
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::from(
+        status.canonical_reason().unwrap_or_default().to_string(),
+    ));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::Entry;
+    use crate::storage::object_ref::ObjectRef;
+
+    #[test]
+    fn test_proxied_upstream() {
+        let upstream = Upstream::new(
+            Url::parse("https://pypi.org/simple/").unwrap(),
+            &["acme-*".to_string()],
+        )
+        .unwrap();
+        let mut index = Index::new();
+        index.add(Entry::new(
+            "acme_lib",
+            "1.0.0",
+            &ObjectRef {
+                bucket: "private".to_string(),
+                key: "acme_lib/acme_lib-1.0.0.tar.gz".to_string(),
+                protocol: "s3".to_string(),
+                size: None,
+                last_modified: None,
+                md5: None,
+            },
+        ));
+
+        // Published to the bucket, so the public project of the same name is never merged in
+        assert!(proxied_upstream(Some(&upstream), &index, "acme-lib").is_none());
+        assert!(proxied_upstream(Some(&upstream), &index, "acme-other").is_some());
+        assert!(proxied_upstream(Some(&upstream), &index, "requests").is_none());
+        assert!(proxied_upstream(None, &index, "acme-other").is_none());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::archive::Archive;
+use crate::index::Index;
+use crate::install::download_package;
+use crate::list::format_size;
+use crate::package::Package;
+use crate::{yank, RuntimeConfig, StorageDriver};
+use std::error::Error;
+
+#[derive(Debug)]
+enum ShowError {
+    NoBucketDefined,
+    PackageNotFound(String),
+}
+
+impl std::fmt::Display for ShowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoBucketDefined => write!(f, "no bucket was provided"),
+            Self::PackageNotFound(pkg) => write!(f, "package not found: {}", pkg),
+        }
+    }
+}
+
+impl std::error::Error for ShowError {}
+
+pub(crate) async fn show(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    package: Package,
+) -> Result<(), Box<dyn Error>> {
+    let bucket = if config.bucket.is_none() {
+        return Result::Err(ShowError::NoBucketDefined.into());
+    } else {
+        config.bucket.clone().unwrap()
+    };
+
+    let index = Index::from_storage_bucket(driver, bucket.as_str()).await?;
+    let entry = index
+        .resolve(&package)
+        .ok_or_else(|| ShowError::PackageNotFound(package.to_string()))?;
+
+    yank::warn_if_yanked(&index, &entry);
+
+    let dir = tempdir::TempDir::new("sling-")?;
+    let filename = entry.object.filename().to_string();
+    let target = dir.path().join(&filename);
+
+    download_package(driver, entry.clone(), &target).await?;
+
+    let archive = Archive::read(&filename, &std::fs::read(&target)?)?;
+    let metadata = archive.core_metadata()?;
+
+    println!("Name: {}", metadata.name().unwrap_or(&entry.name));
+    println!("Version: {}", metadata.version().unwrap_or(&entry.version));
+    print_field("Metadata-Version", metadata.metadata_version());
+    print_field("Summary", metadata.summary());
+    print_field("Author", metadata.author());
+    print_field("License", metadata.license());
+    print_field("Requires-Python", metadata.requires_python());
+    print_list("Requires-Dist", metadata.requires_dist());
+    print_list("Classifiers", metadata.classifiers());
+    println!("Location: {}", entry.object.get_object_url());
+    println!("Distributions ({}):", entry.files.len());
+
+    for file in entry.files.iter() {
+        println!(
+            "  {:>10}  {}",
+            file.size
+                .map(format_size)
+                .unwrap_or_else(|| "-".to_string()),
+            file.filename()
+        );
+    }
+
+    println!("Files ({}):", archive.files.len());
+
+    for file in archive.files.iter() {
+        println!("  {:>10}  {}", format_size(file.size()), file.path);
+    }
+
+    Result::Ok(())
+}
+
+fn print_field(name: &str, value: Option<&str>) {
+    println!("{}: {}", name, value.unwrap_or("-").replace('\n', "\n  "));
+}
+
+fn print_list(name: &str, values: Vec<&str>) {
+    if values.is_empty() {
+        println!("{}: -", name);
+    } else {
+        println!("{}:", name);
+
+        for value in values {
+            println!("  {}", value);
+        }
+    }
+}
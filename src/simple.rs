@@ -0,0 +1,204 @@
+use url::Url;
+
+#[derive(Clone, Debug)]
+pub(crate) struct FileLink {
+    pub(crate) filename: String,
+    pub(crate) url: String,
+    /// Hex encoded sha256 digest, carried in the `#sha256=` fragment of the link.
+    pub(crate) sha256: Option<String>,
+    /// Set when the file is yanked (PEP 592), holding the reason or an empty string.
+    pub(crate) yanked: Option<String>,
+}
+
+/// Normalizes a project name as described by PEP 503.
+pub(crate) fn normalize_name(name: &str) -> String {
+    let mut result = String::new();
+    let mut previous_separator = false;
+
+    for c in name.chars() {
+        if c == '-' || c == '_' || c == '.' {
+            if !previous_separator {
+                result.push('-');
+            }
+            previous_separator = true;
+        } else {
+            result.push(c.to_ascii_lowercase());
+            previous_separator = false;
+        }
+    }
+
+    result
+}
+
+pub(crate) fn render_project_list(names: &[String]) -> String {
+    let links: Vec<String> = names
+        .iter()
+        .map(|name| {
+            format!(
+                "    <a href=\"/simple/{}/\">{}</a><br/>",
+                escape(&normalize_name(name)),
+                escape(name)
+            )
+        })
+        .collect();
+
+    render_page("Simple index", &links)
+}
+
+pub(crate) fn render_project_page(name: &str, files: &[FileLink]) -> String {
+    let links: Vec<String> = files
+        .iter()
+        .map(|file| {
+            let yanked = match &file.yanked {
+                Some(reason) => format!(" data-yanked=\"{}\"", escape(reason)),
+                None => String::new(),
+            };
+
+            let url = match &file.sha256 {
+                Some(digest) => format!("{}#sha256={}", file.url, digest),
+                None => file.url.clone(),
+            };
+
+            format!(
+                "    <a href=\"{}\"{}>{}</a><br/>",
+                escape(&url),
+                yanked,
+                escape(&file.filename)
+            )
+        })
+        .collect();
+
+    render_page(&format!("Links for {}", name), &links)
+}
+
+/// Extracts the file links from a project page served by a PEP 503 simple index. Link targets
+/// are resolved against `base`, the URL the page was fetched from.
+pub(crate) fn parse_project_page(html: &str, base: &Url) -> Vec<FileLink> {
+    let anchor = regex::Regex::new(r"(?is)<a\s([^>]*)>(.*?)</a>").unwrap();
+    let attribute = regex::Regex::new(
+        r#"([a-zA-Z][a-zA-Z0-9-]*)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+)))?"#,
+    )
+    .unwrap();
+    let mut links: Vec<FileLink> = Vec::new();
+
+    for captures in anchor.captures_iter(html) {
+        let mut href: Option<String> = None;
+        let mut yanked: Option<String> = None;
+
+        for attr in attribute.captures_iter(captures.get(1).unwrap().as_str()) {
+            let value = unescape(
+                attr.get(2)
+                    .or_else(|| attr.get(3))
+                    .or_else(|| attr.get(4))
+                    .map(|x| x.as_str())
+                    .unwrap_or(""),
+            );
+
+            match attr.get(1).unwrap().as_str().to_ascii_lowercase().as_str() {
+                "href" => href = Some(value),
+                "data-yanked" => yanked = Some(value),
+                _ => (),
+            }
+        }
+
+        let mut url = match href.and_then(|x| base.join(&x).ok()) {
+            Some(url) => url,
+            None => continue,
+        };
+
+        let sha256 = url
+            .fragment()
+            .and_then(|x| x.strip_prefix("sha256="))
+            .map(|x| x.to_ascii_lowercase());
+        url.set_fragment(None);
+
+        links.push(FileLink {
+            filename: unescape(captures.get(2).unwrap().as_str().trim()),
+            url: url.to_string(),
+            sha256,
+            yanked,
+        });
+    }
+
+    links
+}
+
+fn render_page(title: &str, links: &[String]) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n  <head>\n    <meta name=\"pypi:repository-version\" content=\"1.0\">\n    <title>{}</title>\n  </head>\n  <body>\n    <h1>{}</h1>\n{}\n  </body>\n</html>\n",
+        escape(title),
+        escape(title),
+        links.join("\n")
+    )
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("My_Package"), "my-package");
+        assert_eq!(normalize_name("my.-_package"), "my-package");
+    }
+
+    #[test]
+    fn test_render_project_page_yanked() {
+        let page = render_project_page(
+            "pkg",
+            &[
+                FileLink {
+                    filename: "pkg-1.0.0.tar.gz".to_string(),
+                    url: "/files/pkg/pkg-1.0.0.tar.gz".to_string(),
+                    sha256: None,
+                    yanked: Some("broken \"build\"".to_string()),
+                },
+                FileLink {
+                    filename: "pkg-1.0.1.tar.gz".to_string(),
+                    url: "/files/pkg/pkg-1.0.1.tar.gz".to_string(),
+                    sha256: None,
+                    yanked: None,
+                },
+            ],
+        );
+
+        assert!(page.contains(
+            "<a href=\"/files/pkg/pkg-1.0.0.tar.gz\" data-yanked=\"broken &quot;build&quot;\">pkg-1.0.0.tar.gz</a>"
+        ));
+        assert!(page.contains("<a href=\"/files/pkg/pkg-1.0.1.tar.gz\">pkg-1.0.1.tar.gz</a>"));
+    }
+
+    #[test]
+    fn test_parse_project_page() {
+        let base = Url::parse("https://example.com/simple/pkg/").unwrap();
+        let links = parse_project_page(
+            "<a href=\"../../files/pkg-1.0.0.tar.gz#sha256=ABC123\">pkg-1.0.0.tar.gz</a><br/>\n\
+             <A HREF='https://cdn.example.com/pkg-1.0.1.tar.gz' data-yanked=\"bad &amp; broken\">pkg-1.0.1.tar.gz</A>",
+            &base,
+        );
+
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].url, "https://example.com/files/pkg-1.0.0.tar.gz");
+        assert_eq!(links[0].sha256.as_deref(), Some("abc123"));
+        assert_eq!(links[0].yanked, None);
+        assert_eq!(links[1].filename, "pkg-1.0.1.tar.gz");
+        assert_eq!(links[1].sha256, None);
+        assert_eq!(links[1].yanked.as_deref(), Some("bad & broken"));
+    }
+}
@@ -1,12 +1,87 @@
 use crate::storage::object_ref::ObjectRef;
 use bytes::Bytes;
 use std::error::Error;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub(crate) enum StorageDriverError {
+    PresignUnsupported(String),
+}
+
+impl std::fmt::Display for StorageDriverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::PresignUnsupported(protocol) => {
+                write!(f, "{} storage does not support presigned urls", protocol)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StorageDriverError {}
 
 #[async_trait::async_trait]
-pub(crate) trait StorageDriver {
+pub(crate) trait StorageDriver: Send + Sync {
     async fn list(&self, bucket: &str) -> Result<Vec<ObjectRef>, Box<dyn Error>>;
     async fn get(&self, bucket: &str, key: &str) -> Result<Bytes, Box<dyn Error>>;
     async fn put(&self, bucket: &str, key: &str, content: Bytes) -> Result<(), Box<dyn Error>>;
+
+    /// Reads an object along with its revision (an S3 ETag or a GCS generation), or `None` when
+    /// the object doesn't exist.
+    async fn get_revision(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Result<Option<(Bytes, String)>, Box<dyn Error>>;
+
+    /// Writes an object only if it still has the revision returned by `get_revision`, or only if
+    /// it doesn't exist when `revision` is `None`. Returns whether the object was written.
+    async fn put_if_unchanged(
+        &self,
+        bucket: &str,
+        key: &str,
+        content: Bytes,
+        revision: Option<&str>,
+    ) -> Result<bool, Box<dyn Error>>;
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Looks up the size and digest of an object without downloading it.
+    async fn stat(&self, bucket: &str, key: &str) -> Result<ObjectRef, Box<dyn Error>>;
+
+    async fn copy(
+        &self,
+        source_bucket: &str,
+        source_key: &str,
+        bucket: &str,
+        key: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let content = self.get(source_bucket, source_key).await?;
+        self.put(bucket, key, content).await
+    }
+
+    /// Creates a URL that downloads an object without credentials until it expires.
+    async fn presign_get(
+        &self,
+        _bucket: &str,
+        _key: &str,
+        _expires: Duration,
+    ) -> Result<String, Box<dyn Error>> {
+        Result::Err(StorageDriverError::PresignUnsupported(self.get_protocol().to_string()).into())
+    }
+
+    /// Creates a URL that uploads exactly one object without credentials until it expires. The
+    /// upload only succeeds while the object doesn't exist, which requires sending the
+    /// precondition header listed among the URL's signed headers, see `ticket::upload`.
+    async fn presign_put(
+        &self,
+        _bucket: &str,
+        _key: &str,
+        _expires: Duration,
+    ) -> Result<String, Box<dyn Error>> {
+        Result::Err(StorageDriverError::PresignUnsupported(self.get_protocol().to_string()).into())
+    }
+
     fn get_protocol(&self) -> &str;
 
     fn get_object_ref(&self, bucket: &str, key: &str) -> ObjectRef {
@@ -14,6 +89,9 @@ pub(crate) trait StorageDriver {
             bucket: bucket.to_string(),
             key: key.to_string(),
             protocol: self.get_protocol().to_string(),
+            size: None,
+            last_modified: None,
+            md5: None,
         }
     }
 }
@@ -1,52 +1,307 @@
+use crate::config::GcpSettings;
+use crate::digest::sha256;
+use crate::duration::format_duration;
+use crate::gcp::Credentials;
+use crate::http;
 use crate::storage::driver::StorageDriver;
 use crate::storage::object_ref::ObjectRef;
 use bytes::Bytes;
-use google_storage1::api::Object;
-use google_storage1::Storage as Client;
-use hyper::body::HttpBody;
-use hyper::Body;
+use chrono::{DateTime, Utc};
+use hyper::client::HttpConnector;
+use hyper::{header, Body, Client, HeaderMap, Method, Request};
+use hyper_rustls::HttpsConnector;
 use mime::APPLICATION_OCTET_STREAM;
+use serde_derive::Deserialize;
 use std::error::Error;
 use std::fmt::Formatter;
+use std::time::Duration;
+use url::Url;
+
+const DEFAULT_ENDPOINT: &str = "https://storage.googleapis.com";
+const SIGNING_ALGORITHM: &str = "GOOG4-RSA-SHA256";
+const MAX_SIGNED_URL_EXPIRY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
 
 #[derive(Debug)]
 pub(crate) enum GoogleStorageError {
     ErrorAndCode(String, u16),
-    GeneralError(google_storage1::Error),
+    SigningRequiresCredentials,
+    ExpiryTooLong(Duration),
 }
 
 impl std::fmt::Display for GoogleStorageError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::ErrorAndCode(msg, code) => write!(f, "{:?}: Code={} - {}", self, code, msg),
-            Self::GeneralError(e) => write!(f, "{:?}: {}", self, e),
+            Self::SigningRequiresCredentials => {
+                write!(f, "signed urls cannot be created with anonymous access")
+            }
+            Self::ExpiryTooLong(expires) => write!(
+                f,
+                "signed urls expire after at most 7 days: {}",
+                format_duration(*expires)
+            ),
         }
     }
 }
 
 impl std::error::Error for GoogleStorageError {}
 
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorDetail,
+}
+
+#[derive(Deserialize)]
+struct ErrorDetail {
+    message: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Objects {
+    #[serde(default)]
+    items: Vec<Object>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Object {
+    name: String,
+    size: Option<String>,
+    updated: Option<String>,
+    md5_hash: Option<String>,
+}
+
+/// The storage endpoint to use: `STORAGE_EMULATOR_HOST`, then the profile's endpoint.
+pub(crate) fn get_endpoint(settings: &GcpSettings) -> Option<String> {
+    let endpoint = std::env::var("STORAGE_EMULATOR_HOST")
+        .ok()
+        .filter(|x| !x.is_empty())
+        .or_else(|| settings.endpoint.clone())?;
+
+    Some(if endpoint.contains("://") {
+        endpoint.trim_end_matches('/').to_string()
+    } else {
+        format!("http://{}", endpoint.trim_end_matches('/'))
+    })
+}
+
+/// Talks to the Cloud Storage JSON API, or an emulator implementing it. Requests are sent without
+/// an `Authorization` header when no credentials are given.
+///
+/// The driver doesn't use the generated `google-storage1` client because it only accepts a
+/// yup-oauth2 authenticator, which can't be backed by the credentials `gcp` resolves (user
+/// credentials, the metadata server, external accounts and impersonation). All it needs from
+/// them is a bearer token, and for signed urls, a signature.
 pub(crate) struct GoogleStorageDriver {
-    client: Client,
+    client: Client<HttpsConnector<HttpConnector>, Body>,
+    endpoint: String,
+    credentials: Option<Credentials>,
 }
 
 impl GoogleStorageDriver {
-    pub(crate) fn new(client: Client) -> GoogleStorageDriver {
-        GoogleStorageDriver { client }
+    pub(crate) fn new(
+        endpoint: Option<String>,
+        credentials: Option<Credentials>,
+    ) -> GoogleStorageDriver {
+        GoogleStorageDriver {
+            client: http::client(),
+            endpoint: endpoint.unwrap_or_else(|| DEFAULT_ENDPOINT.to_string()),
+            credentials,
+        }
+    }
+
+    fn object_url(&self, bucket: &str, key: &str) -> String {
+        format!(
+            "{}/storage/v1/b/{}/o/{}",
+            self.endpoint,
+            urlencoding::encode(bucket),
+            urlencoding::encode(key)
+        )
+    }
+
+    fn as_object_ref(&self, bucket: &str, object: Object) -> ObjectRef {
+        ObjectRef {
+            size: object.size.and_then(|x| x.parse().ok()),
+            last_modified: object
+                .updated
+                .and_then(|x| DateTime::parse_from_rfc3339(x.as_str()).ok())
+                .map(|x| x.with_timezone(&Utc)),
+            md5: object
+                .md5_hash
+                .and_then(|x| base64::decode(x).ok())
+                .map(|x| x.iter().map(|b| format!("{:02x}", b)).collect()),
+            ..self.get_object_ref(bucket, object.name.as_str())
+        }
     }
+
+    /// Creates a V4 signed url for an object, signed by the service account behind the
+    /// driver's credentials. The `headers`, lowercase and sorted, must be sent with the url.
+    async fn signed_url(
+        &self,
+        method: Method,
+        bucket: &str,
+        key: &str,
+        expires: Duration,
+        headers: &[(&str, &str)],
+    ) -> Result<String, Box<dyn Error>> {
+        let credentials = self
+            .credentials
+            .as_ref()
+            .ok_or(GoogleStorageError::SigningRequiresCredentials)?;
+
+        if expires > MAX_SIGNED_URL_EXPIRY {
+            return Result::Err(GoogleStorageError::ExpiryTooLong(expires).into());
+        }
+
+        let endpoint = Url::parse(&self.endpoint)?;
+        let host = match (endpoint.host_str(), endpoint.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => self.endpoint.clone(),
+        };
+        let path = format!(
+            "/{}/{}",
+            urlencoding::encode(bucket),
+            key.split('/')
+                .map(|x| urlencoding::encode(x).into_owned())
+                .collect::<Vec<String>>()
+                .join("/")
+        );
+        let now = Utc::now();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let scope = format!("{}/auto/storage/goog4_request", now.format("%Y%m%d"));
+        let credential = format!("{}/{}", credentials.service_account().await?, scope);
+        let headers: Vec<(&str, &str)> = [("host", host.as_str())]
+            .iter()
+            .chain(headers.iter())
+            .cloned()
+            .collect();
+        let signed_headers = headers.iter().map(|x| x.0).collect::<Vec<&str>>().join(";");
+        let query = [
+            ("X-Goog-Algorithm", SIGNING_ALGORITHM.to_string()),
+            ("X-Goog-Credential", credential),
+            ("X-Goog-Date", timestamp.clone()),
+            ("X-Goog-Expires", expires.as_secs().to_string()),
+            ("X-Goog-SignedHeaders", signed_headers.clone()),
+        ]
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, urlencoding::encode(value)))
+        .collect::<Vec<String>>()
+        .join("&");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value))
+            .collect();
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\nUNSIGNED-PAYLOAD",
+            method, path, query, canonical_headers, signed_headers
+        );
+        let string_to_sign = format!(
+            "{}\n{}\n{}\n{}",
+            SIGNING_ALGORITHM,
+            timestamp,
+            scope,
+            sha256(canonical_request.as_bytes())
+        );
+        let signature = credentials.sign(string_to_sign.as_bytes()).await?;
+
+        Result::Ok(format!(
+            "{}{}?{}&X-Goog-Signature={}",
+            self.endpoint,
+            path,
+            query,
+            signature
+                .iter()
+                .map(|x| format!("{:02x}", x))
+                .collect::<String>()
+        ))
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        url: String,
+        content: Option<Bytes>,
+    ) -> Result<Bytes, Box<dyn Error>> {
+        Result::Ok(self.send_with_headers(method, url, content).await?.1)
+    }
+
+    async fn send_with_headers(
+        &self,
+        method: Method,
+        url: String,
+        content: Option<Bytes>,
+    ) -> Result<(HeaderMap, Bytes), Box<dyn Error>> {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(url)
+            .header(header::USER_AGENT, http::USER_AGENT);
+
+        if let Some(credentials) = &self.credentials {
+            let token = credentials.token().await?;
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+
+        if content.is_some() {
+            request = request.header(header::CONTENT_TYPE, APPLICATION_OCTET_STREAM.as_ref());
+        }
+
+        let request = request.body(content.map(Body::from).unwrap_or_else(Body::empty))?;
+        let response = self.client.request(request).await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+
+        if !status.is_success() {
+            let message = match serde_json::from_slice::<ErrorResponse>(&body) {
+                Result::Ok(response) => response.error.message,
+                Result::Err(_) => String::from_utf8_lossy(&body).trim().to_string(),
+            };
+
+            return Result::Err(GoogleStorageError::ErrorAndCode(message, status.as_u16()).into());
+        }
+
+        Result::Ok((headers, body))
+    }
+}
+
+fn has_code(e: &(dyn Error + 'static), expected: u16) -> bool {
+    matches!(
+        e.downcast_ref::<GoogleStorageError>(),
+        Some(GoogleStorageError::ErrorAndCode(_, code)) if *code == expected
+    )
 }
 
 #[async_trait::async_trait]
 impl StorageDriver for GoogleStorageDriver {
     async fn list(&self, bucket: &str) -> Result<Vec<ObjectRef>, Box<dyn Error>> {
-        let (_, objects) = handle_error(self.client.objects().list(bucket).doit().await)?;
         let mut result: Vec<ObjectRef> = Vec::new();
+        let mut page_token: Option<String> = None;
 
-        if let Some(items) = objects.items {
-            for object in items {
-                if let Some(name) = object.name {
-                    result.push(self.get_object_ref(bucket, name.as_str()));
-                }
+        loop {
+            let mut url = format!(
+                "{}/storage/v1/b/{}/o?fields=items(name,size,updated,md5Hash),nextPageToken",
+                self.endpoint,
+                urlencoding::encode(bucket)
+            );
+
+            if let Some(token) = &page_token {
+                url.push_str(&format!("&pageToken={}", urlencoding::encode(token)));
+            }
+
+            let body = self.send(Method::GET, url, None).await?;
+            let objects: Objects = serde_json::from_slice(&body)?;
+
+            for object in objects.items {
+                result.push(self.as_object_ref(bucket, object));
+            }
+
+            page_token = objects.next_page_token;
+
+            if page_token.is_none() {
+                break;
             }
         }
 
@@ -54,55 +309,413 @@ impl StorageDriver for GoogleStorageDriver {
     }
 
     async fn get(&self, bucket: &str, key: &str) -> Result<Bytes, Box<dyn Error>> {
-        let object = urlencoding::encode(key).into_owned();
-        let (mut response, _) = handle_error(
-            self.client
-                .objects()
-                .get(bucket, object.as_str())
-                .param("alt", "media")
-                .doit()
-                .await,
-        )?;
-        let body: &mut Body = response.body_mut();
-        let mut buffer: Vec<u8> = Vec::new();
-
-        while let Some(data) = body.data().await {
-            if let Ok(content) = data {
-                buffer.append(&mut content.to_vec());
-            } else {
-                return Err(data.err().unwrap().into());
+        self.send(
+            Method::GET,
+            format!("{}?alt=media", self.object_url(bucket, key)),
+            None,
+        )
+        .await
+    }
+
+    async fn put(&self, bucket: &str, key: &str, content: Bytes) -> Result<(), Box<dyn Error>> {
+        let url = format!(
+            "{}/upload/storage/v1/b/{}/o?uploadType=media&name={}",
+            self.endpoint,
+            urlencoding::encode(bucket),
+            urlencoding::encode(key)
+        );
+
+        self.send(Method::POST, url, Some(content)).await?;
+
+        Result::Ok(())
+    }
+
+    async fn get_revision(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Result<Option<(Bytes, String)>, Box<dyn Error>> {
+        let url = format!("{}?alt=media", self.object_url(bucket, key));
+
+        match self.send_with_headers(Method::GET, url, None).await {
+            Result::Ok((headers, body)) => {
+                let generation = headers
+                    .get("x-goog-generation")
+                    .and_then(|x| x.to_str().ok())
+                    .ok_or_else(|| format!("no generation returned for {}", key))?;
+
+                Result::Ok(Some((body, generation.to_string())))
             }
+            Result::Err(e) if has_code(e.as_ref(), 404) => Result::Ok(None),
+            Result::Err(e) => Result::Err(e),
         }
+    }
+
+    async fn put_if_unchanged(
+        &self,
+        bucket: &str,
+        key: &str,
+        content: Bytes,
+        revision: Option<&str>,
+    ) -> Result<bool, Box<dyn Error>> {
+        // A generation of 0 only matches when the object doesn't exist yet
+        let url = format!(
+            "{}/upload/storage/v1/b/{}/o?uploadType=media&name={}&ifGenerationMatch={}",
+            self.endpoint,
+            urlencoding::encode(bucket),
+            urlencoding::encode(key),
+            urlencoding::encode(revision.unwrap_or("0"))
+        );
 
-        Ok(Bytes::from(buffer))
+        match self.send(Method::POST, url, Some(content)).await {
+            Result::Ok(_) => Result::Ok(true),
+            Result::Err(e) if has_code(e.as_ref(), 412) => Result::Ok(false),
+            Result::Err(e) => Result::Err(e),
+        }
     }
 
-    async fn put(&self, bucket: &str, key: &str, content: Bytes) -> Result<(), Box<dyn Error>> {
-        let mime = APPLICATION_OCTET_STREAM.to_string().parse().unwrap();
-        let reader = std::io::Cursor::new(content);
-        let req = {
-            let call = self.client.objects().insert(Object::default(), bucket);
-            call.name(key)
-        };
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), Box<dyn Error>> {
+        self.send(Method::DELETE, self.object_url(bucket, key), None)
+            .await?;
+
+        Result::Ok(())
+    }
 
-        handle_error(req.upload(reader, mime).await)?;
+    async fn stat(&self, bucket: &str, key: &str) -> Result<ObjectRef, Box<dyn Error>> {
+        let url = format!(
+            "{}?fields=name,size,updated,md5Hash",
+            self.object_url(bucket, key)
+        );
+        let body = self.send(Method::GET, url, None).await?;
+
+        Result::Ok(self.as_object_ref(bucket, serde_json::from_slice(&body)?))
+    }
+
+    async fn copy(
+        &self,
+        source_bucket: &str,
+        source_key: &str,
+        bucket: &str,
+        key: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let url = format!(
+            "{}/copyTo/b/{}/o/{}",
+            self.object_url(source_bucket, source_key),
+            urlencoding::encode(bucket),
+            urlencoding::encode(key)
+        );
+
+        self.send(Method::POST, url, None).await?;
 
         Result::Ok(())
     }
 
+    async fn presign_get(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires: Duration,
+    ) -> Result<String, Box<dyn Error>> {
+        self.signed_url(Method::GET, bucket, key, expires, &[])
+            .await
+    }
+
+    async fn presign_put(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires: Duration,
+    ) -> Result<String, Box<dyn Error>> {
+        // Signed, so the url only uploads when nothing exists at the key yet
+        let headers = [("x-goog-if-generation-match", "0")];
+
+        self.signed_url(Method::PUT, bucket, key, expires, &headers)
+            .await
+    }
+
     fn get_protocol(&self) -> &str {
         "gs"
     }
 }
 
-fn handle_error<T>(result: google_storage1::Result<T>) -> Result<T, GoogleStorageError> {
-    match result {
-        Ok(x) => Ok(x),
-        Err(e) => Err(match e {
-            google_storage1::Error::BadRequest(response) => {
-                GoogleStorageError::ErrorAndCode(response.error.message, response.error.code)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Response, Server, StatusCode};
+    use std::collections::BTreeMap;
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+
+    type Objects = Arc<Mutex<BTreeMap<String, (Bytes, u64)>>>;
+
+    const PAGE_SIZE: usize = 2;
+
+    /// A stand-in for fake-gcs-server implementing the parts of the JSON API the driver uses.
+    async fn fake_gcs(request: Request<Body>, objects: Objects) -> Response<Body> {
+        let respond = |status: StatusCode, body: String| {
+            Response::builder()
+                .status(status)
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        if request.headers().contains_key(header::AUTHORIZATION) {
+            return respond(StatusCode::UNAUTHORIZED, "unexpected credentials".into());
+        }
+
+        let query: BTreeMap<String, String> = request
+            .uri()
+            .query()
+            .map(|x| {
+                url::form_urlencoded::parse(x.as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        let path: Vec<String> = request
+            .uri()
+            .path()
+            .split('/')
+            .map(|x| urlencoding::decode(x).unwrap().into_owned())
+            .collect();
+        let path: Vec<&str> = path.iter().map(|x| x.as_str()).collect();
+        let method = request.method().clone();
+        let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+        let mut objects = objects.lock().unwrap();
+        let not_found = || {
+            respond(
+                StatusCode::NOT_FOUND,
+                r#"{"error": {"code": 404, "message": "Not Found"}}"#.into(),
+            )
+        };
+
+        match (method, &path[1..]) {
+            (Method::GET, ["storage", "v1", "b", "bucket", "o"]) => {
+                let start = query
+                    .get("pageToken")
+                    .map(|x| x.parse().unwrap())
+                    .unwrap_or(0);
+                let items: Vec<serde_json::Value> = objects
+                    .iter()
+                    .skip(start)
+                    .take(PAGE_SIZE)
+                    .map(|(name, (content, _))| {
+                        serde_json::json!({
+                            "name": name,
+                            "size": content.len().to_string(),
+                            "updated": "2022-03-01T12:00:00.000Z",
+                        })
+                    })
+                    .collect();
+                let next = if start + PAGE_SIZE < objects.len() {
+                    Some((start + PAGE_SIZE).to_string())
+                } else {
+                    None
+                };
+
+                respond(
+                    StatusCode::OK,
+                    serde_json::json!({"items": items, "nextPageToken": next}).to_string(),
+                )
             }
-            e => GoogleStorageError::GeneralError(e),
-        }),
+            (Method::GET, ["storage", "v1", "b", "bucket", "o", key]) => match objects.get(*key) {
+                Some((content, generation))
+                    if query.get("alt").map(|x| x == "media") == Some(true) =>
+                {
+                    Response::builder()
+                        .header("x-goog-generation", generation.to_string())
+                        .body(Body::from(content.clone()))
+                        .unwrap()
+                }
+                Some((content, _)) => respond(
+                    StatusCode::OK,
+                    serde_json::json!({
+                        "name": key,
+                        "size": content.len().to_string(),
+                        "updated": "2022-03-01T12:00:00.000Z",
+                        "md5Hash": base64::encode(md5::compute(content).0),
+                    })
+                    .to_string(),
+                ),
+                None => not_found(),
+            },
+            (Method::POST, ["upload", "storage", "v1", "b", "bucket", "o"]) => {
+                let current = objects.get(&query["name"]).map(|x| x.1).unwrap_or(0);
+
+                if let Some(expected) = query.get("ifGenerationMatch") {
+                    if expected.parse::<u64>().unwrap() != current {
+                        return respond(
+                            StatusCode::PRECONDITION_FAILED,
+                            r#"{"error": {"code": 412, "message": "Precondition Failed"}}"#.into(),
+                        );
+                    }
+                }
+
+                let generation = objects.values().map(|x| x.1).max().unwrap_or(0) + 1;
+                objects.insert(query["name"].clone(), (body, generation));
+                respond(StatusCode::OK, "{}".into())
+            }
+            (Method::DELETE, ["storage", "v1", "b", "bucket", "o", key]) => {
+                match objects.remove(*key) {
+                    Some(_) => respond(StatusCode::NO_CONTENT, String::new()),
+                    None => not_found(),
+                }
+            }
+            (
+                Method::POST,
+                ["storage", "v1", "b", "bucket", "o", source, "copyTo", "b", "bucket", "o", key],
+            ) => match objects.get(*source).cloned() {
+                Some((content, _)) => {
+                    let generation = objects.values().map(|x| x.1).max().unwrap_or(0) + 1;
+                    objects.insert(key.to_string(), (content, generation));
+                    respond(StatusCode::OK, "{}".into())
+                }
+                None => not_found(),
+            },
+            _ => not_found(),
+        }
+    }
+
+    async fn start() -> (GoogleStorageDriver, Objects) {
+        let objects: Objects = Arc::new(Mutex::new(BTreeMap::new()));
+        let state = objects.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(fake_gcs(request, state).await) }
+                }))
+            }
+        });
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let endpoint = format!("http://{}", server.local_addr());
+
+        tokio::spawn(server);
+
+        (GoogleStorageDriver::new(Some(endpoint), None), objects)
+    }
+
+    #[tokio::test]
+    async fn test_put_get_list() {
+        let (driver, objects) = start().await;
+
+        for key in [
+            "pkg/pkg-0.1.0.tar.gz",
+            "pkg/pkg-0.2.0.tar.gz",
+            "other/other-1.0.0.tar.gz",
+        ] {
+            driver
+                .put("bucket", key, Bytes::from(key.to_string()))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(objects.lock().unwrap().len(), 3);
+        assert_eq!(
+            driver.get("bucket", "pkg/pkg-0.2.0.tar.gz").await.unwrap(),
+            Bytes::from("pkg/pkg-0.2.0.tar.gz")
+        );
+
+        let listed = driver.list("bucket").await.unwrap();
+        assert_eq!(
+            listed.iter().map(|x| x.key.as_str()).collect::<Vec<&str>>(),
+            vec![
+                "other/other-1.0.0.tar.gz",
+                "pkg/pkg-0.1.0.tar.gz",
+                "pkg/pkg-0.2.0.tar.gz"
+            ]
+        );
+        assert_eq!(listed[1].size, Some(20));
+        assert!(listed[1].last_modified.is_some());
+        assert_eq!(
+            listed[1].get_object_url(),
+            "gs://bucket/pkg/pkg-0.1.0.tar.gz"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_copy_delete() {
+        let (driver, objects) = start().await;
+
+        driver
+            .put("bucket", "pkg/pkg-0.1.0.tar.gz", Bytes::from("content"))
+            .await
+            .unwrap();
+        driver
+            .copy(
+                "bucket",
+                "pkg/pkg-0.1.0.tar.gz",
+                "bucket",
+                "copy/pkg-0.1.0.tar.gz",
+            )
+            .await
+            .unwrap();
+        driver
+            .delete("bucket", "pkg/pkg-0.1.0.tar.gz")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            objects.lock().unwrap().keys().collect::<Vec<&String>>(),
+            vec!["copy/pkg-0.1.0.tar.gz"]
+        );
+
+        let copied = driver
+            .stat("bucket", "copy/pkg-0.1.0.tar.gz")
+            .await
+            .unwrap();
+        assert_eq!(copied.size, Some(7));
+        assert_eq!(copied.md5.unwrap(), crate::digest::md5(b"content"));
+
+        let error = driver
+            .get("bucket", "pkg/pkg-0.1.0.tar.gz")
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Code=404 - Not Found"));
+    }
+
+    #[tokio::test]
+    async fn test_put_if_unchanged() {
+        let (driver, _) = start().await;
+        let key = ".sling/metadata.json";
+
+        assert!(driver.get_revision("bucket", key).await.unwrap().is_none());
+        assert!(driver
+            .put_if_unchanged("bucket", key, Bytes::from("first"), None)
+            .await
+            .unwrap());
+        assert!(!driver
+            .put_if_unchanged("bucket", key, Bytes::from("again"), None)
+            .await
+            .unwrap());
+
+        let (content, revision) = driver.get_revision("bucket", key).await.unwrap().unwrap();
+        assert_eq!(content, Bytes::from("first"));
+
+        driver
+            .put("bucket", key, Bytes::from("concurrent"))
+            .await
+            .unwrap();
+        assert!(!driver
+            .put_if_unchanged("bucket", key, Bytes::from("stale"), Some(revision.as_str()))
+            .await
+            .unwrap());
+
+        let (_, revision) = driver.get_revision("bucket", key).await.unwrap().unwrap();
+        assert!(driver
+            .put_if_unchanged(
+                "bucket",
+                key,
+                Bytes::from("second"),
+                Some(revision.as_str())
+            )
+            .await
+            .unwrap());
+        assert_eq!(
+            driver.get("bucket", key).await.unwrap(),
+            Bytes::from("second")
+        );
     }
 }
@@ -0,0 +1,87 @@
+use crate::config::{Config, Profile};
+use crate::storage::AvailableStorageDrivers;
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub(crate) enum StorageLocationError {
+    InvalidUrl(String),
+    ProfileNotFound(String),
+    IncompleteProfile(String),
+}
+
+impl std::fmt::Display for StorageLocationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::InvalidUrl(url) => write!(
+                f,
+                "invalid storage location (expected s3://BUCKET or gs://BUCKET): {}",
+                url
+            ),
+            Self::ProfileNotFound(name) => write!(f, "profile not found: {}", name),
+            Self::IncompleteProfile(name) => {
+                write!(
+                    f,
+                    "profile must define both a driver and a bucket: {}",
+                    name
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for StorageLocationError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StorageLocation {
+    pub(crate) driver: AvailableStorageDrivers,
+    pub(crate) bucket: String,
+    /// The profile the location was resolved from, whose credentials should be used for it.
+    pub(crate) profile: Option<Profile>,
+}
+
+impl StorageLocation {
+    /// Resolves either a `PROTOCOL://BUCKET` url or the name of a configured profile.
+    pub(crate) fn resolve(value: &str, config: &Config) -> Result<Self, StorageLocationError> {
+        if let Some((protocol, bucket)) = value.split_once("://") {
+            let driver = AvailableStorageDrivers::from_str(protocol)
+                .map_err(|_| StorageLocationError::InvalidUrl(value.to_string()))?;
+            let bucket = bucket.trim_end_matches('/');
+
+            if bucket.is_empty() || bucket.contains('/') {
+                return Result::Err(StorageLocationError::InvalidUrl(value.to_string()));
+            }
+
+            return Result::Ok(StorageLocation {
+                driver,
+                bucket: bucket.to_string(),
+                profile: None,
+            });
+        }
+
+        let profile = config
+            .profiles
+            .get(value)
+            .ok_or_else(|| StorageLocationError::ProfileNotFound(value.to_string()))?;
+
+        match (
+            profile
+                .driver
+                .as_ref()
+                .and_then(|x| AvailableStorageDrivers::from_str(x).ok()),
+            profile.bucket.clone(),
+        ) {
+            (Some(driver), Some(bucket)) => Result::Ok(StorageLocation {
+                driver,
+                bucket,
+                profile: Some(profile.clone()),
+            }),
+            _ => Result::Err(StorageLocationError::IncompleteProfile(value.to_string())),
+        }
+    }
+}
+
+impl std::fmt::Display for StorageLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}://{}", self.driver.get_protocol(), self.bucket)
+    }
+}
@@ -0,0 +1,153 @@
+use crate::digest;
+use crate::storage::driver::StorageDriver;
+use crate::storage::object_ref::ObjectRef;
+use bytes::Bytes;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Keeps every bucket in memory, for testing commands without a storage service. Objects carry
+/// a generation that changes on every write, like GCS generations.
+#[derive(Default)]
+pub(crate) struct MemoryStorageDriver {
+    objects: Mutex<BTreeMap<(String, String), (Bytes, u64)>>,
+    generation: AtomicU64,
+    failing: Mutex<HashSet<String>>,
+}
+
+impl MemoryStorageDriver {
+    pub(crate) fn insert(&self, bucket: &str, key: &str, content: &str) {
+        self.write(bucket, key, Bytes::from(content.to_string()));
+    }
+
+    pub(crate) fn keys(&self, bucket: &str) -> Vec<String> {
+        self.objects
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|x| x.0 == bucket)
+            .map(|x| x.1.clone())
+            .collect()
+    }
+
+    /// Makes every later `put` of the key fail, like a dropped connection would.
+    pub(crate) fn fail_put(&self, key: &str) {
+        self.failing.lock().unwrap().insert(key.to_string());
+    }
+
+    fn write(&self, bucket: &str, key: &str, content: Bytes) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+
+        self.objects
+            .lock()
+            .unwrap()
+            .insert((bucket.to_string(), key.to_string()), (content, generation));
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageDriver for MemoryStorageDriver {
+    async fn list(&self, bucket: &str) -> Result<Vec<ObjectRef>, Box<dyn Error>> {
+        Result::Ok(
+            self.objects
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|x| x.0 .0 == bucket)
+                .map(|((bucket, key), (content, _))| ObjectRef {
+                    size: Some(content.len() as u64),
+                    ..self.get_object_ref(bucket, key)
+                })
+                .collect(),
+        )
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Bytes, Box<dyn Error>> {
+        self.get_revision(bucket, key)
+            .await?
+            .map(|x| x.0)
+            .ok_or_else(|| format!("not found: {}", key).into())
+    }
+
+    async fn put(&self, bucket: &str, key: &str, content: Bytes) -> Result<(), Box<dyn Error>> {
+        if self.failing.lock().unwrap().contains(key) {
+            return Result::Err(format!("connection reset: {}", key).into());
+        }
+
+        self.write(bucket, key, content);
+
+        Result::Ok(())
+    }
+
+    async fn get_revision(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Result<Option<(Bytes, String)>, Box<dyn Error>> {
+        Result::Ok(
+            self.objects
+                .lock()
+                .unwrap()
+                .get(&(bucket.to_string(), key.to_string()))
+                .map(|(content, generation)| (content.clone(), generation.to_string())),
+        )
+    }
+
+    async fn put_if_unchanged(
+        &self,
+        bucket: &str,
+        key: &str,
+        content: Bytes,
+        revision: Option<&str>,
+    ) -> Result<bool, Box<dyn Error>> {
+        let mut objects = self.objects.lock().unwrap();
+        let id = (bucket.to_string(), key.to_string());
+
+        if objects.get(&id).map(|x| x.1.to_string()).as_deref() != revision {
+            return Result::Ok(false);
+        }
+
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        objects.insert(id, (content, generation));
+
+        Result::Ok(true)
+    }
+
+    async fn stat(&self, bucket: &str, key: &str) -> Result<ObjectRef, Box<dyn Error>> {
+        let content = self.get(bucket, key).await?;
+
+        Result::Ok(ObjectRef {
+            size: Some(content.len() as u64),
+            md5: Some(digest::md5(&content)),
+            ..self.get_object_ref(bucket, key)
+        })
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), Box<dyn Error>> {
+        self.objects
+            .lock()
+            .unwrap()
+            .remove(&(bucket.to_string(), key.to_string()))
+            .map(|_| ())
+            .ok_or_else(|| format!("not found: {}", key).into())
+    }
+
+    async fn presign_get(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires: Duration,
+    ) -> Result<String, Box<dyn Error>> {
+        Result::Ok(format!(
+            "{}?expires={}",
+            self.get_object_ref(bucket, key).get_object_url(),
+            expires.as_secs()
+        ))
+    }
+
+    fn get_protocol(&self) -> &str {
+        "memory"
+    }
+}
@@ -1,4 +1,78 @@
 pub(crate) mod driver;
 pub(crate) mod gs;
+pub(crate) mod location;
+#[cfg(test)]
+pub(crate) mod memory;
 pub(crate) mod object_ref;
 pub(crate) mod s3;
+
+use crate::config::Profile;
+use crate::storage::driver::StorageDriver;
+use crate::storage::gs::GoogleStorageDriver;
+use crate::storage::s3::S3StorageDriver;
+use crate::{aws, gcp};
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+#[derive(Clone, Debug)]
+pub(crate) enum StorageDriverParseError {
+    InvalidStorageDriver,
+}
+
+impl std::error::Error for StorageDriverParseError {}
+
+impl std::fmt::Display for StorageDriverParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum AvailableStorageDrivers {
+    GS,
+    S3,
+}
+
+impl AvailableStorageDrivers {
+    pub(crate) fn get_protocol(&self) -> &str {
+        match self {
+            Self::GS => "gs",
+            Self::S3 => "s3",
+        }
+    }
+}
+
+impl FromStr for AvailableStorageDrivers {
+    type Err = StorageDriverParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "GS" => Ok(AvailableStorageDrivers::GS),
+            "S3" => Ok(AvailableStorageDrivers::S3),
+            _ => Err(StorageDriverParseError::InvalidStorageDriver),
+        }
+    }
+}
+
+pub(crate) async fn create_driver(
+    driver: &AvailableStorageDrivers,
+    profile: &Profile,
+) -> Result<Box<dyn StorageDriver>, String> {
+    Result::Ok(match driver {
+        AvailableStorageDrivers::GS => {
+            let settings = profile.gcp.clone().unwrap_or_default();
+
+            Box::new(GoogleStorageDriver::new(
+                gs::get_endpoint(&settings),
+                gcp::get_credentials(&settings)
+                    .await
+                    .map_err(|e| format!("failed to initialize gs driver: {}", e))?,
+            ))
+        }
+        AvailableStorageDrivers::S3 => Box::new(S3StorageDriver::new(
+            aws::get_s3_client(&profile.aws.clone().unwrap_or_default())
+                .await
+                .map_err(|e| format!("failed to initialize aws driver: {}", e))?,
+        )),
+    })
+}
@@ -1,12 +1,22 @@
+use chrono::{DateTime, Utc};
+
 #[derive(Clone, Debug)]
 pub(crate) struct ObjectRef {
     pub(crate) bucket: String,
     pub(crate) key: String,
     pub(crate) protocol: String,
+    pub(crate) size: Option<u64>,
+    pub(crate) last_modified: Option<DateTime<Utc>>,
+    /// The md5 digest of the content in hex, when the provider reports one.
+    pub(crate) md5: Option<String>,
 }
 
 impl ObjectRef {
     pub(crate) fn get_object_url(&self) -> String {
         format!("{}://{}/{}", self.protocol, self.bucket, self.key)
     }
+
+    pub(crate) fn filename(&self) -> &str {
+        self.key.rsplit('/').next().unwrap_or_default()
+    }
 }
@@ -1,30 +1,101 @@
 use crate::storage::driver::StorageDriver;
 use crate::storage::object_ref::ObjectRef;
-use aws_sdk_s3::types::ByteStream;
+use aws_sdk_s3::input::PutObjectInput;
+use aws_sdk_s3::middleware::DefaultMiddleware;
+use aws_sdk_s3::model::ServerSideEncryption;
+use aws_sdk_s3::presigning::config::PresigningConfig;
+use aws_sdk_s3::types::{ByteStream, SdkError};
 use aws_sdk_s3::Client;
+use aws_sig_auth::signer::{HttpSignatureType, OperationSigningConfig};
+use aws_sigv4::http_request::SignableBody;
+use aws_smithy_client::erase::{DynConnector, DynMiddleware};
+use aws_smithy_http::body::SdkBody;
+use aws_smithy_http::operation::{self, Operation};
+use aws_smithy_http_tower::SendOperationError;
 use bytes::Bytes;
+use chrono::{TimeZone, Utc};
+use hyper::header::{HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, IF_MATCH, IF_NONE_MATCH};
+use hyper::StatusCode;
 use std::error::Error;
+use std::future::{ready, Ready};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower::{Layer, ServiceExt};
 
 pub(crate) struct S3StorageDriver {
     client: Client,
+    /// Sends operations the fluent client can't express, like puts with precondition headers,
+    /// through the same signing middleware.
+    raw: aws_smithy_client::Client<DynConnector, DynMiddleware<DynConnector>>,
 }
 
 impl S3StorageDriver {
     pub(crate) fn new(client: Client) -> S3StorageDriver {
-        S3StorageDriver { client }
+        let raw = aws_smithy_client::Builder::dyn_https()
+            .middleware(DynMiddleware::new(DefaultMiddleware::new()))
+            .sleep_impl(aws_smithy_async::rt::sleep::default_async_sleep())
+            .build();
+
+        S3StorageDriver { client, raw }
+    }
+}
+
+/// Ends the middleware stack after signing, returning the request instead of sending it.
+#[derive(Clone)]
+struct SignedRequest;
+
+impl tower::Service<operation::Request> for SignedRequest {
+    type Response = hyper::Request<SdkBody>;
+    type Error = SendOperationError;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Result::Ok(()))
+    }
+
+    fn call(&mut self, request: operation::Request) -> Self::Future {
+        ready(Result::Ok(request.into_parts().0))
     }
 }
 
 #[async_trait::async_trait]
 impl StorageDriver for S3StorageDriver {
     async fn list(&self, bucket: &str) -> Result<Vec<ObjectRef>, Box<dyn Error>> {
-        let response = self.client.list_objects_v2().bucket(bucket).send().await?;
-        Ok(response
-            .contents()
-            .unwrap_or_default()
-            .iter()
-            .filter_map(|o| o.key().map(|s| self.get_object_ref(bucket, s)))
-            .collect())
+        let mut result: Vec<ObjectRef> = Vec::new();
+        let mut continuation_token: Option<String> = None;
+
+        loop {
+            let mut request = self.client.list_objects_v2().bucket(bucket);
+
+            if let Some(token) = &continuation_token {
+                request = request.continuation_token(token);
+            }
+
+            let response = request.send().await?;
+
+            for object in response.contents().unwrap_or_default() {
+                if let Some(key) = object.key() {
+                    result.push(ObjectRef {
+                        size: u64::try_from(object.size()).ok(),
+                        last_modified: object
+                            .last_modified()
+                            .and_then(|t| Utc.timestamp_opt(t.secs(), t.subsec_nanos()).single()),
+                        ..self.get_object_ref(bucket, key)
+                    });
+                }
+            }
+
+            continuation_token = match response.next_continuation_token() {
+                Some(token) if response.is_truncated() => Some(token.to_string()),
+                _ => None,
+            };
+
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(result)
     }
 
     async fn get(&self, bucket: &str, key: &str) -> Result<Bytes, Box<dyn Error>> {
@@ -51,7 +122,220 @@ impl StorageDriver for S3StorageDriver {
         Result::Ok(())
     }
 
+    async fn get_revision(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Result<Option<(Bytes, String)>, Box<dyn Error>> {
+        let response = match self
+            .client
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Result::Ok(response) => response,
+            Result::Err(SdkError::ServiceError { err, .. }) if err.is_no_such_key() => {
+                return Result::Ok(None)
+            }
+            Result::Err(e) => return Result::Err(e.into()),
+        };
+        let e_tag = response
+            .e_tag()
+            .map(|x| x.to_string())
+            .ok_or_else(|| format!("no etag returned for {}", key))?;
+        let data = response.body.collect().await?;
+
+        Result::Ok(Some((data.into_bytes(), e_tag)))
+    }
+
+    async fn put_if_unchanged(
+        &self,
+        bucket: &str,
+        key: &str,
+        content: Bytes,
+        revision: Option<&str>,
+    ) -> Result<bool, Box<dyn Error>> {
+        let operation = PutObjectInput::builder()
+            .bucket(bucket)
+            .key(key)
+            .body(ByteStream::from(content))
+            .build()?
+            .make_operation(self.client.conf())
+            .await?;
+        let (mut request, parts) = operation.into_request_response();
+        let headers = request.http_mut().headers_mut();
+
+        match revision {
+            Some(e_tag) => headers.insert(IF_MATCH, HeaderValue::from_str(e_tag)?),
+            None => headers.insert(IF_NONE_MATCH, HeaderValue::from_static("*")),
+        };
+
+        match self.raw.call(Operation::from_parts(request, parts)).await {
+            Result::Ok(_) => Result::Ok(true),
+            // S3 answers 409 when a concurrent conditional write to the same key wins
+            Result::Err(SdkError::ServiceError { raw, .. })
+                if [StatusCode::PRECONDITION_FAILED, StatusCode::CONFLICT]
+                    .contains(&raw.http().status()) =>
+            {
+                Result::Ok(false)
+            }
+            Result::Err(e) => Result::Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), Box<dyn Error>> {
+        self.client
+            .delete_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await?;
+
+        Result::Ok(())
+    }
+
+    async fn stat(&self, bucket: &str, key: &str) -> Result<ObjectRef, Box<dyn Error>> {
+        let response = self
+            .client
+            .head_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await?;
+        // The ETag is only the md5 digest for objects uploaded in one part without SSE-KMS
+        let md5 = match response.server_side_encryption() {
+            Some(ServerSideEncryption::AwsKms) => None,
+            _ => response
+                .e_tag()
+                .map(|x| x.trim_matches('"').to_lowercase())
+                .filter(|x| x.len() == 32 && x.chars().all(|c| c.is_ascii_hexdigit())),
+        };
+
+        Result::Ok(ObjectRef {
+            size: u64::try_from(response.content_length()).ok(),
+            last_modified: response
+                .last_modified()
+                .and_then(|t| Utc.timestamp_opt(t.secs(), t.subsec_nanos()).single()),
+            md5,
+            ..self.get_object_ref(bucket, key)
+        })
+    }
+
+    async fn copy(
+        &self,
+        source_bucket: &str,
+        source_key: &str,
+        bucket: &str,
+        key: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let source_key: Vec<String> = source_key
+            .split('/')
+            .map(|x| urlencoding::encode(x).into_owned())
+            .collect();
+
+        self.client
+            .copy_object()
+            .copy_source(format!("{}/{}", source_bucket, source_key.join("/")))
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await?;
+
+        Result::Ok(())
+    }
+
+    async fn presign_get(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires: Duration,
+    ) -> Result<String, Box<dyn Error>> {
+        let request = self
+            .client
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .presigned(PresigningConfig::expires_in(expires)?)
+            .await?;
+
+        Result::Ok(request.uri().to_string())
+    }
+
+    async fn presign_put(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires: Duration,
+    ) -> Result<String, Box<dyn Error>> {
+        // The fluent presigner can't add headers, so the request is signed like it would do
+        let presigning = PresigningConfig::expires_in(expires)?;
+        let (mut request, _) = PutObjectInput::builder()
+            .bucket(bucket)
+            .key(key)
+            .build()?
+            .make_operation(self.client.conf())
+            .await?
+            .into_request_response();
+        let headers = request.http_mut().headers_mut();
+
+        headers.remove(CONTENT_LENGTH);
+        headers.remove(CONTENT_TYPE);
+        headers.insert(IF_NONE_MATCH, HeaderValue::from_static("*"));
+
+        {
+            let mut properties = request.properties_mut();
+            properties.insert(presigning.start_time());
+            properties.insert(SignableBody::UnsignedPayload);
+
+            let signing = properties
+                .get_mut::<OperationSigningConfig>()
+                .ok_or("put operation has no signing config")?;
+            signing.signature_type = HttpSignatureType::HttpRequestQueryParams;
+            signing.expires_in = Some(presigning.expires());
+        }
+
+        let signed = DefaultMiddleware::new()
+            .layer(SignedRequest)
+            .oneshot(request)
+            .await
+            .map_err(|e| format!("failed to sign upload url: {:?}", e))?;
+
+        Result::Ok(signed.uri().to_string())
+    }
+
     fn get_protocol(&self) -> &str {
         "s3"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_s3::{Config, Credentials, Region};
+    use std::collections::BTreeMap;
+
+    #[tokio::test]
+    async fn test_presign_put() {
+        let config = Config::builder()
+            .region(Region::new("us-west-2"))
+            .credentials_provider(Credentials::new("AKID", "SECRET", None, None, "test"))
+            .build();
+        let driver = S3StorageDriver::new(Client::from_conf(config));
+        let url = driver
+            .presign_put("bucket", "pkg/pkg-1.0.0.tar.gz", Duration::from_secs(600))
+            .await
+            .unwrap();
+        let url = url::Url::parse(&url).unwrap();
+        let query: BTreeMap<String, String> = url.query_pairs().into_owned().collect();
+
+        assert_eq!(url.path(), "/bucket/pkg/pkg-1.0.0.tar.gz");
+        assert_eq!(query["X-Amz-Expires"], "600");
+        assert!(query.contains_key("X-Amz-Signature"));
+        assert!(query["X-Amz-SignedHeaders"]
+            .split(';')
+            .any(|x| x == "if-none-match"));
+        assert!(!query["X-Amz-SignedHeaders"].contains("content-length"));
+    }
+}
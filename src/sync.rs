@@ -0,0 +1,512 @@
+use crate::bucket_metadata::{BucketMetadata, LATEST_TAG};
+use crate::index::{Entry, Index};
+use crate::pattern::glob_to_regex;
+use crate::promote::{copy_entry, copy_metadata};
+use crate::publish;
+use crate::storage::location::StorageLocation;
+use crate::storage::object_ref::ObjectRef;
+use crate::version_requirement::VersionRequirement;
+use crate::StorageDriver;
+use futures::stream::{self, StreamExt};
+use regex::Regex;
+use std::error::Error;
+
+#[derive(Debug)]
+enum SyncError {
+    TransferFailed(usize),
+    DeleteFailed(usize),
+}
+
+impl std::fmt::Display for SyncError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::TransferFailed(count) => write!(f, "{} package file(s) failed to copy", count),
+            Self::DeleteFailed(count) => {
+                write!(f, "{} package file(s) failed to delete", count)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SyncError {}
+
+pub(crate) struct SyncOptions {
+    pub(crate) packages: Vec<String>,
+    pub(crate) version: Option<VersionRequirement>,
+    pub(crate) jobs: usize,
+    pub(crate) dry_run: bool,
+    /// Removes files from the target that are no longer in the source.
+    pub(crate) delete: bool,
+}
+
+enum Action {
+    Copy(Entry),
+    Skip(Entry),
+    Conflict(Entry),
+    Delete(Entry),
+}
+
+/// Copies every version present in the source bucket and missing from the target, along with
+/// the yanks and tags of the synced versions. The diff is recomputed on every run, so an
+/// interrupted sync resumes by running it again.
+pub(crate) async fn sync(
+    source: &StorageLocation,
+    source_driver: &'_ dyn StorageDriver,
+    target: &StorageLocation,
+    target_driver: &'_ dyn StorageDriver,
+    options: SyncOptions,
+) -> Result<(), Box<dyn Error>> {
+    let patterns = options
+        .packages
+        .iter()
+        .map(|x| glob_to_regex(x))
+        .collect::<Result<Vec<Regex>, regex::Error>>()?;
+
+    let source_index = Index::from_storage_bucket(source_driver, source.bucket.as_str()).await?;
+    let target_index = Index::from_storage_bucket(target_driver, target.bucket.as_str()).await?;
+    let actions = diff(
+        &source_index,
+        &target_index,
+        &patterns,
+        &options.version,
+        options.delete,
+    );
+
+    let mut copies: Vec<Entry> = Vec::new();
+    let mut skipped: Vec<Entry> = Vec::new();
+    let mut conflicts: Vec<Entry> = Vec::new();
+    let mut deletions: Vec<Entry> = Vec::new();
+
+    for action in actions {
+        match action {
+            Action::Copy(entry) => copies.push(entry),
+            Action::Skip(entry) => skipped.push(entry),
+            Action::Conflict(entry) => conflicts.push(entry),
+            Action::Delete(entry) => deletions.push(entry),
+        }
+    }
+
+    for entry in conflicts.iter() {
+        println!(
+            "Conflict: {}@{} differs between {} and {}",
+            entry.name, entry.version, source, target
+        );
+    }
+
+    if options.dry_run {
+        for entry in copies.iter() {
+            println!("[dry-run] Copy {}@{}", entry.name, entry.version);
+        }
+
+        for entry in deletions.iter() {
+            for file in entry.files.iter() {
+                println!("[dry-run] Delete {}", file.get_object_url());
+            }
+        }
+    }
+
+    let results: Vec<(Entry, Result<(), String>)> = if options.dry_run {
+        Vec::new()
+    } else {
+        stream::iter(copies.iter().cloned())
+            .map(|entry| async move {
+                println!("Copying {}@{}", entry.name, entry.version);
+                let result = copy_entry(source, source_driver, target, target_driver, &entry)
+                    .await
+                    .map_err(|e| e.to_string());
+                (entry, result)
+            })
+            .buffer_unordered(options.jobs.max(1))
+            .collect()
+            .await
+    };
+
+    let failures: Vec<&(Entry, Result<(), String>)> =
+        results.iter().filter(|x| x.1.is_err()).collect();
+    let mut deleted = 0;
+    let mut delete_failures = 0;
+
+    if !options.dry_run {
+        for entry in deletions.iter() {
+            for file in entry.files.iter() {
+                println!("Deleting {}", file.get_object_url());
+
+                match target_driver
+                    .delete(target.bucket.as_str(), file.key.as_str())
+                    .await
+                {
+                    Result::Ok(_) => deleted += 1,
+                    Result::Err(e) => {
+                        println!("Failed to delete {} (Error={})", file.get_object_url(), e);
+                        delete_failures += 1;
+                    }
+                }
+            }
+        }
+
+        let synced: Vec<Entry> = results
+            .iter()
+            .filter(|x| x.1.is_ok())
+            .map(|x| x.0.clone())
+            .chain(skipped.iter().cloned())
+            .collect();
+        let removed: Vec<Entry> = deletions
+            .iter()
+            .filter(|x| source_index.find(&x.name, &x.version).is_none())
+            .cloned()
+            .collect();
+
+        let mut messages: Vec<String> = Vec::new();
+
+        BucketMetadata::update(target_driver, target.bucket.as_str(), |metadata| {
+            messages = sync_metadata(
+                source_index.metadata(),
+                metadata,
+                &target_index,
+                &synced,
+                &removed,
+            );
+        })
+        .await?;
+
+        for message in messages {
+            println!("{}", message);
+        }
+    }
+
+    for (entry, result) in failures.iter() {
+        println!(
+            "Failed: {}@{} (Error={})",
+            entry.name,
+            entry.version,
+            result.as_ref().err().unwrap()
+        );
+    }
+
+    println!(
+        "{}Copied: {}, Skipped: {}, Conflicting: {}, Deleted: {}, Failed: {}",
+        if options.dry_run { "[dry-run] " } else { "" },
+        if options.dry_run {
+            copies.len()
+        } else {
+            results.len() - failures.len()
+        },
+        skipped.len(),
+        conflicts.len(),
+        if options.dry_run {
+            deletions.iter().map(|x| x.files.len()).sum()
+        } else {
+            deleted
+        },
+        failures.len() + delete_failures
+    );
+
+    if !failures.is_empty() {
+        return Result::Err(SyncError::TransferFailed(failures.len()).into());
+    }
+
+    if delete_failures > 0 {
+        return Result::Err(SyncError::DeleteFailed(delete_failures).into());
+    }
+
+    Result::Ok(())
+}
+
+/// Carries the yanks and tags of the synced releases over to the target and drops the records
+/// of releases deleted from it. `latest` only follows the source when that moves it forward in
+/// the target. Returns a message for every tag it moved or kept.
+fn sync_metadata(
+    source: &BucketMetadata,
+    target: &mut BucketMetadata,
+    target_index: &Index,
+    synced: &[Entry],
+    removed: &[Entry],
+) -> Vec<String> {
+    let mut messages: Vec<String> = Vec::new();
+
+    for entry in synced {
+        let mut tagged = copy_metadata(source, target, entry);
+
+        if source.get_tag(&entry.name, LATEST_TAG) == Some(&entry.version)
+            && target.get_tag(&entry.name, LATEST_TAG) != Some(&entry.version)
+        {
+            let higher = match entry.semantic_version() {
+                Some(x) => publish::higher_latest(target, target_index, &entry.name, &x)
+                    .map(|x| x.to_string()),
+                None => target.get_tag(&entry.name, LATEST_TAG).cloned(),
+            };
+
+            match higher {
+                Some(higher) => messages.push(format!(
+                    "Kept {} tag of {} on the higher version {}",
+                    LATEST_TAG, entry.name, higher
+                )),
+                None => {
+                    target.tag(&entry.name, &entry.version, LATEST_TAG);
+                    tagged.push(LATEST_TAG.to_string());
+                }
+            }
+        }
+
+        messages.extend(
+            tagged
+                .into_iter()
+                .map(|tag| format!("Tagged {}@{} as {}", entry.name, entry.version, tag)),
+        );
+    }
+
+    for entry in removed {
+        target.remove_version(&entry.name, &entry.version);
+    }
+
+    messages
+}
+
+/// Files present in both buckets are considered identical unless their sizes differ. Releases
+/// missing some of their files in the target only have those files copied. With `delete`, files
+/// in the target that the source doesn't have are deleted.
+fn diff(
+    source: &Index,
+    target: &Index,
+    patterns: &[Regex],
+    version: &Option<VersionRequirement>,
+    delete: bool,
+) -> Vec<Action> {
+    let mut actions: Vec<Action> = Vec::new();
+    let selected = |name: &str, entry: &Entry| {
+        (patterns.is_empty() || patterns.iter().any(|x| x.is_match(name)))
+            && version.as_ref().is_none_or(|requirement| {
+                entry
+                    .semantic_version()
+                    .is_some_and(|x| requirement.matches(&x))
+            })
+    };
+
+    for name in source.names() {
+        for entry in source.list_versions(&name) {
+            if !selected(&name, &entry) {
+                continue;
+            }
+
+            let existing = match target.find(&entry.name, &entry.version) {
+                Some(existing) => existing,
+                None => {
+                    actions.push(Action::Copy(entry));
+                    continue;
+                }
+            };
+
+            let conflicting = entry.files.iter().any(|file| {
+                existing.files.iter().any(|x| {
+                    x.key == file.key
+                        && x.size.is_some()
+                        && file.size.is_some()
+                        && x.size != file.size
+                })
+            });
+            let missing: Vec<ObjectRef> = entry
+                .files
+                .iter()
+                .filter(|file| !existing.files.iter().any(|x| x.key == file.key))
+                .cloned()
+                .collect();
+
+            let extra: Vec<ObjectRef> = existing
+                .files
+                .iter()
+                .filter(|x| !entry.files.iter().any(|file| file.key == x.key))
+                .cloned()
+                .collect();
+
+            if delete && !extra.is_empty() {
+                actions.push(Action::Delete(Entry {
+                    files: extra,
+                    ..existing
+                }));
+            }
+
+            actions.push(if conflicting {
+                Action::Conflict(entry)
+            } else if missing.is_empty() {
+                Action::Skip(entry)
+            } else {
+                Action::Copy(Entry {
+                    files: missing,
+                    ..entry
+                })
+            });
+        }
+    }
+
+    if delete {
+        for name in target.names() {
+            for entry in target.list_versions(&name) {
+                if selected(&name, &entry) && source.find(&name, &entry.version).is_none() {
+                    actions.push(Action::Delete(entry));
+                }
+            }
+        }
+    }
+
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn index(files: &[(&str, u64)]) -> Index {
+        let mut index = Index::new();
+
+        for (key, size) in files {
+            let (name, filename) = key.split_once('/').unwrap();
+            let version = filename
+                .trim_start_matches(&format!("{}-", name))
+                .split('-')
+                .next()
+                .unwrap()
+                .trim_end_matches(".tar.gz");
+
+            index.add(Entry::new(
+                name,
+                version,
+                &ObjectRef {
+                    bucket: "bucket".to_string(),
+                    key: key.to_string(),
+                    protocol: "s3".to_string(),
+                    size: Some(*size),
+                    last_modified: None,
+                    md5: None,
+                },
+            ));
+        }
+
+        index
+    }
+
+    fn describe(actions: Vec<Action>) -> Vec<String> {
+        let mut result: Vec<String> = actions
+            .into_iter()
+            .map(|action| {
+                let (kind, entry) = match action {
+                    Action::Copy(entry) => ("copy", entry),
+                    Action::Skip(entry) => ("skip", entry),
+                    Action::Conflict(entry) => ("conflict", entry),
+                    Action::Delete(entry) => ("delete", entry),
+                };
+                let mut keys: Vec<String> = entry.files.into_iter().map(|x| x.key).collect();
+                keys.sort();
+
+                format!("{} {}", kind, keys.join(" "))
+            })
+            .collect();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn test_diff() {
+        let source = index(&[
+            ("pkg/pkg-1.0.0.tar.gz", 10),
+            ("pkg/pkg-1.1.0.tar.gz", 10),
+            ("pkg/pkg-1.1.0-py3-none-any.whl", 10),
+            ("pkg/pkg-1.2.0.tar.gz", 10),
+            ("other/other-2.0.0.tar.gz", 10),
+        ]);
+        let target = index(&[
+            ("pkg/pkg-1.0.0.tar.gz", 10),
+            ("pkg/pkg-1.0.0-py3-none-any.whl", 10),
+            ("pkg/pkg-1.1.0.tar.gz", 10),
+            ("pkg/pkg-1.2.0.tar.gz", 12),
+            ("pkg/pkg-0.9.0.tar.gz", 10),
+        ]);
+
+        assert_eq!(
+            describe(diff(&source, &target, &[], &None, false)),
+            vec![
+                "conflict pkg/pkg-1.2.0.tar.gz",
+                "copy other/other-2.0.0.tar.gz",
+                "copy pkg/pkg-1.1.0-py3-none-any.whl",
+                "skip pkg/pkg-1.0.0.tar.gz",
+            ]
+        );
+        assert_eq!(
+            describe(diff(&source, &target, &[], &None, true)),
+            vec![
+                "conflict pkg/pkg-1.2.0.tar.gz",
+                "copy other/other-2.0.0.tar.gz",
+                "copy pkg/pkg-1.1.0-py3-none-any.whl",
+                "delete pkg/pkg-0.9.0.tar.gz",
+                "delete pkg/pkg-1.0.0-py3-none-any.whl",
+                "skip pkg/pkg-1.0.0.tar.gz",
+            ]
+        );
+
+        let patterns = vec![glob_to_regex("pkg").unwrap()];
+        let version = Some(VersionRequirement::from_str(">=1.0").unwrap());
+        assert_eq!(
+            describe(diff(&source, &target, &patterns, &version, true)),
+            vec![
+                "conflict pkg/pkg-1.2.0.tar.gz",
+                "copy pkg/pkg-1.1.0-py3-none-any.whl",
+                "delete pkg/pkg-1.0.0-py3-none-any.whl",
+                "skip pkg/pkg-1.0.0.tar.gz",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sync_metadata() {
+        let synced = index(&[("pkg/pkg-1.0.0.tar.gz", 10), ("pkg/pkg-1.1.0.tar.gz", 10)]);
+        let removed = index(&[("pkg/pkg-0.9.0.tar.gz", 10)]);
+        let mut source = BucketMetadata::default();
+        source.yank("pkg", "1.0.0", Some("broken".to_string()));
+        source.tag("pkg", "1.1.0", LATEST_TAG);
+
+        let mut target = BucketMetadata::default();
+        target.tag("pkg", "0.9.0", "stable");
+
+        assert_eq!(
+            sync_metadata(
+                &source,
+                &mut target,
+                &removed,
+                &synced.list_versions("pkg"),
+                &removed.list_versions("pkg")
+            ),
+            vec!["Tagged pkg@1.1.0 as latest"]
+        );
+        assert_eq!(
+            target.get_yank("pkg", "1.0.0").unwrap().reason.as_deref(),
+            Some("broken")
+        );
+        assert_eq!(target.get_tag("pkg", LATEST_TAG).unwrap(), "1.1.0");
+        assert_eq!(target.get_tag("pkg", "stable"), None);
+        assert!(sync_metadata(
+            &source,
+            &mut target,
+            &synced,
+            &synced.list_versions("pkg"),
+            &[]
+        )
+        .is_empty());
+
+        // A target that already moved on to a newer release keeps its latest tag
+        let newer = index(&[("pkg/pkg-2.0.0.tar.gz", 10)]);
+        let mut target = BucketMetadata::default();
+        target.tag("pkg", "2.0.0", LATEST_TAG);
+
+        assert_eq!(
+            sync_metadata(
+                &source,
+                &mut target,
+                &newer,
+                &synced.list_versions("pkg"),
+                &[]
+            ),
+            vec!["Kept latest tag of pkg on the higher version 2.0.0"]
+        );
+        assert_eq!(target.get_tag("pkg", LATEST_TAG).unwrap(), "2.0.0");
+    }
+}
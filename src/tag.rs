@@ -0,0 +1,100 @@
+use crate::bucket_metadata::BucketMetadata;
+use crate::index::Index;
+use crate::package::Package;
+use crate::{RuntimeConfig, StorageDriver};
+use std::error::Error;
+
+#[derive(Debug)]
+enum TagError {
+    NoBucketDefined,
+    InvalidTag(String),
+    PackageNotFound(String),
+    TagNotFound(String),
+}
+
+impl std::fmt::Display for TagError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoBucketDefined => write!(f, "no bucket was provided"),
+            Self::InvalidTag(tag) => write!(
+                f,
+                "invalid tag (tags start with a letter and contain only letters, digits, '.', '_' and '-'): {}",
+                tag
+            ),
+            Self::PackageNotFound(pkg) => write!(f, "package not found: {}", pkg),
+            Self::TagNotFound(tag) => write!(f, "tag not found: {}", tag),
+        }
+    }
+}
+
+impl std::error::Error for TagError {}
+
+pub(crate) async fn tag(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    package: &Package,
+    tag: &str,
+) -> Result<(), Box<dyn Error>> {
+    if !is_valid_tag(tag) {
+        return Result::Err(TagError::InvalidTag(tag.to_string()).into());
+    }
+
+    let (bucket, index) = load_index(config, driver).await?;
+    let entry = index
+        .resolve(package)
+        .ok_or_else(|| TagError::PackageNotFound(package.to_string()))?;
+
+    BucketMetadata::update(driver, bucket.as_str(), |metadata| {
+        metadata.tag(&entry.name, &entry.version, tag)
+    })
+    .await?;
+
+    println!("Tagged {}@{} as {}", entry.name, entry.version, tag);
+
+    Result::Ok(())
+}
+
+pub(crate) async fn untag(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    name: &str,
+    tag: &str,
+) -> Result<(), Box<dyn Error>> {
+    let (bucket, _) = load_index(config, driver).await?;
+    let mut removed = false;
+
+    BucketMetadata::update(driver, bucket.as_str(), |metadata| {
+        removed = metadata.untag(name, tag)
+    })
+    .await?;
+
+    if !removed {
+        return Result::Err(TagError::TagNotFound(format!("{}@{}", name, tag)).into());
+    }
+
+    println!("Removed tag {} from {}", tag, name);
+
+    Result::Ok(())
+}
+
+pub(crate) fn is_valid_tag(tag: &str) -> bool {
+    tag.starts_with(|c: char| c.is_ascii_alphabetic())
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+}
+
+async fn load_index(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+) -> Result<(String, Index), Box<dyn Error>> {
+    let bucket = if config.bucket.is_none() {
+        return Result::Err(TagError::NoBucketDefined.into());
+    } else {
+        config.bucket.clone().unwrap()
+    };
+
+    let index = Index::from_storage_bucket(driver, bucket.as_str()).await?;
+
+    Result::Ok((bucket, index))
+}
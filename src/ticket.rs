@@ -0,0 +1,269 @@
+use crate::distribution::{Distribution, DistributionKind};
+use crate::http;
+use crate::index::Index;
+use crate::package::Package;
+use crate::package_version::PackageVersion;
+use crate::publish;
+use crate::simple::normalize_name;
+use crate::{RuntimeConfig, StorageDriver};
+use hyper::{header, Body, Request, StatusCode};
+use std::error::Error;
+use std::path::Path;
+use std::time::Duration;
+use url::Url;
+
+/// Headers that make a ticket create-only, sent when the ticket's signature covers them.
+const PRECONDITION_HEADERS: [(&str, &str); 2] =
+    [("x-goog-if-generation-match", "0"), ("if-none-match", "*")];
+
+#[derive(Debug)]
+enum TicketError {
+    NoBucketDefined,
+    ExactVersionRequired(String),
+    InvalidName(String),
+    VersionExists(String),
+    InvalidTicket(String),
+    InvalidPackage(String, String),
+    PackageMismatch(String, String),
+    UploadFailed(StatusCode, String),
+}
+
+impl std::fmt::Display for TicketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoBucketDefined => write!(f, "no bucket was provided"),
+            Self::ExactVersionRequired(pkg) => {
+                write!(f, "upload tickets require an exact version: {}", pkg)
+            }
+            Self::InvalidName(name) => write!(
+                f,
+                "package names may only contain letters, digits and underscores: {}",
+                name
+            ),
+            Self::VersionExists(pkg) => write!(f, "version is already published: {}", pkg),
+            Self::InvalidTicket(url) => write!(f, "not an upload ticket: {}", url),
+            Self::InvalidPackage(path, msg) => write!(f, "invalid package {}: {}", path, msg),
+            Self::PackageMismatch(filename, key) => {
+                write!(f, "{} does not match the ticket for {}", filename, key)
+            }
+            Self::UploadFailed(status, msg) => {
+                write!(f, "upload rejected with status {}: {}", status, msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TicketError {}
+
+/// Prints a presigned url that uploads the source distribution of exactly one version, so it can
+/// be published by someone without write access to the bucket.
+pub(crate) async fn grant_upload(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    package: &Package,
+    expires: Duration,
+) -> Result<(), Box<dyn Error>> {
+    let bucket = if config.bucket.is_none() {
+        return Result::Err(TicketError::NoBucketDefined.into());
+    } else {
+        config.bucket.clone().unwrap()
+    };
+
+    let version = match &package.version {
+        PackageVersion::Literal(version) => version.clone(),
+        _ => return Result::Err(TicketError::ExactVersionRequired(package.to_string()).into()),
+    };
+
+    let index = Index::from_storage_bucket(driver, bucket.as_str()).await?;
+
+    // Upload into the directory the project is already published under
+    let name = index
+        .names()
+        .into_iter()
+        .find(|x| normalize_name(x) == normalize_name(&package.name))
+        .unwrap_or_else(|| package.name.clone());
+
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Result::Err(TicketError::InvalidName(name).into());
+    }
+
+    let package = Package {
+        name,
+        version: PackageVersion::Literal(version),
+        extras: Vec::new(),
+    };
+
+    if index.contains(&package) {
+        return Result::Err(TicketError::VersionExists(package.to_string()).into());
+    }
+
+    let url = driver
+        .presign_put(bucket.as_str(), package.object_key().as_str(), expires)
+        .await?;
+
+    println!("{}", url);
+
+    Result::Ok(())
+}
+
+/// Uploads a source distribution with a ticket from `grant_upload`. The file is validated like
+/// `put` does and must be the version the ticket was granted for.
+pub(crate) async fn upload(path: &Path, ticket: &Url) -> Result<(), Box<dyn Error>> {
+    let key = ticket
+        .path_segments()
+        .and_then(|mut x| x.next_back())
+        .and_then(|x| urlencoding::decode(x).ok())
+        .map(|x| x.into_owned())
+        .unwrap_or_default();
+    let granted = Distribution::from_filename(&key)
+        .filter(|x| matches!(x.kind, DistributionKind::Sdist))
+        .ok_or_else(|| TicketError::InvalidTicket(redact(ticket)))?;
+
+    let (distribution, data) = publish::read_distribution(path)
+        .map_err(|e| TicketError::InvalidPackage(path.display().to_string(), e.to_string()))?;
+
+    if !matches!(distribution.kind, DistributionKind::Sdist)
+        || normalize_name(&distribution.name) != normalize_name(&granted.name)
+        || distribution.version != granted.version
+    {
+        return Result::Err(TicketError::PackageMismatch(distribution.filename, key).into());
+    }
+
+    println!("Uploading {} -> {}", path.display(), redact(ticket));
+
+    let mut request =
+        Request::put(ticket.as_str()).header(header::CONTENT_TYPE, "application/octet-stream");
+
+    for (name, value) in preconditions(ticket) {
+        request = request.header(name, value);
+    }
+
+    let request = request.body(Body::from(data))?;
+    let (status, body) = http::send(request).await?;
+
+    if status == StatusCode::PRECONDITION_FAILED {
+        return Result::Err(
+            TicketError::VersionExists(format!("{}@{}", granted.name, granted.version)).into(),
+        );
+    }
+
+    if !status.is_success() {
+        return Result::Err(
+            TicketError::UploadFailed(status, String::from_utf8_lossy(&body).trim().to_string())
+                .into(),
+        );
+    }
+
+    println!(
+        "Uploaded {}. Tags are not moved by ticket uploads; run `sling tag {}@{} latest` to update them.",
+        key, granted.name, granted.version
+    );
+
+    Result::Ok(())
+}
+
+/// The precondition headers listed among the signed headers of a ticket.
+fn preconditions(ticket: &Url) -> Vec<(&'static str, &'static str)> {
+    let signed: Vec<String> = ticket
+        .query_pairs()
+        .filter(|(name, _)| name == "X-Goog-SignedHeaders" || name == "X-Amz-SignedHeaders")
+        .flat_map(|(_, value)| {
+            value
+                .split(';')
+                .map(|x| x.to_lowercase())
+                .collect::<Vec<String>>()
+        })
+        .collect();
+
+    PRECONDITION_HEADERS
+        .iter()
+        .filter(|(name, _)| signed.iter().any(|x| x == name))
+        .cloned()
+        .collect()
+}
+
+/// The ticket url without its signature.
+fn redact(ticket: &Url) -> String {
+    let mut url = ticket.clone();
+    url.set_query(None);
+    url.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{write_sdist, write_wheel};
+
+    const TICKET: &str = "https://storage.googleapis.com/bucket/pkg/pkg-1.0.0.tar.gz\
+        ?X-Goog-Algorithm=GOOG4-RSA-SHA256\
+        &X-Goog-SignedHeaders=host%3Bx-goog-if-generation-match\
+        &X-Goog-Signature=0123abcd";
+
+    #[tokio::test]
+    async fn test_upload_mismatch() {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let ticket = Url::parse(TICKET).unwrap();
+
+        for (filename, name, version) in [
+            ("other-1.0.0.tar.gz", "other", "1.0.0"),
+            ("pkg-1.0.1.tar.gz", "pkg", "1.0.1"),
+        ] {
+            let path = write_sdist(dir.path(), filename, name, version);
+            let error = upload(&path, &ticket).await.unwrap_err();
+
+            assert_eq!(
+                error.to_string(),
+                format!(
+                    "{} does not match the ticket for pkg-1.0.0.tar.gz",
+                    filename
+                )
+            );
+        }
+
+        let wheel = write_wheel(dir.path(), "pkg", "1.0.0");
+        assert_eq!(
+            upload(&wheel, &ticket).await.unwrap_err().to_string(),
+            "pkg-1.0.0-py3-none-any.whl does not match the ticket for pkg-1.0.0.tar.gz"
+        );
+
+        let url =
+            Url::parse("https://storage.googleapis.com/bucket/pkg/pkg-1.0.0-py3-none-any.whl")
+                .unwrap();
+        let path = write_sdist(dir.path(), "pkg-1.0.0.tar.gz", "pkg", "1.0.0");
+        assert!(upload(&path, &url)
+            .await
+            .unwrap_err()
+            .to_string()
+            .starts_with("not an upload ticket"));
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact(&Url::parse(TICKET).unwrap()),
+            "https://storage.googleapis.com/bucket/pkg/pkg-1.0.0.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_preconditions() {
+        assert_eq!(
+            preconditions(&Url::parse(TICKET).unwrap()),
+            vec![("x-goog-if-generation-match", "0")]
+        );
+        assert_eq!(
+            preconditions(
+                &Url::parse(
+                    "https://bucket.s3.amazonaws.com/pkg/pkg-1.0.0.tar.gz\
+                     ?X-Amz-SignedHeaders=host%3Bif-none-match%3Bx-amz-content-sha256"
+                )
+                .unwrap()
+            ),
+            vec![("if-none-match", "*")]
+        );
+        assert!(preconditions(
+            &Url::parse("https://bucket.s3.amazonaws.com/pkg/pkg-1.0.0.tar.gz").unwrap()
+        )
+        .is_empty());
+    }
+}
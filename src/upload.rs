@@ -0,0 +1,324 @@
+use crate::list::format_size;
+use crate::publish::{self, PublishOptions};
+use crate::{digest, RuntimeConfig, StorageDriver};
+use bytes::Bytes;
+use hyper::{header, Body, Request, StatusCode};
+use std::path::PathBuf;
+
+/// Failures of an upload, each mapped onto the status code the legacy PyPI upload API uses.
+#[derive(Debug)]
+pub(crate) enum UploadError {
+    Unauthorized,
+    Forbidden,
+    BadRequest(String),
+    Conflict(String),
+    PayloadTooLarge(u64),
+    Internal(String),
+}
+
+impl UploadError {
+    pub(crate) fn status(&self) -> StatusCode {
+        match self {
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl std::fmt::Display for UploadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Unauthorized => write!(f, "authentication required"),
+            Self::Forbidden => write!(f, "invalid upload token"),
+            Self::BadRequest(msg) => write!(f, "{}", msg),
+            Self::Conflict(msg) => write!(f, "{}", msg),
+            Self::PayloadTooLarge(limit) => {
+                write!(
+                    f,
+                    "upload exceeds the maximum size of {}",
+                    format_size(*limit)
+                )
+            }
+            Self::Internal(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+/// Parses the `--max-upload-size` of `serve`, given in MiB, into bytes.
+pub(crate) fn parse_max_size(s: &str) -> Result<u64, String> {
+    s.trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|x| *x > 0)
+        .and_then(|x| x.checked_mul(1024 * 1024))
+        .ok_or_else(|| format!("expected a size in MiB between 1 and {}", u64::MAX >> 20))
+}
+
+/// Handles a `file_upload` request of the legacy PyPI upload protocol as sent by twine and
+/// poetry. The password of the basic auth credentials must be one of the configured tokens.
+/// Requests larger than `max_size` bytes are rejected before they are buffered.
+pub(crate) async fn upload(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    tokens: &[String],
+    max_size: u64,
+    req: Request<Body>,
+) -> Result<String, UploadError> {
+    authenticate(&req, tokens)?;
+
+    let length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.parse::<u64>().ok());
+
+    if length.is_some_and(|x| x > max_size) {
+        return Result::Err(UploadError::PayloadTooLarge(max_size));
+    }
+
+    let boundary = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| multer::parse_boundary(x).ok())
+        .ok_or_else(|| UploadError::BadRequest("expected a multipart form".to_string()))?;
+
+    // Also enforced while streaming, since the header is optional and can't be trusted
+    let constraints =
+        multer::Constraints::new().size_limit(multer::SizeLimit::new().whole_stream(max_size));
+    let mut multipart = multer::Multipart::with_constraints(req.into_body(), boundary, constraints);
+    let mut action: Option<String> = None;
+    let mut sha256: Option<String> = None;
+    let mut content: Option<(String, Bytes)> = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(bad_request)? {
+        let name = field.name().unwrap_or_default().to_string();
+
+        match name.as_str() {
+            ":action" => action = Some(field.text().await.map_err(bad_request)?),
+            "sha256_digest" => sha256 = Some(field.text().await.map_err(bad_request)?),
+            "content" => {
+                let filename = field.file_name().unwrap_or_default().to_string();
+                content = Some((filename, field.bytes().await.map_err(bad_request)?));
+            }
+            _ => (),
+        }
+    }
+
+    if action.as_deref() != Some("file_upload") {
+        return Result::Err(UploadError::BadRequest(format!(
+            "unsupported action: {}",
+            action.unwrap_or_default()
+        )));
+    }
+
+    let (filename, data) =
+        content.ok_or_else(|| UploadError::BadRequest("missing content".to_string()))?;
+
+    // Only the bare filename is used so a client cannot write outside the upload directory
+    let filename = match PathBuf::from(&filename)
+        .file_name()
+        .and_then(|x| x.to_str())
+    {
+        Some(x) => x.to_string(),
+        None => return Result::Err(UploadError::BadRequest("missing filename".to_string())),
+    };
+
+    if let Some(expected) = sha256.filter(|x| !x.is_empty()) {
+        if !digest::sha256(&data).eq_ignore_ascii_case(expected.trim()) {
+            return Result::Err(UploadError::BadRequest(format!(
+                "sha256 digest does not match {}",
+                filename
+            )));
+        }
+    }
+
+    let dir = tempdir::TempDir::new("sling-").map_err(internal)?;
+    let path = dir.path().join(&filename);
+    std::fs::write(&path, &data).map_err(internal)?;
+
+    let options = PublishOptions {
+        overwrite: false,
+        update_latest: true,
+        jobs: 1,
+    };
+
+    match publish::publish(config, driver, &[path], options).await {
+        Result::Ok(_) => Result::Ok(filename),
+        Result::Err(e) if publish::is_conflict(e.as_ref()) => {
+            Result::Err(UploadError::Conflict(e.to_string()))
+        }
+        Result::Err(e) if publish::is_invalid(e.as_ref()) => {
+            Result::Err(UploadError::BadRequest(e.to_string()))
+        }
+        Result::Err(e) => Result::Err(UploadError::Internal(e.to_string())),
+    }
+}
+
+fn authenticate(req: &Request<Body>, tokens: &[String]) -> Result<(), UploadError> {
+    let credentials = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.strip_prefix("Basic "))
+        .and_then(|x| base64::decode(x.trim()).ok())
+        .and_then(|x| String::from_utf8(x).ok())
+        .ok_or(UploadError::Unauthorized)?;
+
+    let password = credentials
+        .split_once(':')
+        .map(|x| x.1)
+        .ok_or(UploadError::Unauthorized)?;
+
+    if tokens
+        .iter()
+        .any(|x| constant_time_eq(x.as_bytes(), password.as_bytes()))
+    {
+        Result::Ok(())
+    } else {
+        Result::Err(UploadError::Forbidden)
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn bad_request(e: multer::Error) -> UploadError {
+    match e {
+        multer::Error::StreamSizeExceeded { limit } => UploadError::PayloadTooLarge(limit),
+        multer::Error::FieldSizeExceeded { limit, .. } => UploadError::PayloadTooLarge(limit),
+        // The size limit surfaces from inside the body stream while a field is being read
+        multer::Error::StreamReadFailed(inner) => match inner.downcast::<multer::Error>() {
+            Result::Ok(inner) => bad_request(*inner),
+            Result::Err(inner) => {
+                UploadError::BadRequest(format!("failed to read stream: {}", inner))
+            }
+        },
+        e => UploadError::BadRequest(e.to_string()),
+    }
+}
+
+fn internal(e: std::io::Error) -> UploadError {
+    UploadError::Internal(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::write_sdist;
+    use crate::storage::memory::MemoryStorageDriver;
+
+    const BOUNDARY: &str = "sling-boundary";
+
+    fn config() -> RuntimeConfig {
+        RuntimeConfig {
+            bucket: Some("bucket".to_string()),
+            ..RuntimeConfig::default()
+        }
+    }
+
+    fn request(password: Option<&str>, sha256: &str, filename: &str, data: &[u8]) -> Request<Body> {
+        let mut body = Vec::new();
+
+        for (name, value) in [(":action", "file_upload"), ("sha256_digest", sha256)] {
+            body.extend(
+                format!(
+                    "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                    BOUNDARY, name, value
+                )
+                .as_bytes(),
+            );
+        }
+
+        body.extend(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"content\"; filename=\"{}\"\r\n\
+                 Content-Type: application/octet-stream\r\n\r\n",
+                BOUNDARY, filename
+            )
+            .as_bytes(),
+        );
+        body.extend(data);
+        body.extend(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+        let mut builder = Request::post("/").header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", BOUNDARY),
+        );
+
+        if let Some(password) = password {
+            builder = builder.header(
+                header::AUTHORIZATION,
+                format!(
+                    "Basic {}",
+                    base64::encode(format!("__token__:{}", password))
+                ),
+            );
+        }
+
+        builder.body(Body::from(body)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_upload() {
+        let dir = tempdir::TempDir::new("sling-").unwrap();
+        let driver = MemoryStorageDriver::default();
+        let tokens = vec!["secret".to_string()];
+        let path = write_sdist(dir.path(), "pkg-1.0.0.tar.gz", "pkg", "1.0.0");
+        let data = std::fs::read(&path).unwrap();
+        let sha256 = digest::sha256(&data);
+        let config = config();
+        let upload = |req| upload(&config, &driver, &tokens, 1024 * 1024, req);
+
+        let error = upload(request(None, &sha256, "pkg-1.0.0.tar.gz", &data))
+            .await
+            .unwrap_err();
+        assert_eq!(error.status(), StatusCode::UNAUTHORIZED);
+
+        let error = upload(request(Some("wrong"), &sha256, "pkg-1.0.0.tar.gz", &data))
+            .await
+            .unwrap_err();
+        assert_eq!(error.status(), StatusCode::FORBIDDEN);
+
+        let error = upload(request(
+            Some("secret"),
+            &digest::sha256(b"other"),
+            "pkg-1.0.0.tar.gz",
+            &data,
+        ))
+        .await
+        .unwrap_err();
+        assert_eq!(error.status(), StatusCode::BAD_REQUEST);
+        assert!(driver.keys("bucket").is_empty());
+
+        let filename = upload(request(Some("secret"), &sha256, "pkg-1.0.0.tar.gz", &data))
+            .await
+            .unwrap();
+        assert_eq!(filename, "pkg-1.0.0.tar.gz");
+        assert!(driver
+            .keys("bucket")
+            .contains(&"pkg/pkg-1.0.0.tar.gz".to_string()));
+
+        let error = upload(request(Some("secret"), &sha256, "pkg-1.0.0.tar.gz", &data))
+            .await
+            .unwrap_err();
+        assert_eq!(error.status(), StatusCode::CONFLICT);
+    }
+
+    #[test]
+    fn test_parse_max_size() {
+        assert_eq!(parse_max_size("100").unwrap(), 100 * 1024 * 1024);
+        assert_eq!(parse_max_size("1").unwrap(), 1024 * 1024);
+
+        for value in ["0", "-1", "1.5", "", "17592186044416"] {
+            assert!(parse_max_size(value).is_err(), "{}", value);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use crate::semantic_version::SemanticVersion;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub(crate) enum ParseVersionRequirementError {
+    InvalidFormat(String),
+}
+
+impl std::fmt::Display for ParseVersionRequirementError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidFormat(s) => write!(f, "invalid version requirement: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for ParseVersionRequirementError {}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Operator {
+    Exact,
+    NotEqual,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+    Compatible,
+    Caret,
+    Tilde,
+}
+
+impl Operator {
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Exact => "==",
+            Self::NotEqual => "!=",
+            Self::Greater => ">",
+            Self::GreaterOrEqual => ">=",
+            Self::Less => "<",
+            Self::LessOrEqual => "<=",
+            Self::Compatible => "~=",
+            Self::Caret => "^",
+            Self::Tilde => "~",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Comparator {
+    operator: Operator,
+    version: SemanticVersion,
+    segments: usize,
+}
+
+impl Comparator {
+    fn matches(&self, version: &SemanticVersion) -> bool {
+        match self.operator {
+            Operator::Exact => *version == self.version,
+            Operator::NotEqual => *version != self.version,
+            Operator::Greater => *version > self.version,
+            Operator::GreaterOrEqual => *version >= self.version,
+            Operator::Less => *version < self.version,
+            Operator::LessOrEqual => *version <= self.version,
+            Operator::Compatible | Operator::Caret | Operator::Tilde => {
+                *version >= self.version && *version < self.upper_bound()
+            }
+        }
+    }
+
+    fn upper_bound(&self) -> SemanticVersion {
+        let (major, minor, patch) = self.version.segments();
+
+        match self.operator {
+            // ~=1.2 allows 1.x (x >= 2), ~=1.2.3 allows 1.2.x (x >= 3)
+            Operator::Compatible if self.segments <= 2 => SemanticVersion::new(major + 1, 0, 0),
+            Operator::Compatible => SemanticVersion::new(major, minor + 1, 0),
+            // ^1.2.3 allows changes that do not modify the left-most non-zero segment
+            Operator::Caret if major > 0 || self.segments == 1 => {
+                SemanticVersion::new(major + 1, 0, 0)
+            }
+            Operator::Caret if minor > 0 || self.segments == 2 => {
+                SemanticVersion::new(major, minor + 1, 0)
+            }
+            Operator::Caret => SemanticVersion::new(major, minor, patch + 1),
+            // ~1 allows 1.x.y, ~1.2 and ~1.2.3 allow 1.2.x
+            Operator::Tilde if self.segments == 1 => SemanticVersion::new(major + 1, 0, 0),
+            Operator::Tilde => SemanticVersion::new(major, minor + 1, 0),
+            _ => self.version.clone(),
+        }
+    }
+}
+
+impl FromStr for Comparator {
+    type Err = ParseVersionRequirementError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let operators = [
+            Operator::Exact,
+            Operator::NotEqual,
+            Operator::GreaterOrEqual,
+            Operator::LessOrEqual,
+            Operator::Compatible,
+            Operator::Greater,
+            Operator::Less,
+            Operator::Caret,
+            Operator::Tilde,
+        ];
+
+        let (operator, version) = match operators.iter().find(|x| s.starts_with(x.as_str())) {
+            Some(operator) => (operator.clone(), s[operator.as_str().len()..].trim()),
+            None => (Operator::Exact, s),
+        };
+
+        let segments = version.split('.').count();
+        let version = SemanticVersion::from_str(version)
+            .map_err(|_| ParseVersionRequirementError::InvalidFormat(s.to_string()))?;
+
+        if operator == Operator::Compatible && segments < 2 {
+            return Result::Err(ParseVersionRequirementError::InvalidFormat(s.to_string()));
+        }
+
+        Result::Ok(Comparator {
+            operator,
+            version,
+            segments,
+        })
+    }
+}
+
+impl std::fmt::Display for Comparator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.operator.as_str(), self.version)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct VersionRequirement(Vec<Comparator>);
+
+impl VersionRequirement {
+    pub(crate) fn matches(&self, version: &SemanticVersion) -> bool {
+        self.0.iter().all(|x| x.matches(version))
+    }
+
+    /// Returns the pinned version when the requirement is a single `==` comparator.
+    pub(crate) fn as_exact(&self) -> Option<SemanticVersion> {
+        match self.0.as_slice() {
+            [x] if x.operator == Operator::Exact => Some(x.version.clone()),
+            _ => None,
+        }
+    }
+
+    /// Renders the requirement with PEP 440 operators only, so pip accepts it. Range operators
+    /// become explicit bounds.
+    pub(crate) fn to_pep440(&self) -> String {
+        self.0
+            .iter()
+            .map(|x| match x.operator {
+                Operator::Compatible | Operator::Caret | Operator::Tilde => {
+                    format!(">={},<{}", x.version, x.upper_bound())
+                }
+                _ => x.to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    pub(crate) fn is_requirement(s: &str) -> bool {
+        s.contains(',') || s.starts_with(|c| "=!<>~^".contains(c))
+    }
+}
+
+impl FromStr for VersionRequirement {
+    type Err = ParseVersionRequirementError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let comparators = s
+            .split(',')
+            .filter(|x| !x.trim().is_empty())
+            .map(Comparator::from_str)
+            .collect::<Result<Vec<Comparator>, ParseVersionRequirementError>>()?;
+
+        if comparators.is_empty() {
+            return Result::Err(ParseVersionRequirementError::InvalidFormat(s.to_string()));
+        }
+
+        Result::Ok(VersionRequirement(comparators))
+    }
+}
+
+impl std::fmt::Display for VersionRequirement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.0
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(requirement: &str, version: &str) -> bool {
+        VersionRequirement::from_str(requirement)
+            .unwrap()
+            .matches(&SemanticVersion::from_str(version).unwrap())
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        assert!(matches(">=1.0,<2.0", "1.5.0"));
+        assert!(!matches(">=1.0,<2.0", "2.0.0"));
+        assert!(matches("==1.2.3", "1.2.3"));
+        assert!(!matches("!=1.2.3", "1.2.3"));
+        assert!(matches(">1.2.3", "1.2.4"));
+        assert!(matches("<=1.2.3", "1.2.3"));
+    }
+
+    #[test]
+    fn test_range_operators() {
+        assert!(matches("~=1.2", "1.9.0"));
+        assert!(!matches("~=1.2", "2.0.0"));
+        assert!(matches("~=1.2.3", "1.2.9"));
+        assert!(!matches("~=1.2.3", "1.3.0"));
+        assert!(matches("^1.2", "1.9.9"));
+        assert!(!matches("^0.2", "0.3.0"));
+        assert!(!matches("^0.0.3", "0.0.4"));
+        assert!(matches("~1.2.3", "1.2.9"));
+        assert!(!matches("~1.2.3", "1.3.0"));
+    }
+
+    #[test]
+    fn test_to_pep440() {
+        let pep440 = |x: &str| VersionRequirement::from_str(x).unwrap().to_pep440();
+
+        assert_eq!(pep440("^1.2"), ">=1.2.0,<2.0.0");
+        assert_eq!(pep440("~1.2.3,!=1.2.5"), ">=1.2.3,<1.3.0,!=1.2.5");
+        assert_eq!(pep440("~=1.2"), ">=1.2.0,<2.0.0");
+        assert_eq!(pep440("1.0.0"), "==1.0.0");
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(VersionRequirement::from_str(">=abc").is_err());
+        assert!(VersionRequirement::from_str("~=1").is_err());
+        assert!(VersionRequirement::from_str("").is_err());
+    }
+}
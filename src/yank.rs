@@ -0,0 +1,102 @@
+use crate::bucket_metadata::BucketMetadata;
+use crate::index::{Entry, Index};
+use crate::package::Package;
+use crate::package_version::PackageVersion;
+use crate::{RuntimeConfig, StorageDriver};
+use std::error::Error;
+
+#[derive(Debug)]
+enum YankError {
+    NoBucketDefined,
+    VersionRequired(String),
+    PackageNotFound(String),
+    NotYanked(String),
+}
+
+impl std::fmt::Display for YankError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::NoBucketDefined => write!(f, "no bucket was provided"),
+            Self::VersionRequired(pkg) => write!(f, "an exact version is required: {}", pkg),
+            Self::PackageNotFound(pkg) => write!(f, "package not found: {}", pkg),
+            Self::NotYanked(pkg) => write!(f, "package is not yanked: {}", pkg),
+        }
+    }
+}
+
+impl std::error::Error for YankError {}
+
+pub(crate) async fn yank(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    package: &Package,
+    reason: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    let (bucket, entry) = load_entry(config, driver, package).await?;
+
+    BucketMetadata::update(driver, bucket.as_str(), |metadata| {
+        metadata.yank(&entry.name, &entry.version, reason.clone())
+    })
+    .await?;
+
+    println!("Yanked {}@{}", entry.name, entry.version);
+
+    Result::Ok(())
+}
+
+pub(crate) async fn unyank(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    package: &Package,
+) -> Result<(), Box<dyn Error>> {
+    let (bucket, entry) = load_entry(config, driver, package).await?;
+    let mut removed = false;
+
+    BucketMetadata::update(driver, bucket.as_str(), |metadata| {
+        removed = metadata.unyank(&entry.name, &entry.version)
+    })
+    .await?;
+
+    if !removed {
+        return Result::Err(YankError::NotYanked(package.to_string()).into());
+    }
+
+    println!("Restored {}@{}", entry.name, entry.version);
+
+    Result::Ok(())
+}
+
+pub(crate) fn warn_if_yanked(index: &Index, entry: &Entry) {
+    if let Some(yank) = index.get_yank(&entry.name, &entry.version) {
+        println!(
+            "Warning: {}@{} has been yanked (Reason={})",
+            entry.name,
+            entry.version,
+            yank.reason.as_deref().unwrap_or("none given")
+        );
+    }
+}
+
+async fn load_entry(
+    config: &RuntimeConfig,
+    driver: &'_ dyn StorageDriver,
+    package: &Package,
+) -> Result<(String, Entry), Box<dyn Error>> {
+    let bucket = if config.bucket.is_none() {
+        return Result::Err(YankError::NoBucketDefined.into());
+    } else {
+        config.bucket.clone().unwrap()
+    };
+
+    let version = match &package.version {
+        PackageVersion::Literal(version) => version.clone(),
+        _ => return Result::Err(YankError::VersionRequired(package.to_string()).into()),
+    };
+
+    let index = Index::from_storage_bucket(driver, bucket.as_str()).await?;
+    let entry = index
+        .find(&package.name, &version)
+        .ok_or_else(|| YankError::PackageNotFound(package.to_string()))?;
+
+    Result::Ok((bucket, entry))
+}